@@ -1,43 +1,62 @@
-use std::cmp::Ordering;
+#[cfg(test)]
+use std::cell::Cell;
 
-use rand::Rng;
+use rand::RngCore;
+use rayon::prelude::*;
 
 use crate::scene::{
     bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
 };
 
-use super::ray::Ray;
+use super::{
+    ray::Ray,
+    surface_area_heuristic::{self, Primitive, Split},
+};
+
+/// How many objects a leaf may hold when `BvhNode::new` builds a tree.
+pub const DEFAULT_MAX_LEAF_SIZE: usize = 4;
+
+#[cfg(test)]
+thread_local! {
+    /// How many nodes have been visited on this thread, to compare trees.
+    static NODE_VISITS: Cell<u64> = const { Cell::new(0) };
+}
 
+/// A bounding volume hierarchy: a tree of boxes, so a ray only has to be
+/// tested against the objects in the boxes it goes through.
 pub struct BvhNode {
-    left: Option<Box<dyn Hittable>>,
-    right: Option<Box<dyn Hittable>>,
+    children: BvhChildren,
     bounding_box: AxisAlignedBoundingBox,
 }
 
+enum BvhChildren {
+    Leaf(Vec<Box<dyn Hittable>>),
+    Branch(Box<BvhNode>, Box<BvhNode>),
+}
+
 impl Hittable for BvhNode {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        #[cfg(test)]
+        NODE_VISITS.with(|visits| visits.set(visits.get() + 1));
+
         if !self.bounding_box.hit(ray, t_min, t_max) {
             return None;
         }
 
-        let left_hit = match &self.left {
-            Some(hittable) => hittable.hit(ray, t_min, t_max),
-            _ => None,
-        };
-        let left_time = match left_hit {
-            Some(rec) => rec.t,
-            None => t_max,
-        };
-
-        let right_hit = match &self.right {
-            Some(hittable) => hittable.hit(ray, t_min, left_time),
-            _ => None,
-        };
-
-        if right_hit.is_some() {
-            return right_hit;
+        match &self.children {
+            BvhChildren::Leaf(objects) => objects.hit(ray, t_min, t_max, generator),
+            BvhChildren::Branch(left, right) => {
+                let left_hit = left.hit(ray, t_min, t_max, generator);
+                let left_time = left_hit.as_ref().map_or(t_max, |record| record.t);
+                right.hit(ray, t_min, left_time, generator).or(left_hit)
+            }
         }
-        left_hit
     }
 
     fn bounding_box(&self, _time_0: f64, _time_1: f64) -> Option<AxisAlignedBoundingBox> {
@@ -46,85 +65,265 @@ impl Hittable for BvhNode {
 }
 
 impl BvhNode {
-    pub fn new(mut src_objects: Vec<Box<dyn Hittable>>, time_0: f64, time_1: f64) -> BvhNode {
-        if src_objects.len() == 0 {
+    /// Builds a tree with the surface area heuristic, with at most
+    /// `DEFAULT_MAX_LEAF_SIZE` objects per leaf.
+    pub fn new(objects: Vec<Box<dyn Hittable>>, time_0: f64, time_1: f64) -> BvhNode {
+        BvhNode::with_max_leaf_size(objects, time_0, time_1, DEFAULT_MAX_LEAF_SIZE)
+    }
+
+    /// Builds a tree with the surface area heuristic.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects` - What to put in the tree.
+    /// * `time_0` - When the shutter opens. Moving objects are bounded over
+    ///   the whole time the shutter is open.
+    /// * `time_1` - When the shutter closes.
+    /// * `max_leaf_size` - How many objects a leaf may hold. Smaller leaves
+    ///   are made when that is cheaper to trace.
+    ///
+    /// # Panics
+    ///
+    /// If there are no objects, or an object has no bounding box.
+    pub fn with_max_leaf_size(
+        objects: Vec<Box<dyn Hittable>>,
+        time_0: f64,
+        time_1: f64,
+        max_leaf_size: usize,
+    ) -> BvhNode {
+        if objects.is_empty() {
             panic!("Can't construct BVH node without anything in it.")
         }
 
-        let mut generator = rand::thread_rng();
-        let axis = generator.gen_range(0..2);
-
-        let (left, right) = if src_objects.len() == 1 {
-            // If we have one element, put it in the left
-            (Some(src_objects.remove(0)), None)
-        } else if src_objects.len() == 2 {
-            // If we have two elements, put one in each side
-            let compare_results = BvhNode::box_compare(&src_objects[0], &src_objects[1], axis);
-            let second_item = src_objects.remove(1);
-            let first_item = src_objects.remove(0);
-            if compare_results.is_lt() {
-                (Some(first_item), Some(second_item))
-            } else {
-                (Some(second_item), Some(first_item))
-            }
-        } else {
-            // If we have more than two elements, split the vector and call recursively
-            src_objects.sort_by(|left, right| BvhNode::box_compare(left, right, axis));
-
-            let mid = src_objects.len() / 2;
-            let right_objects = src_objects.split_off(mid);
-            let left_objects = src_objects;
-            (
-                Some(Box::new(BvhNode::new(left_objects, time_0, time_1)) as Box<dyn Hittable>),
-                Some(Box::new(BvhNode::new(right_objects, time_0, time_1)) as Box<dyn Hittable>),
-            )
-        };
+        let mut items: Vec<BvhItem> = objects
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bounding_box = object
+                    .bounding_box(time_0, time_1)
+                    .expect("Can't put an object without a bounding box in a BVH.");
+                BvhItem {
+                    primitive: Primitive::new(index, bounding_box),
+                    object: Some(object),
+                }
+            })
+            .collect();
+        BvhNode::build(&mut items, max_leaf_size)
+    }
 
-        let left_box = match &left {
-            Some(left) => left.bounding_box(time_0, time_1),
-            _ => None,
-        };
-        let right_box = match &right {
-            Some(right) => right.bounding_box(time_0, time_1),
-            _ => None,
-        };
-        let surrounding_box = match (left_box, right_box) {
-            (Some(left_box), Some(right_box)) => {
-                AxisAlignedBoundingBox::surrounding_box(&left_box, &right_box)
+    /// Builds the subtree for some of the objects, taking them. Large
+    /// subtrees build their two children on separate threads, which gives
+    /// the same tree as building them one after the other.
+    fn build(items: &mut [BvhItem], max_leaf_size: usize) -> BvhNode {
+        match surface_area_heuristic::split(items, max_leaf_size) {
+            Some(Split { middle, .. }) => {
+                let parallel = items.len() >= surface_area_heuristic::PARALLEL_THRESHOLD;
+                let (first, second) = items.split_at_mut(middle);
+                let (left, right) = if parallel {
+                    rayon::join(
+                        || BvhNode::build(first, max_leaf_size),
+                        || BvhNode::build(second, max_leaf_size),
+                    )
+                } else {
+                    (
+                        BvhNode::build(first, max_leaf_size),
+                        BvhNode::build(second, max_leaf_size),
+                    )
+                };
+                BvhNode {
+                    bounding_box: AxisAlignedBoundingBox::surrounding_box(
+                        &left.bounding_box,
+                        &right.bounding_box,
+                    ),
+                    children: BvhChildren::Branch(Box::new(left), Box::new(right)),
+                }
             }
-            (Some(left_box), None) => left_box,
-            (None, Some(right_box)) => right_box,
-            (None, None) => panic!("Tree didn't contain left or right node."),
-        };
-        BvhNode {
-            left,
-            right,
-            bounding_box: surrounding_box,
+            None => BvhNode {
+                bounding_box: surface_area_heuristic::surrounding_box(items),
+                children: BvhChildren::Leaf(
+                    items
+                        .iter_mut()
+                        .map(|item| item.object.take().unwrap())
+                        .collect(),
+                ),
+            },
         }
     }
+}
 
-    fn box_compare(a: &Box<dyn Hittable>, b: &Box<dyn Hittable>, axis: usize) -> Ordering {
-        let left_box = a.bounding_box(0.0, 0.0).unwrap();
-        let right_box = b.bounding_box(0.0, 0.0).unwrap();
+/// An object waiting to be put in a leaf, next to its box.
+struct BvhItem {
+    primitive: Primitive,
+    object: Option<Box<dyn Hittable>>,
+}
 
-        if left_box.minimum[axis] < right_box.minimum[axis] {
-            return Ordering::Less;
-        } else if left_box.minimum[axis] > right_box.minimum[axis] {
-            return Ordering::Greater;
-        }
-        Ordering::Equal
+impl AsRef<Primitive> for BvhItem {
+    fn as_ref(&self) -> &Primitive {
+        &self.primitive
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
     use crate::{
         geometry::vector_3d::Vector3D,
-        scene::{materials::Dielectric, sphere::Sphere},
+        scene::{materials::Dielectric, scene::Scene, sphere::Sphere, world},
+        util::{camera::Camera, random},
     };
 
     use super::*;
 
+    /// The builder this module used to have: the median split along a
+    /// random axis (never z), with one or two objects per leaf. Kept to
+    /// compare against. The axis comes from hashing the number of objects,
+    /// so builds are the same every run.
+    fn median_split(mut objects: Vec<Box<dyn Hittable>>, time_0: f64, time_1: f64) -> BvhNode {
+        let bounding_box = objects.bounding_box(time_0, time_1).unwrap();
+        if objects.len() <= 2 {
+            return BvhNode {
+                children: BvhChildren::Leaf(objects),
+                bounding_box,
+            };
+        }
+        let axis = (random::hash(&[objects.len() as u64]) % 2) as usize;
+        let minimum = |object: &dyn Hittable| object.bounding_box(0.0, 0.0).unwrap().minimum[axis];
+        objects.sort_by(|a, b| minimum(&**a).partial_cmp(&minimum(&**b)).unwrap());
+        let right = objects.split_off(objects.len() / 2);
+        BvhNode {
+            children: BvhChildren::Branch(
+                Box::new(median_split(objects, time_0, time_1)),
+                Box::new(median_split(right, time_0, time_1)),
+            ),
+            bounding_box,
+        }
+    }
+
+    /// A grid of small spheres, `side` along every axis, the same every time.
+    fn sphere_grid(side: u32) -> Vec<Box<dyn Hittable>> {
+        (0..side * side * side)
+            .map(|index| {
+                let center = Vector3D::new(
+                    f64::from(index % side),
+                    f64::from(index / side % side),
+                    f64::from(index / side / side),
+                );
+                Box::new(Sphere::new(center, 0.3, Box::new(Dielectric::new(1.5))))
+                    as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    /// Lists the boxes of the nodes of a tree, and the number of objects in
+    /// every leaf, from the root down.
+    fn shape(node: &BvhNode, shape: &mut Vec<(AxisAlignedBoundingBox, usize)>) {
+        match &node.children {
+            BvhChildren::Leaf(objects) => shape.push((node.bounding_box, objects.len())),
+            BvhChildren::Branch(left, right) => {
+                shape.push((node.bounding_box, 0));
+                self::shape(left, shape);
+                self::shape(right, shape);
+            }
+        }
+    }
+
+    #[test]
+    fn builds_the_same_on_any_number_of_threads() {
+        let build = |threads| {
+            let bvh = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| BvhNode::new(sphere_grid(20), 0.0, 1.0));
+            let mut nodes = vec![];
+            shape(&bvh, &mut nodes);
+            nodes
+        };
+        assert_eq!(build(1), build(8));
+    }
+
+    #[test]
+    fn finds_same_hits_as_list() {
+        let list = sphere_grid(10);
+        let mut generator = StdRng::seed_from_u64(0);
+        for max_leaf_size in [1, DEFAULT_MAX_LEAF_SIZE, 16] {
+            let bvh = BvhNode::with_max_leaf_size(sphere_grid(10), 0.0, 1.0, max_leaf_size);
+            for _ in 0..500 {
+                let origin = Vector3D::new(
+                    generator.gen_range(-5.0..15.0),
+                    generator.gen_range(-5.0..15.0),
+                    -5.0,
+                );
+                let target = Vector3D::new(
+                    generator.gen_range(0.0..9.0),
+                    generator.gen_range(0.0..9.0),
+                    generator.gen_range(0.0..9.0),
+                );
+                let ray = Ray::new(origin, target - origin, None);
+                let expected = list
+                    .hit(&ray, 0.001, f64::INFINITY, &mut generator)
+                    .map(|record| record.t);
+                let actual = bvh
+                    .hit(&ray, 0.001, f64::INFINITY, &mut generator)
+                    .map(|record| record.t);
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    /// Compares how many nodes camera rays visit in trees built for the same
+    /// random scenes by the median split and by the surface area heuristic. Run it
+    /// with `cargo test --release node_visits -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_node_visits_on_random_scene() {
+        const RESOLUTION: u32 = 200;
+        const SCENES: usize = 5;
+        let count_visits = |bvh: &BvhNode, camera: &Camera| {
+            NODE_VISITS.with(|visits| visits.set(0));
+            let generator = &mut StdRng::seed_from_u64(0);
+            for y in 0..RESOLUTION {
+                for x in 0..RESOLUTION {
+                    let scale = f64::from(RESOLUTION - 1);
+                    let lens = [generator.gen(), generator.gen()];
+                    let ray =
+                        camera.get_ray(f64::from(x) / scale, f64::from(y) / scale, lens, generator);
+                    bvh.hit(&ray, 0.001, f64::INFINITY, generator);
+                }
+            }
+            NODE_VISITS.with(Cell::get)
+        };
+
+        let camera = Scene::random_scene(3.0 / 2.0, &mut StdRng::seed_from_u64(0)).camera;
+        let rays = u64::from(RESOLUTION * RESOLUTION) * SCENES as u64;
+        let mut totals = vec![];
+        for (name, build) in [
+            ("median split", median_split as fn(_, _, _) -> BvhNode),
+            ("SAH", BvhNode::new),
+        ] {
+            let mut visits = 0;
+            let mut build_time = 0.0;
+            for scene in 0..SCENES {
+                let objects = world::random_scene(&mut StdRng::seed_from_u64(scene as u64));
+                let start = Instant::now();
+                let bvh = build(objects, 0.0, 1.0);
+                build_time += start.elapsed().as_secs_f64();
+                visits += count_visits(&bvh, &camera);
+            }
+            println!(
+                "{:>12}: {:6.2} nodes visited per ray, built in {:.2} ms",
+                name,
+                visits as f64 / rays as f64,
+                build_time * 1000.0 / SCENES as f64
+            );
+            totals.push(visits);
+        }
+        assert!(totals[1] < totals[0]);
+    }
+
     #[test]
     #[should_panic]
     fn cant_construct_empty_bvh() {
@@ -145,7 +344,9 @@ mod tests {
             Vector3D::new(0.0, 0.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_some());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_some());
     }
 
     /// Test that we can't a sphere that is in the direction of the ray, but outside the time
@@ -163,7 +364,9 @@ mod tests {
             Vector3D::new(0.0, 0.0, 1.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_none());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_none());
     }
 
     #[test]
@@ -197,28 +400,36 @@ mod tests {
             Vector3D::new(0.0, 4.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_some());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_some());
 
         let ray = Ray::new(
             Vector3D::new(0.0, 0.0, 0.0),
             Vector3D::new(0.0, -4.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_some());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_some());
 
         let ray = Ray::new(
             Vector3D::new(0.0, 0.0, 0.0),
             Vector3D::new(4.0, 0.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_some());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_some());
 
         let ray = Ray::new(
             Vector3D::new(0.0, 0.0, 0.0),
             Vector3D::new(-4.0, 0.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_some());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_some());
     }
 
     #[test]
@@ -241,6 +452,8 @@ mod tests {
             Vector3D::new(0.0, 0.0, 10.0),
             Some(0.0),
         );
-        assert!(bvh.hit(&ray, 0.0, 1.0).is_none());
+        assert!(bvh
+            .hit(&ray, 0.0, 1.0, &mut StdRng::seed_from_u64(0))
+            .is_none());
     }
 }
@@ -0,0 +1,445 @@
+use rand::RngCore;
+use rayon::prelude::*;
+
+use crate::scene::{
+    bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
+};
+
+use super::{
+    bounded_volume_hierarchy::DEFAULT_MAX_LEAF_SIZE,
+    ray::Ray,
+    surface_area_heuristic::{self, Primitive, Split},
+    vector_3d::Vector3D,
+};
+
+/// Deeper than this, nodes are split in half instead of by the surface area
+/// heuristic, which keeps any tree shallow enough for the traversal stack.
+const SURFACE_AREA_HEURISTIC_DEPTH: usize = 32;
+/// Room for one node per level: 32 levels split by the heuristic, then at
+/// most 32 more halving the remaining objects.
+const STACK_SIZE: usize = 64;
+
+/// One node of a `FlatBvh`. The first child of a branch directly follows it.
+#[derive(Debug, PartialEq)]
+struct FlatNode {
+    bounding_box: AxisAlignedBoundingBox,
+    /// For a leaf, the index of its first object. For a branch, the index of
+    /// its second child.
+    offset: u32,
+    /// How many objects a leaf holds, or 0 for a branch.
+    object_count: u32,
+    /// The axis a branch was split along. The first child is on its low side.
+    axis: u8,
+}
+
+impl FlatNode {
+    fn leaf(primitives: &[Primitive], first: usize) -> FlatNode {
+        FlatNode {
+            bounding_box: surface_area_heuristic::surrounding_box(primitives),
+            offset: first as u32,
+            object_count: primitives.len() as u32,
+            axis: 0,
+        }
+    }
+
+    /// Turns a leaf in to a branch, whose first child follows it.
+    fn make_branch(&mut self, second_child: usize, axis: usize) {
+        self.offset = second_child as u32;
+        self.object_count = 0;
+        self.axis = axis as u8;
+    }
+
+    /// The same node, moved `distance` further along in the list of nodes
+    /// together with its children.
+    fn moved(mut self, distance: usize) -> FlatNode {
+        if self.object_count == 0 {
+            self.offset += distance as u32;
+        }
+        self
+    }
+}
+
+/// A bounding volume hierarchy laid out in one array, in depth first order,
+/// with the objects of every leaf next to each other.
+///
+/// Compared to `BvhNode` there is no call through a pointer for each node,
+/// and the nodes a ray visits are close together in memory. Children are
+/// visited nearest first, so the far child can often be skipped.
+pub struct FlatBvh {
+    nodes: Vec<FlatNode>,
+    objects: Vec<Box<dyn Hittable>>,
+}
+
+impl FlatBvh {
+    /// Builds a hierarchy with at most `DEFAULT_MAX_LEAF_SIZE` objects per
+    /// leaf.
+    pub fn new(objects: Vec<Box<dyn Hittable>>, time_0: f64, time_1: f64) -> FlatBvh {
+        FlatBvh::with_max_leaf_size(objects, time_0, time_1, DEFAULT_MAX_LEAF_SIZE)
+    }
+
+    /// Builds a hierarchy with the surface area heuristic.
+    ///
+    /// # Arguments
+    ///
+    /// * `objects` - What to put in the hierarchy.
+    /// * `time_0` - When the shutter opens. Moving objects are bounded over
+    ///   the whole time the shutter is open.
+    /// * `time_1` - When the shutter closes.
+    /// * `max_leaf_size` - How many objects a leaf may hold. Smaller leaves
+    ///   are made when that is cheaper to trace.
+    ///
+    /// # Panics
+    ///
+    /// If there are no objects, more than `u32::MAX` of them, or an object
+    /// has no bounding box.
+    pub fn with_max_leaf_size(
+        objects: Vec<Box<dyn Hittable>>,
+        time_0: f64,
+        time_1: f64,
+        max_leaf_size: usize,
+    ) -> FlatBvh {
+        if objects.is_empty() {
+            panic!("Can't construct BVH without anything in it.")
+        }
+        if objects.len() > u32::MAX as usize {
+            panic!("Can't construct BVH with {} objects.", objects.len())
+        }
+
+        let mut primitives: Vec<Primitive> = objects
+            .par_iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bounding_box = object
+                    .bounding_box(time_0, time_1)
+                    .expect("Can't put an object without a bounding box in a BVH.");
+                Primitive::new(index, bounding_box)
+            })
+            .collect();
+        let nodes = FlatBvh::build_parallel(&mut primitives, 0, 0, max_leaf_size);
+
+        // The primitives are now in the order the leaves refer to them.
+        let mut objects: Vec<Option<Box<dyn Hittable>>> = objects.into_iter().map(Some).collect();
+        let objects = primitives
+            .iter()
+            .map(|primitive| objects[primitive.index].take().unwrap())
+            .collect();
+        FlatBvh { nodes, objects }
+    }
+
+    /// Splits the primitives of a node at the given depth, or returns `None`
+    /// if it should be a leaf.
+    fn split(primitives: &mut [Primitive], depth: usize, max_leaf_size: usize) -> Option<Split> {
+        if depth < SURFACE_AREA_HEURISTIC_DEPTH {
+            surface_area_heuristic::split(primitives, max_leaf_size)
+        } else {
+            surface_area_heuristic::split_in_half(primitives, max_leaf_size)
+        }
+    }
+
+    /// Adds the nodes for some of the primitives, and returns the index of the
+    /// first one.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - Where the primitives start in the list of all of them.
+    /// * `depth` - How many nodes are above these.
+    fn build(
+        nodes: &mut Vec<FlatNode>,
+        primitives: &mut [Primitive],
+        first: usize,
+        depth: usize,
+        max_leaf_size: usize,
+    ) -> usize {
+        let index = nodes.len();
+        nodes.push(FlatNode::leaf(primitives, first));
+        if let Some(Split { middle, axis }) = FlatBvh::split(primitives, depth, max_leaf_size) {
+            let (first_half, second_half) = primitives.split_at_mut(middle);
+            FlatBvh::build(nodes, first_half, first, depth + 1, max_leaf_size);
+            let second =
+                FlatBvh::build(nodes, second_half, first + middle, depth + 1, max_leaf_size);
+            nodes[index].make_branch(second, axis);
+        }
+        index
+    }
+
+    /// Returns the nodes `build` would add for some of the primitives, with
+    /// children counted from the first of them. Large subtrees build their
+    /// two children on separate threads, and are joined afterwards.
+    fn build_parallel(
+        primitives: &mut [Primitive],
+        first: usize,
+        depth: usize,
+        max_leaf_size: usize,
+    ) -> Vec<FlatNode> {
+        let mut nodes = vec![];
+        if primitives.len() < surface_area_heuristic::PARALLEL_THRESHOLD {
+            FlatBvh::build(&mut nodes, primitives, first, depth, max_leaf_size);
+            return nodes;
+        }
+
+        nodes.push(FlatNode::leaf(primitives, first));
+        if let Some(Split { middle, axis }) = FlatBvh::split(primitives, depth, max_leaf_size) {
+            let (first_half, second_half) = primitives.split_at_mut(middle);
+            let (first_nodes, second_nodes) = rayon::join(
+                || FlatBvh::build_parallel(first_half, first, depth + 1, max_leaf_size),
+                || FlatBvh::build_parallel(second_half, first + middle, depth + 1, max_leaf_size),
+            );
+            let second = 1 + first_nodes.len();
+            nodes[0].make_branch(second, axis);
+            nodes.reserve(first_nodes.len() + second_nodes.len());
+            nodes.extend(first_nodes.into_iter().map(|node| node.moved(1)));
+            nodes.extend(second_nodes.into_iter().map(|node| node.moved(second)));
+        }
+        nodes
+    }
+}
+
+impl Hittable for FlatBvh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        let inverse_direction = Vector3D::new(
+            1.0 / ray.direction.x(),
+            1.0 / ray.direction.y(),
+            1.0 / ray.direction.z(),
+        );
+        let mut closest_so_far = t_max;
+        let mut closest_record = None;
+
+        let mut stack = [0; STACK_SIZE];
+        let mut stack_size = 0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            if node
+                .bounding_box
+                .hit_inverse(ray, &inverse_direction, t_min, closest_so_far)
+            {
+                if node.object_count > 0 {
+                    let first = node.offset as usize;
+                    let last = first + node.object_count as usize;
+                    for object in &self.objects[first..last] {
+                        if let Some(record) = object.hit(ray, t_min, closest_so_far, generator) {
+                            closest_so_far = record.t;
+                            closest_record = Some(record);
+                        }
+                    }
+                } else {
+                    // Visit the child on the side the ray comes from first, so
+                    // a hit there can rule out the other one.
+                    let (near, far) = if ray.direction[usize::from(node.axis)] < 0.0 {
+                        (node.offset as usize, index + 1)
+                    } else {
+                        (index + 1, node.offset as usize)
+                    };
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    index = near;
+                    continue;
+                }
+            }
+            if stack_size == 0 {
+                break;
+            }
+            stack_size -= 1;
+            index = stack[stack_size];
+        }
+        closest_record
+    }
+
+    fn bounding_box(&self, _time_0: f64, _time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        Some(self.nodes[0].bounding_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{
+        geometry::bounded_volume_hierarchy::BvhNode,
+        scene::{materials::Dielectric, scene::Scene, sphere::Sphere, world},
+    };
+
+    use super::*;
+
+    /// A 10x10x10 grid of small spheres, the same every time.
+    fn sphere_grid() -> Vec<Box<dyn Hittable>> {
+        (0..1000)
+            .map(|index| {
+                let center = Vector3D::new(
+                    f64::from(index % 10),
+                    f64::from(index / 10 % 10),
+                    f64::from(index / 100),
+                );
+                Box::new(Sphere::new(center, 0.3, Box::new(Dielectric::new(1.5))))
+                    as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    /// A 20x20x20 grid of spheres of different sizes, the same every time,
+    /// with enough of them to be built on several threads.
+    fn large_sphere_grid() -> Vec<Box<dyn Hittable>> {
+        (0..8000)
+            .map(|index| {
+                let center = Vector3D::new(
+                    f64::from(index % 20),
+                    f64::from(index / 20 % 20),
+                    f64::from(index / 400),
+                );
+                let radius = 0.1 + 0.04 * f64::from(index * 7 % 10);
+                Box::new(Sphere::new(center, radius, Box::new(Dielectric::new(1.5))))
+                    as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    fn depth(bvh: &FlatBvh, index: usize) -> usize {
+        let node = &bvh.nodes[index];
+        if node.object_count > 0 {
+            1
+        } else {
+            1 + depth(bvh, index + 1).max(depth(bvh, node.offset as usize))
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_construct_empty_bvh() {
+        FlatBvh::new(vec![], 0.0, 1.0);
+    }
+
+    #[test]
+    fn finds_same_hits_as_list() {
+        let list = sphere_grid();
+        let mut generator = StdRng::seed_from_u64(0);
+        for max_leaf_size in [1, DEFAULT_MAX_LEAF_SIZE, 16] {
+            let bvh = FlatBvh::with_max_leaf_size(sphere_grid(), 0.0, 1.0, max_leaf_size);
+            assert_eq!(1000, bvh.objects.len());
+            for _ in 0..500 {
+                // From every side, so both child orders are used.
+                let origin = Vector3D::new(
+                    generator.gen_range(-10.0..20.0),
+                    generator.gen_range(-10.0..20.0),
+                    generator.gen_range(-10.0..20.0),
+                );
+                let target = Vector3D::new(
+                    generator.gen_range(0.0..9.0),
+                    generator.gen_range(0.0..9.0),
+                    generator.gen_range(0.0..9.0),
+                );
+                let ray = Ray::new(origin, target - origin, None);
+                let expected = list
+                    .hit(&ray, 0.001, f64::INFINITY, &mut generator)
+                    .map(|record| record.t);
+                let actual = bvh
+                    .hit(&ray, 0.001, f64::INFINITY, &mut generator)
+                    .map(|record| record.t);
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn builds_the_same_on_any_number_of_threads() {
+        let build = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| FlatBvh::new(large_sphere_grid(), 0.0, 1.0))
+        };
+        let single = build(1);
+        let several = build(8);
+        assert_eq!(single.nodes, several.nodes);
+        let boxes = |bvh: &FlatBvh| {
+            bvh.objects
+                .iter()
+                .map(|object| object.bounding_box(0.0, 1.0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(boxes(&single), boxes(&several));
+
+        // And the same as building it without joining subtrees.
+        let objects = large_sphere_grid();
+        let mut primitives: Vec<Primitive> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| Primitive::new(index, object.bounding_box(0.0, 1.0).unwrap()))
+            .collect();
+        let mut nodes = vec![];
+        FlatBvh::build(&mut nodes, &mut primitives, 0, 0, DEFAULT_MAX_LEAF_SIZE);
+        assert_eq!(nodes, several.nodes);
+    }
+
+    #[test]
+    fn stays_shallow_for_badly_spread_objects() {
+        // Centers that grow exponentially put almost everything in the first
+        // bin, so the heuristic only splits off a few objects per level.
+        let objects: Vec<Box<dyn Hittable>> = (0..1000)
+            .map(|index| {
+                let center = Vector3D::new(1.05f64.powi(index), 0.0, 0.0);
+                Box::new(Sphere::new(center, 0.1, Box::new(Dielectric::new(1.5))))
+                    as Box<dyn Hittable>
+            })
+            .collect();
+        let bvh = FlatBvh::with_max_leaf_size(objects, 0.0, 1.0, 1);
+        assert!(depth(&bvh, 0) <= STACK_SIZE);
+
+        let ray = Ray::new(
+            Vector3D::new(-1.0, 0.0, 0.0),
+            Vector3D::new(1.0, 0.0, 0.0),
+            None,
+        );
+        let record = bvh
+            .hit(&ray, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert!((record.t - 1.9).abs() < 1e-9);
+    }
+
+    /// Compares how long camera rays through the same random scenes take to
+    /// trace with `BvhNode` and with `FlatBvh`. Run it with
+    /// `cargo test --release flat_bvh -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_flat_bvh_on_random_scene() {
+        const RESOLUTION: u32 = 400;
+        const SCENES: usize = 5;
+        let camera = Scene::random_scene(3.0 / 2.0, &mut StdRng::seed_from_u64(0)).camera;
+        let generator = &mut StdRng::seed_from_u64(0);
+        let rays = (0..RESOLUTION * RESOLUTION)
+            .map(|index| {
+                let scale = f64::from(RESOLUTION - 1);
+                let (x, y) = (index % RESOLUTION, index / RESOLUTION);
+                let lens = [generator.gen(), generator.gen()];
+                camera.get_ray(f64::from(x) / scale, f64::from(y) / scale, lens, generator)
+            })
+            .collect::<Vec<Ray>>();
+        let time = |bvh: &dyn Hittable| {
+            let generator = &mut StdRng::seed_from_u64(0);
+            let start = Instant::now();
+            for ray in &rays {
+                bvh.hit(ray, 0.001, f64::INFINITY, generator);
+            }
+            start.elapsed().as_secs_f64()
+        };
+
+        let (mut tree_time, mut flat_time) = (0.0, 0.0);
+        for scene in 0..SCENES {
+            let objects = || world::random_scene(&mut StdRng::seed_from_u64(scene as u64));
+            tree_time += time(&BvhNode::new(objects(), 0.0, 1.0));
+            flat_time += time(&FlatBvh::new(objects(), 0.0, 1.0));
+        }
+        let per_ray = |elapsed: f64| elapsed * 1e9 / (rays.len() * SCENES) as f64;
+        println!("BvhNode: {:6.1} ns per ray", per_ray(tree_time));
+        println!("FlatBvh: {:6.1} ns per ray", per_ray(flat_time));
+        assert!(flat_time < tree_time);
+    }
+}
@@ -1,3 +1,6 @@
 pub mod bounded_volume_hierarchy;
+pub mod flat_bvh;
 pub mod ray;
+pub mod surface_area_heuristic;
+pub mod transform;
 pub mod vector_3d;
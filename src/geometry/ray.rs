@@ -19,7 +19,7 @@ impl Ray {
     }
 
     pub fn at(&self, t: f64) -> Point3D {
-        self.origin + (t * &self.direction)
+        self.origin + (t * self.direction)
     }
 }
 
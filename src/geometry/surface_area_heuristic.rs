@@ -0,0 +1,300 @@
+//! Choosing where to split a set of primitives when building a bounding
+//! volume hierarchy.
+//!
+//! The surface area heuristic estimates how expensive a split is to trace a
+//! ray through. The chance that a ray which hits a node also hits one of its
+//! children is about the surface area of the child divided by that of the
+//! node, so a split is cheap when it puts many primitives in small boxes.
+//! Rather than trying every possible plane, the primitives are sorted in to
+//! a few bins along each axis by the centers of their boxes, and only the
+//! planes between bins are tried.
+//!
+//! The functions here take anything that holds a `Primitive`, so builders
+//! can keep their objects next to the boxes while they are reordered.
+
+use rayon::prelude::*;
+
+use crate::{scene::bounding_box::AxisAlignedBoundingBox, util::point::Point3D};
+
+const BIN_COUNT: usize = 12;
+/// The cost of visiting a node, relative to intersecting a primitive.
+const TRAVERSAL_COST: f64 = 1.0;
+/// From how many primitives on work is worth spreading over threads, both
+/// when binning and when builders build the two children of a node.
+pub const PARALLEL_THRESHOLD: usize = 4096;
+
+/// A primitive to be placed in a hierarchy.
+#[derive(Debug, Clone, Copy)]
+pub struct Primitive {
+    /// Where the primitive is in the list it came from.
+    pub index: usize,
+    pub bounding_box: AxisAlignedBoundingBox,
+    centroid: Point3D,
+}
+
+impl Primitive {
+    pub fn new(index: usize, bounding_box: AxisAlignedBoundingBox) -> Primitive {
+        Primitive {
+            index,
+            bounding_box,
+            centroid: bounding_box.centroid(),
+        }
+    }
+}
+
+impl AsRef<Primitive> for Primitive {
+    fn as_ref(&self) -> &Primitive {
+        self
+    }
+}
+
+/// The box around all of the primitives.
+///
+/// # Panics
+///
+/// If there are no primitives.
+pub fn surrounding_box<T: AsRef<Primitive>>(items: &[T]) -> AxisAlignedBoundingBox {
+    items
+        .iter()
+        .map(|item| item.as_ref().bounding_box)
+        .reduce(|box_0, box_1| AxisAlignedBoundingBox::surrounding_box(&box_0, &box_1))
+        .expect("Can't find the box around no primitives.")
+}
+
+/// Where a set of primitives was split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Split {
+    /// How many primitives go in the first child.
+    pub middle: usize,
+    /// The axis the primitives were split along. Those in the first child
+    /// are on the low side.
+    pub axis: usize,
+}
+
+/// The primitives whose centers fall in one bin.
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    count: usize,
+    bounding_box: Option<AxisAlignedBoundingBox>,
+}
+
+impl Bin {
+    fn add(&mut self, count: usize, bounding_box: &AxisAlignedBoundingBox) {
+        self.count += count;
+        self.bounding_box = Some(match &self.bounding_box {
+            Some(own) => AxisAlignedBoundingBox::surrounding_box(own, bounding_box),
+            None => *bounding_box,
+        });
+    }
+
+    fn merge(&mut self, other: &Bin) {
+        if let Some(bounding_box) = &other.bounding_box {
+            self.add(other.count, bounding_box);
+        }
+    }
+
+    /// The number of primitives times their surface area, which is what
+    /// intersecting them costs relative to the parent.
+    fn cost(&self) -> f64 {
+        self.bounding_box
+            .map_or(0.0, |bounding_box| bounding_box.surface_area())
+            * self.count as f64
+    }
+}
+
+fn bin_index(value: f64, low: f64, high: f64) -> usize {
+    (((value - low) / (high - low) * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+}
+
+/// Sorts the primitives in to bins along an axis. Large sets are binned on
+/// several threads. Merging bins only takes minimums, maximums and sums of
+/// whole numbers, so the result is the same however the work was divided.
+fn fill_bins<T: AsRef<Primitive> + Sync>(
+    items: &[T],
+    axis: usize,
+    low: f64,
+    high: f64,
+) -> [Bin; BIN_COUNT] {
+    let add = |mut bins: [Bin; BIN_COUNT], item: &T| {
+        let primitive = item.as_ref();
+        bins[bin_index(primitive.centroid[axis], low, high)].add(1, &primitive.bounding_box);
+        bins
+    };
+    if items.len() < PARALLEL_THRESHOLD {
+        return items.iter().fold([Bin::default(); BIN_COUNT], add);
+    }
+    items
+        .par_iter()
+        .fold(|| [Bin::default(); BIN_COUNT], add)
+        .reduce(
+            || [Bin::default(); BIN_COUNT],
+            |mut bins, other| {
+                for (bin, other) in bins.iter_mut().zip(&other) {
+                    bin.merge(other);
+                }
+                bins
+            },
+        )
+}
+
+/// Reorders the primitives so that those that go in the first child come
+/// first, and returns where they were split.
+///
+/// Returns `None` if the primitives should stay together in a leaf: when
+/// there are at most `max_leaf_size` of them and no split is cheaper than
+/// intersecting all of them. Larger sets are always split, even if all of
+/// their centers are in the same place.
+pub fn split<T: AsRef<Primitive> + Sync>(items: &mut [T], max_leaf_size: usize) -> Option<Split> {
+    let count = items.len();
+    if count <= 1 {
+        return None;
+    }
+    let area = surrounding_box(items).surface_area();
+    let (low, high) = centroid_bounds(items);
+
+    // The cheapest split: its cost, axis, and the last bin on the first side.
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in (0..3).filter(|&axis| high[axis] > low[axis]) {
+        let bins = fill_bins(items, axis, low[axis], high[axis]);
+
+        // Sweep from the end to find the cost of every second side, then from
+        // the start to find that of the first side.
+        let mut second_sides = [Bin::default(); BIN_COUNT];
+        let mut second_side = Bin::default();
+        for index in (1..BIN_COUNT).rev() {
+            second_side.merge(&bins[index]);
+            second_sides[index] = second_side;
+        }
+        let mut first_side = Bin::default();
+        for index in 0..BIN_COUNT - 1 {
+            first_side.merge(&bins[index]);
+            let second_side = &second_sides[index + 1];
+            if first_side.count == 0 || second_side.count == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST + (first_side.cost() + second_side.cost()) / area;
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, index));
+            }
+        }
+    }
+
+    match best {
+        Some((cost, _, _)) if count <= max_leaf_size && cost >= count as f64 => None,
+        Some((_, axis, last_bin)) => {
+            let mut middle = 0;
+            for index in 0..count {
+                let centroid = items[index].as_ref().centroid[axis];
+                if bin_index(centroid, low[axis], high[axis]) <= last_bin {
+                    items.swap(index, middle);
+                    middle += 1;
+                }
+            }
+            Some(Split { middle, axis })
+        }
+        // All of the centers are in the same place, so any split is as good
+        // as another.
+        None if count > max_leaf_size => Some(Split {
+            middle: count / 2,
+            axis: 0,
+        }),
+        None => None,
+    }
+}
+
+/// Splits the primitives in to two halves along the axis where their centers
+/// are furthest apart, or returns `None` if there are at most
+/// `max_leaf_size` of them. Unlike `split`, this never makes a tree deeper
+/// than it has to be.
+pub fn split_in_half<T: AsRef<Primitive>>(items: &mut [T], max_leaf_size: usize) -> Option<Split> {
+    let count = items.len();
+    if count <= max_leaf_size.max(1) {
+        return None;
+    }
+    let (low, high) = centroid_bounds(items);
+    let size = high - low;
+    let axis = (0..3)
+        .reduce(|best, axis| if size[axis] > size[best] { axis } else { best })
+        .unwrap();
+    let middle = count / 2;
+    items.select_nth_unstable_by(middle, |a, b| {
+        a.as_ref().centroid[axis].total_cmp(&b.as_ref().centroid[axis])
+    });
+    Some(Split { middle, axis })
+}
+
+/// The lowest and highest centers of the primitives along every axis.
+fn centroid_bounds<T: AsRef<Primitive>>(items: &[T]) -> (Point3D, Point3D) {
+    let mut low = Point3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut high = -low;
+    for item in items {
+        let centroid = item.as_ref().centroid;
+        for axis in 0..3 {
+            low[axis] = low[axis].min(centroid[axis]);
+            high[axis] = high[axis].max(centroid[axis]);
+        }
+    }
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn primitives(centers: &[f64], half_size: f64) -> Vec<Primitive> {
+        centers
+            .iter()
+            .enumerate()
+            .map(|(index, &x)| {
+                let size = Point3D::new(half_size, half_size, half_size);
+                let center = Point3D::new(x, 0.0, 0.0);
+                Primitive::new(
+                    index,
+                    AxisAlignedBoundingBox::new(center - size, center + size),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_between_clusters() {
+        let mut primitives = primitives(&[0.0, 100.0, 1.0, 101.0, 2.0, 102.0], 0.5);
+        let Split { middle, axis } = split(&mut primitives, 1).unwrap();
+        assert_eq!((3, 0), (middle, axis));
+        let mut first: Vec<usize> = primitives[..middle]
+            .iter()
+            .map(|primitive| primitive.index)
+            .collect();
+        first.sort_unstable();
+        assert_eq!(vec![0, 2, 4], first);
+    }
+
+    #[test]
+    fn keeps_overlapping_primitives_together() {
+        // Big boxes that almost completely overlap are cheaper to test
+        // together than to put behind another node.
+        let mut primitives = primitives(&[0.0, 0.1, 0.2], 10.0);
+        assert_eq!(None, split(&mut primitives, 4));
+        // Unless there are too many for a leaf.
+        assert!(split(&mut primitives, 2).is_some());
+    }
+
+    #[test]
+    fn splits_primitives_in_the_same_place() {
+        let mut primitives = primitives(&[5.0; 6], 1.0);
+        assert_eq!(3, split(&mut primitives, 4).unwrap().middle);
+        assert_eq!(None, split(&mut primitives[..4], 4));
+        assert_eq!(None, split(&mut primitives[..1], 0));
+    }
+
+    #[test]
+    fn halves_along_longest_axis() {
+        let mut primitives = primitives(&[9.0, 3.0, 7.0, 1.0, 5.0], 10.0);
+        let Split { middle, axis } = split_in_half(&mut primitives, 1).unwrap();
+        assert_eq!((2, 0), (middle, axis));
+        assert!(primitives[..2]
+            .iter()
+            .all(|primitive| primitive.centroid.x() < 4.0));
+        assert_eq!(None, split_in_half(&mut primitives, 5));
+    }
+}
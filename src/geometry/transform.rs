@@ -0,0 +1,264 @@
+use crate::util::point::Point3D;
+
+use super::vector_3d::{self, Vector3D};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+fn multiply(first: &Matrix, second: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..4).map(|i| first[row][i] * second[i][column]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for (row, values) in matrix.iter().enumerate() {
+        for (column, value) in values.iter().enumerate() {
+            result[column][row] = *value;
+        }
+    }
+    result
+}
+
+/// An affine transformation of 3D space, stored as a 4x4 matrix together with
+/// its inverse.
+///
+/// Transforms are built from translations, rotations and scalings, each of
+/// which has a known inverse, so no general matrix inversion is needed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    /// Returns the transform that leaves everything where it is.
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    /// Returns a transform moving everything by `offset`.
+    pub fn translation(offset: Vector3D) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Returns a transform scaling everything away from the origin, by a
+    /// separate factor for each axis. Negative factors mirror along the axis.
+    ///
+    /// # Panics
+    ///
+    /// If any of the factors is zero, as that flattens space and can't be
+    /// undone.
+    pub fn scaling(factors: Vector3D) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            if factors[axis] == 0.0 {
+                panic!("Can't scale by zero along an axis.")
+            }
+            matrix[axis][axis] = factors[axis];
+            inverse[axis][axis] = 1.0 / factors[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Returns a transform rotating everything counter-clockwise around an
+    /// axis through the origin, when looking down the axis towards the
+    /// origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - Direction of the axis to rotate around, of any length.
+    /// * `degrees` - How far to rotate.
+    pub fn rotation(axis: Vector3D, degrees: f64) -> Transform {
+        let axis = vector_3d::unit_vector(&axis);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (x, y, z) = (axis.x(), axis.y(), axis.z());
+        let one_minus_cos = 1.0 - cos;
+
+        // Rodrigues' rotation formula as a matrix.
+        let matrix = [
+            [
+                cos + x * x * one_minus_cos,
+                x * y * one_minus_cos - z * sin,
+                x * z * one_minus_cos + y * sin,
+                0.0,
+            ],
+            [
+                y * x * one_minus_cos + z * sin,
+                cos + y * y * one_minus_cos,
+                y * z * one_minus_cos - x * sin,
+                0.0,
+            ],
+            [
+                z * x * one_minus_cos - y * sin,
+                z * y * one_minus_cos + x * sin,
+                cos + z * z * one_minus_cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        // Rotations are orthogonal, so the inverse is the transpose.
+        Transform {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    /// Returns a transform that applies this one first, and then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    /// Returns the transform that undoes this one.
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// The 4x4 matrix of the transform, in row-major order.
+    pub fn matrix(&self) -> [[f64; 4]; 4] {
+        self.matrix
+    }
+
+    /// Transforms a point, which is affected by translation.
+    pub fn point(&self, point: &Point3D) -> Point3D {
+        let m = &self.matrix;
+        Point3D::new(
+            m[0][0] * point.x() + m[0][1] * point.y() + m[0][2] * point.z() + m[0][3],
+            m[1][0] * point.x() + m[1][1] * point.y() + m[1][2] * point.z() + m[1][3],
+            m[2][0] * point.x() + m[2][1] * point.y() + m[2][2] * point.z() + m[2][3],
+        )
+    }
+
+    /// Transforms a direction, which isn't affected by translation.
+    pub fn vector(&self, vector: &Vector3D) -> Vector3D {
+        let m = &self.matrix;
+        Vector3D::new(
+            m[0][0] * vector.x() + m[0][1] * vector.y() + m[0][2] * vector.z(),
+            m[1][0] * vector.x() + m[1][1] * vector.y() + m[1][2] * vector.z(),
+            m[2][0] * vector.x() + m[2][1] * vector.y() + m[2][2] * vector.z(),
+        )
+    }
+
+    /// Transforms a surface normal, so it stays perpendicular to the
+    /// transformed surface. This uses the inverse-transpose of the matrix,
+    /// which differs from [`Transform::vector`] once scaling isn't uniform.
+    ///
+    /// The result isn't normalized.
+    pub fn normal(&self, normal: &Vector3D) -> Vector3D {
+        let m = &self.inverse;
+        Vector3D::new(
+            m[0][0] * normal.x() + m[1][0] * normal.y() + m[2][0] * normal.z(),
+            m[0][1] * normal.x() + m[1][1] * normal.y() + m[2][1] * normal.z(),
+            m[0][2] * normal.x() + m[1][2] * normal.y() + m[2][2] * normal.z(),
+        )
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn transform_and_inverse_cancel_out() {
+        let transform = Transform::scaling(Vector3D::new(2.0, -1.0, 0.5))
+            .then(&Transform::rotation(Vector3D::new(1.0, 1.0, 0.0), 33.0))
+            .then(&Transform::translation(Vector3D::new(3.0, 4.0, 5.0)));
+        let product = multiply(&transform.matrix, &transform.inverse);
+        for (row, values) in product.iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                let expected = if row == column { 1.0 } else { 0.0 };
+                assert_relative_eq!(expected, *value, epsilon = 1e-12);
+            }
+        }
+
+        let point = Point3D::new(-1.0, 2.0, 0.25);
+        let round_trip = transform.inverse().point(&transform.point(&point));
+        assert_relative_eq!(point, round_trip, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn translation_moves_points_but_not_vectors() {
+        let transform = Transform::translation(Vector3D::new(1.0, 2.0, 3.0));
+        let v = Vector3D::new(1.0, 0.0, 0.0);
+        assert_relative_eq!(Point3D::new(2.0, 2.0, 3.0), transform.point(&v));
+        assert_relative_eq!(v, transform.vector(&v));
+    }
+
+    #[test]
+    fn rotation_is_counter_clockwise() {
+        let transform = Transform::rotation(Vector3D::new(0.0, 0.0, 2.0), 90.0);
+        let rotated = transform.vector(&Vector3D::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(Vector3D::new(0.0, 1.0, 0.0), rotated, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn transforms_apply_in_order() {
+        let scale = Transform::scaling(Vector3D::new(2.0, 2.0, 2.0));
+        let translate = Transform::translation(Vector3D::new(1.0, 0.0, 0.0));
+        let point = Point3D::new(1.0, 1.0, 1.0);
+        assert_relative_eq!(
+            Point3D::new(3.0, 2.0, 2.0),
+            scale.then(&translate).point(&point)
+        );
+        assert_relative_eq!(
+            Point3D::new(4.0, 2.0, 2.0),
+            translate.then(&scale).point(&point)
+        );
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scaling() {
+        let transform = Transform::scaling(Vector3D::new(4.0, 1.0, 1.0));
+        // A surface along (1, 1, 0) with a normal of (1, -1, 0).
+        let tangent = transform.vector(&Vector3D::new(1.0, 1.0, 0.0));
+        let normal = transform.normal(&Vector3D::new(1.0, -1.0, 0.0));
+        assert_relative_eq!(0.0, tangent.dot(&normal));
+        assert!(
+            transform
+                .vector(&Vector3D::new(1.0, -1.0, 0.0))
+                .dot(&tangent)
+                != 0.0
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_scale_by_zero() {
+        Transform::scaling(Vector3D::new(1.0, 0.0, 1.0));
+    }
+}
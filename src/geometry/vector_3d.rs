@@ -29,8 +29,7 @@ impl Vector3D {
     }
 
     /// Generate a random vector in a cube.
-    pub fn random(min: f64, max: f64) -> Vector3D {
-        let mut generator = rand::thread_rng();
+    pub fn random<R: Rng + ?Sized>(min: f64, max: f64, generator: &mut R) -> Vector3D {
         Vector3D {
             x: generator.gen_range(min..max),
             y: generator.gen_range(min..max),
@@ -39,14 +38,14 @@ impl Vector3D {
     }
 
     /// Generate a random vector in a cube spanning from 0 to 1.
-    pub fn all_random() -> Vector3D {
-        Vector3D::random(0.0, 1.0)
+    pub fn all_random<R: Rng + ?Sized>(generator: &mut R) -> Vector3D {
+        Vector3D::random(0.0, 1.0, generator)
     }
 
     /// Generate a random vector in a sphere.
-    pub fn random_in_unit_sphere() -> Vector3D {
+    pub fn random_in_unit_sphere<R: Rng + ?Sized>(generator: &mut R) -> Vector3D {
         loop {
-            let vec = Vector3D::random(-1.0, 1.0);
+            let vec = Vector3D::random(-1.0, 1.0, generator);
             if vec.length_squared() >= 1.0 {
                 continue;
             }
@@ -54,24 +53,41 @@ impl Vector3D {
         }
     }
 
-    pub fn random_unit_vector() -> Vector3D {
-        unit_vector(&Vector3D::random_in_unit_sphere())
+    pub fn random_unit_vector<R: Rng + ?Sized>(generator: &mut R) -> Vector3D {
+        unit_vector(&Vector3D::random_in_unit_sphere(generator))
     }
 
-    /// Generate a random vector in a circle.
-    pub fn random_in_unit_disk() -> Vector3D {
-        loop {
-            let mut generator = rand::thread_rng();
-            let random_vector = Vector3D::new(
-                generator.gen_range(-1.0..1.0),
-                generator.gen_range(-1.0..1.1),
-                0.0,
-            );
-            if random_vector.length_squared() >= 1.0 {
-                continue;
-            }
-            return random_vector;
+    /// Generate a random unit vector above the xy plane, more likely the
+    /// closer it is to the z axis: the density is the cosine of the angle to
+    /// the z axis over pi. Turn it around a normal with `OrthonormalBasis`.
+    pub fn random_cosine_direction<R: Rng + ?Sized>(generator: &mut R) -> Vector3D {
+        let (r_1, r_2): (f64, f64) = (generator.gen(), generator.gen());
+        let phi = 2.0 * std::f64::consts::PI * r_1;
+        let radius = r_2.sqrt();
+        Vector3D::new(phi.cos() * radius, phi.sin() * radius, (1.0 - r_2).sqrt())
+    }
+
+    /// Maps two numbers from 0 up to 1 to a vector in a circle, spreading
+    /// them evenly over it. Nearby numbers give nearby points, so numbers
+    /// that are well spread over the square stay well spread over the circle.
+    ///
+    /// This is Shirley and Chiu's concentric mapping, which takes squares
+    /// around the center of the square to circles around the center of the
+    /// circle.
+    pub fn in_unit_disk(u: f64, v: f64) -> Vector3D {
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Vector3D::empty();
         }
+        let (radius, angle) = if a.abs() > b.abs() {
+            (a, std::f64::consts::FRAC_PI_4 * (b / a))
+        } else {
+            (
+                b,
+                std::f64::consts::FRAC_PI_2 - std::f64::consts::FRAC_PI_4 * (a / b),
+            )
+        };
+        Vector3D::new(radius * angle.cos(), radius * angle.sin(), 0.0)
     }
 
     /// Get the x-axis coordinate
@@ -145,6 +161,51 @@ pub fn cross(first: &Vector3D, second: &Vector3D) -> Vector3D {
     }
 }
 
+/// Three axes at right angles to each other, the third along a given
+/// direction, for turning directions around that axis in to world space.
+#[derive(Debug, Clone, Copy)]
+pub struct OrthonormalBasis {
+    u: Vector3D,
+    v: Vector3D,
+    w: Vector3D,
+}
+
+impl OrthonormalBasis {
+    /// Returns a basis whose `w` axis points along `w`, which doesn't need
+    /// to be of unit length.
+    ///
+    /// This is the construction of Duff et al., which has no special cases
+    /// other than the sign of `w.z`.
+    pub fn new(w: &Vector3D) -> OrthonormalBasis {
+        let w = unit_vector(w);
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        OrthonormalBasis {
+            u: Vector3D::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vector3D::new(b, sign + w.y * w.y * a, -w.y),
+            w,
+        }
+    }
+
+    pub fn u(&self) -> Vector3D {
+        self.u
+    }
+
+    pub fn v(&self) -> Vector3D {
+        self.v
+    }
+
+    pub fn w(&self) -> Vector3D {
+        self.w
+    }
+
+    /// Turns a vector given along the axes of the basis in to world space.
+    pub fn local(&self, a: &Vector3D) -> Vector3D {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
 impl fmt::Display for Vector3D {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} {} {}", self.x, self.y, self.z)
@@ -265,7 +326,11 @@ impl ops::Mul<f64> for Vector3D {
     type Output = Vector3D;
 
     fn mul(self, rhs: f64) -> Self::Output {
-        &self * rhs
+        Vector3D {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
     }
 }
 
@@ -285,7 +350,7 @@ impl ops::Mul<Vector3D> for f64 {
     type Output = Vector3D;
 
     fn mul(self, rhs: Vector3D) -> Self::Output {
-        &rhs * self
+        rhs * self
     }
 }
 
@@ -301,7 +366,7 @@ impl ops::Div<f64> for Vector3D {
     type Output = Vector3D;
 
     fn div(self, rhs: f64) -> Self::Output {
-        &self / rhs
+        (1.0 / rhs) * self
     }
 }
 
@@ -309,7 +374,7 @@ impl ops::Div<f64> for &Vector3D {
     type Output = Vector3D;
 
     fn div(self, rhs: f64) -> Self::Output {
-        (1 as f64 / rhs) * self
+        (1.0 / rhs) * self
     }
 }
 
@@ -367,6 +432,7 @@ impl ops::IndexMut<usize> for Vector3D {
 #[cfg(test)]
 mod tests {
     use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
 
     use super::*;
 
@@ -528,4 +594,68 @@ mod tests {
         let expected = Vector3D::new(0.22821, -0.42131, 0.87773);
         assert_relative_eq!(expected, normalized, epsilon = 0.001);
     }
+
+    #[test]
+    fn basis_is_orthonormal() {
+        for w in [
+            Vector3D::new(0.0, 0.0, 1.0),
+            Vector3D::new(0.0, 0.0, -2.0),
+            Vector3D::new(1.0, -3.0, 0.5),
+            Vector3D::new(-0.2, 0.1, -4.0),
+        ] {
+            let basis = OrthonormalBasis::new(&w);
+            assert_relative_eq!(unit_vector(&w), basis.w());
+            for axis in [basis.u(), basis.v(), basis.w()] {
+                assert_relative_eq!(1.0, axis.length(), epsilon = 1e-12);
+            }
+            assert_relative_eq!(0.0, basis.u().dot(&basis.v()), epsilon = 1e-12);
+            assert_relative_eq!(0.0, basis.u().dot(&basis.w()), epsilon = 1e-12);
+            assert_relative_eq!(0.0, basis.v().dot(&basis.w()), epsilon = 1e-12);
+            assert_relative_eq!(basis.w(), basis.u().cross(&basis.v()), epsilon = 1e-12);
+            assert_relative_eq!(
+                basis.w(),
+                basis.local(&Vector3D::new(0.0, 0.0, 1.0)),
+                epsilon = 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn cosine_directions_lean_towards_z() {
+        let mut generator = StdRng::seed_from_u64(7);
+        let samples = 100_000;
+        let mut total_z = 0.0;
+        for _ in 0..samples {
+            let direction = Vector3D::random_cosine_direction(&mut generator);
+            assert_relative_eq!(1.0, direction.length(), epsilon = 1e-12);
+            assert!(direction.z() >= 0.0);
+            total_z += direction.z();
+        }
+        // The mean cosine of a cosine weighted hemisphere is 2/3.
+        assert_relative_eq!(2.0 / 3.0, total_z / samples as f64, epsilon = 0.01);
+    }
+
+    #[test]
+    fn maps_square_on_to_disk() {
+        assert_relative_eq!(Vector3D::empty(), Vector3D::in_unit_disk(0.5, 0.5));
+        assert_relative_eq!(
+            Vector3D::new(1.0, 0.0, 0.0),
+            Vector3D::in_unit_disk(1.0, 0.5)
+        );
+        assert_relative_eq!(
+            Vector3D::new(0.0, -1.0, 0.0),
+            Vector3D::in_unit_disk(0.5, 0.0),
+            epsilon = 1e-12
+        );
+        // Squares around the center become circles around the center, so
+        // half of the points are within the inner half of the square.
+        let corner = Vector3D::in_unit_disk(0.75, 0.75);
+        assert_relative_eq!(0.5, corner.length(), epsilon = 1e-12);
+        for step in 0..=100 {
+            let u = f64::from(step) / 100.0;
+            for v in [0.0, 0.3, 1.0] {
+                assert!(Vector3D::in_unit_disk(u, v).length() <= 1.0 + 1e-12);
+            }
+        }
+    }
 }
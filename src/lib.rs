@@ -0,0 +1,33 @@
+//! A path tracer, following "Ray Tracing in One Weekend" and its sequels.
+//!
+//! Build a `Scene`, either in code or from a scene description file with
+//! `SceneDescription`, pick `RenderSettings`, and `render` it in to a
+//! `Framebuffer` of linear colors. The `Renderer` renders in passes, and
+//! can save and resume checkpoints, for more control.
+//!
+//! [`Scene`]: scene::scene::Scene
+//! [`SceneDescription`]: scene::scene_file::SceneDescription
+//! [`RenderSettings`]: scene::settings::RenderSettings
+//! [`Framebuffer`]: util::framebuffer::Framebuffer
+//! [`Renderer`]: util::renderer::Renderer
+
+pub mod geometry;
+pub mod scene;
+pub mod util;
+
+use scene::{scene::Scene, settings::RenderSettings};
+use util::{framebuffer::Framebuffer, progress::Progress, renderer::Renderer};
+
+/// Renders a scene with the given settings, without reporting progress.
+///
+/// # Panics
+///
+/// If the region of the settings doesn't fit in the image.
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Framebuffer {
+    let mut renderer = Renderer::new(scene, settings);
+    let region = renderer.region();
+    renderer
+        .render(&Progress::new((region.width * region.height) as u64, false))
+        .expect("Rendering without checkpoints can't fail.");
+    renderer.film().to_framebuffer()
+}
@@ -1,124 +1,532 @@
-#![feature(split_array)]
-
-pub mod geometry;
-pub mod scene;
-pub mod util;
-
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
 
 use clap::{Parser, ValueEnum};
-use geometry::{ray::Ray, vector_3d};
-use rand::Rng;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use scene::hittable::Hittable;
-use util::color::Color;
-
-use crate::{scene::scene::Scene, util::color::Pixel};
+use raytracer::{
+    scene::{
+        scene_file::SceneDescription,
+        settings::{Region, RenderSettings},
+    },
+    util::{
+        adaptive,
+        filter::{Filter, FilterKind},
+        framebuffer::Framebuffer,
+        image::{Dither, ImageFormat, Quantizer},
+        progress::Progress,
+        renderer::Renderer,
+        sampler::SamplerKind,
+        tone_map::{ToneMapOperator, ToneMapping},
+    },
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Scene to render
-    #[arg(value_enum, default_value_t = Scenes::Balls)]
-    scene: Scenes,
+    /// Scene description file to render
+    #[arg(long)]
+    scene_file: PathBuf,
+
+    /// Samples to shoot per pixel, or the most a pixel gets with
+    /// --adaptive-threshold [default: 100]
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    samples: Option<i32>,
+
+    /// Render the whole image in passes of this many samples per pixel,
+    /// writing the image after every pass [default: 4 with --time-limit or
+    /// --target-spp]
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pass_samples: Option<u64>,
+
+    /// Stop rendering after this many seconds, writing the image after
+    /// every pass until then
+    #[arg(long)]
+    time_limit: Option<f64>,
+
+    /// Samples per pixel to refine the image to in passes, instead of
+    /// --samples
+    #[arg(
+        long,
+        conflicts_with = "samples",
+        value_parser = clap::value_parser!(i32).range(1..)
+    )]
+    target_spp: Option<i32>,
+
+    /// Stop sampling a pixel once the 95% confidence interval of its
+    /// brightness is within this fraction of it, for example 0.05
+    #[arg(long)]
+    adaptive_threshold: Option<f64>,
+
+    /// Samples every pixel gets before adaptive sampling may stop it, at
+    /// most --samples [default: 16]
+    #[arg(long, requires = "adaptive_threshold", value_parser = clap::value_parser!(i32).range(1..))]
+    min_samples: Option<i32>,
+
+    /// File to write an image of how many samples every pixel got to
+    #[arg(long)]
+    sample_heatmap: Option<PathBuf>,
+
+    /// Width in pixels of the image, at least 2 [default: 400]
+    #[arg(long, value_parser = clap::value_parser!(i32).range(2..))]
+    width: Option<i32>,
+
+    /// Aspect ration (width) [default: 3]
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    aspect_ratio_width: Option<i32>,
+
+    /// Aspect ration (height) [default: 2]
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    aspect_ratio_height: Option<i32>,
 
-    /// Samples to shoot per pixel
-    #[arg(long, default_value_t = 100)]
-    samples: i32,
+    /// Maximum number of times a ray bounces [default: 50]
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    max_depth: Option<i32>,
 
-    /// Width in pixels of the image
-    #[arg(long, default_value_t = 400)]
-    width: i32,
+    /// How samples are spread over each pixel and over the lens
+    /// [default: independent]
+    #[arg(long, value_enum)]
+    sampler: Option<SamplerMode>,
 
-    /// Aspect ration (width)
-    #[arg(long, default_value_t = 3)]
-    aspect_ratio_width: i32,
+    /// How samples are weighed in to the pixels around them [default: box]
+    #[arg(long, value_enum)]
+    filter: Option<FilterMode>,
 
-    /// Aspect ration (height)
-    #[arg(long, default_value_t = 2)]
-    aspect_ratio_height: i32,
+    /// How far in pixels samples reach, instead of the usual radius of the
+    /// filter
+    #[arg(long)]
+    filter_radius: Option<f64>,
+
+    /// Seed for every random choice, so the same seed gives the same image
+    /// [default: 0]
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// File to save the samples so far to every --checkpoint-interval
+    /// seconds, so a render that is stopped can be carried on
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60)]
+    checkpoint_interval: u64,
+
+    /// Checkpoint to carry on from. Checkpoints are saved back to it,
+    /// unless --checkpoint is given. The scene and the settings that pick
+    /// samples, like the seed, must be the same, but --samples can grow,
+    /// except with --sampler stratified
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// Only render the pixels from column x and row y (from the top left),
+    /// as x,y,width,height. The rest of the image stays black
+    #[arg(long)]
+    region: Option<Region>,
+
+    /// File to write the image to, instead of writing an ASCII PPM to
+    /// standard output. The format follows the extension: .png, .ppm, or
+    /// .hdr, .pfm and .exr for linear high dynamic range output
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Format of the output file, instead of following its extension
+    #[arg(long, value_enum, requires = "output")]
+    format: Option<OutputFormat>,
+
+    /// How to hide banding when rounding colors to 8 bits
+    #[arg(long, value_enum, default_value_t = DitherMode::None)]
+    dither: DitherMode,
+
+    /// How to fit bright colors in to 8-bit images [default: clamp]
+    #[arg(long, value_enum)]
+    tone_map: Option<ToneMapMode>,
+
+    /// Brightness adjustment in stops before tone mapping [default: 0]
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Smallest value that becomes white with extended Reinhard [default: 4]
+    #[arg(long)]
+    white_point: Option<f64>,
+}
+
+impl Args {
+    /// Overrides the settings with those given on the command line.
+    fn apply_settings(&self, settings: &mut RenderSettings) {
+        if let Some(samples) = self.samples.or(self.target_spp) {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(width) = self.width {
+            settings.width = width;
+        }
+        if self.aspect_ratio_width.is_some() || self.aspect_ratio_height.is_some() {
+            settings.aspect_ratio = self.aspect_ratio_width.unwrap_or(3) as f64
+                / self.aspect_ratio_height.unwrap_or(2) as f64;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(tone_map) = self.tone_map {
+            settings.tone_mapping.operator = ToneMapOperator::from(tone_map);
+        }
+        if let Some(exposure) = self.exposure {
+            settings.tone_mapping.exposure = exposure;
+        }
+        if let Some(white_point) = self.white_point {
+            settings.tone_mapping.white_point = white_point;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            settings.sampler = SamplerKind::from(sampler);
+        }
+        if let Some(filter) = self.filter {
+            settings.filter = Filter::new(FilterKind::from(filter));
+        }
+        if let Some(radius) = self.filter_radius {
+            settings.filter.radius = radius;
+        }
+        if let Some(threshold) = self.adaptive_threshold {
+            settings.adaptive_threshold = Some(threshold);
+        }
+        if let Some(min_samples) = self.min_samples {
+            settings.min_samples = min_samples;
+        }
+        if let Some(region) = self.region {
+            settings.region = Some(region);
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum Scenes {
-    /// Many balls on a checkered surface
-    Balls,
-    /// Two massive balls
-    TwoBalls,
+enum OutputFormat {
+    /// Compressed 8-bit PNG
+    Png,
+    /// Binary (P6) PPM
+    Ppm,
+    /// ASCII (P3) PPM
+    PpmAscii,
+    /// Radiance RGBE
+    Hdr,
+    /// Portable FloatMap
+    Pfm,
+    /// OpenEXR
+    Exr,
 }
 
-fn ray_color(ray: &Ray, world: &dyn Hittable, depth: i32) -> Color {
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
+impl From<OutputFormat> for ImageFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Ppm => ImageFormat::BinaryPpm,
+            OutputFormat::PpmAscii => ImageFormat::AsciiPpm,
+            OutputFormat::Hdr => ImageFormat::Hdr,
+            OutputFormat::Pfm => ImageFormat::Pfm,
+            OutputFormat::Exr => ImageFormat::Exr,
+        }
     }
+}
 
-    if let Some(record) = world.hit(ray, 0.001, f64::INFINITY) {
-        match record.material.scatter(ray, &record) {
-            Some((attenuation, scattered)) => {
-                return attenuation * ray_color(&scattered, world, depth - 1);
-            }
-            None => {
-                return Color::new(0.0, 0.0, 0.0);
-            }
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum DitherMode {
+    /// Round to the nearest value
+    None,
+    /// Add an 8x8 Bayer pattern
+    Ordered,
+    /// Add blue noise
+    BlueNoise,
+}
+
+impl From<DitherMode> for Dither {
+    fn from(mode: DitherMode) -> Self {
+        match mode {
+            DitherMode::None => Dither::None,
+            DitherMode::Ordered => Dither::Ordered,
+            DitherMode::BlueNoise => Dither::BlueNoise,
         }
     }
+}
 
-    let unit_direction = vector_3d::unit_vector(&ray.direction);
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    return (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0);
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SamplerMode {
+    /// Independent random samples
+    Independent,
+    /// One sample in every cell of a grid
+    Stratified,
+    /// Randomly shifted Halton sequence
+    Halton,
+    /// Owen-scrambled Sobol sequence
+    Sobol,
+}
+
+impl From<SamplerMode> for SamplerKind {
+    fn from(mode: SamplerMode) -> Self {
+        match mode {
+            SamplerMode::Independent => SamplerKind::Independent,
+            SamplerMode::Stratified => SamplerKind::Stratified,
+            SamplerMode::Halton => SamplerKind::Halton,
+            SamplerMode::Sobol => SamplerKind::Sobol,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum FilterMode {
+    /// Average the samples in each pixel (radius 0.5)
+    Box,
+    /// Linear falloff (radius 1)
+    Tent,
+    /// Gaussian falloff (radius 1.5)
+    Gaussian,
+    /// Mitchell-Netravali cubic, a little sharper (radius 2)
+    Mitchell,
+    /// Windowed sinc, the sharpest (radius 3)
+    Lanczos,
+}
+
+impl From<FilterMode> for FilterKind {
+    fn from(mode: FilterMode) -> Self {
+        match mode {
+            FilterMode::Box => FilterKind::Box,
+            FilterMode::Tent => FilterKind::Tent,
+            FilterMode::Gaussian => FilterKind::Gaussian,
+            FilterMode::Mitchell => FilterKind::Mitchell,
+            FilterMode::Lanczos => FilterKind::Lanczos,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum ToneMapMode {
+    /// Cut off everything brighter than white
+    Clamp,
+    /// Reinhard's x / (1 + x)
+    Reinhard,
+    /// Reinhard reaching white at the white point
+    ExtendedReinhard,
+    /// ACES filmic curve
+    Aces,
+    /// Hable's Uncharted 2 filmic curve
+    Hable,
+}
+
+impl From<ToneMapMode> for ToneMapOperator {
+    fn from(mode: ToneMapMode) -> Self {
+        match mode {
+            ToneMapMode::Clamp => ToneMapOperator::Clamp,
+            ToneMapMode::Reinhard => ToneMapOperator::Reinhard,
+            ToneMapMode::ExtendedReinhard => ToneMapOperator::ExtendedReinhard,
+            ToneMapMode::Aces => ToneMapOperator::Aces,
+            ToneMapMode::Hable => ToneMapOperator::Hable,
+        }
+    }
+}
+
+/// Writes the framebuffer to the file, or to standard output if there is
+/// none. 8-bit formats are tone mapped first.
+fn write_image(
+    framebuffer: &Framebuffer,
+    format: ImageFormat,
+    path: Option<&Path>,
+    tone_mapping: &ToneMapping,
+    dither: Dither,
+) -> io::Result<()> {
+    let image = if format.is_high_dynamic_range() {
+        framebuffer.to_image()
+    } else {
+        framebuffer.to_display_image(tone_mapping)
+    };
+    let writer = format.writer(Quantizer::new(dither));
+    match path {
+        Some(path) => File::create(path).and_then(|file| {
+            let mut output = BufWriter::new(file);
+            writer.write_image(&image, &mut output)?;
+            output.flush()
+        }),
+        None => {
+            let mut output = io::stdout().lock();
+            writer
+                .write_image(&image, &mut output)
+                .and_then(|_| output.flush())
+        }
+    }
+}
+
+/// The format of an image file, from its extension. Exits if it isn't known.
+fn format_of(path: &Path) -> ImageFormat {
+    ImageFormat::from_path(path).unwrap_or_else(|| {
+        eprintln!(
+            "Can't tell the image format of {}, use .png, .ppm, .hdr, .pfm or .exr, or give --format",
+            path.display()
+        );
+        process::exit(1);
+    })
 }
 
 fn main() {
     let args = Args::parse();
 
-    // Image
-    let aspect_ratio: f64 = args.aspect_ratio_width as f64 / args.aspect_ratio_height as f64;
-    let image_width: i32 = args.width;
-    let image_height: i32 = ((image_width as f64) / aspect_ratio) as i32;
-    let samples_per_pixel: i32 = args.samples;
-    const MAX_DEPTH: i32 = 50;
-
-    // World
-    let scene = match args.scene {
-        Scenes::Balls => Scene::random_scene(aspect_ratio),
-        Scenes::TwoBalls => Scene::two_balls(aspect_ratio),
+    // Check where the image goes before spending time rendering it.
+    let format = match (&args.output, args.format) {
+        (_, Some(format)) => ImageFormat::from(format),
+        (Some(path), None) => format_of(path),
+        (None, None) => ImageFormat::AsciiPpm,
     };
+    let heatmap_format = args.sample_heatmap.as_deref().map(format_of);
 
-    // Render
-    println!("P3\n{} {}\n255", image_width, image_height);
-
-    let remaining_scanlines = AtomicI32::new(image_height);
-    let mut pixels: Vec<Pixel> = vec![];
-    let pixel_row = (0..(image_height * image_width))
-        .into_par_iter()
-        .map(|count| (image_height - (count / image_width), count % image_width))
-        .map(|(row, column)| {
-            if column % image_width == 0 {
-                let remaining = remaining_scanlines.fetch_sub(1, Ordering::Relaxed);
-                eprint!("\rScanlines remaining: {:04}", remaining);
-            }
-
-            let mut generator = rand::thread_rng();
+    // World, and image settings. Settings given on the command line take
+    // precedence over those in the scene file.
+    let mut settings = RenderSettings::default();
+    let description = SceneDescription::load(&args.scene_file).unwrap_or_else(|error| {
+        eprintln!("Invalid scene file: {}", error);
+        process::exit(1);
+    });
+    description.apply_settings(&mut settings);
+    args.apply_settings(&mut settings);
+    let scene = description.into_scene(settings.aspect_ratio);
 
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..samples_per_pixel {
-                let u =
-                    ((column as f64) + generator.gen_range(0.0..1.0)) / ((image_width - 1) as f64);
-                let v =
-                    ((row as f64) + generator.gen_range(0.0..1.0)) / ((image_height - 1) as f64);
-                let ray = scene.camera.get_ray(u, v);
-                pixel_color += &ray_color(&ray, &scene.objects, MAX_DEPTH);
-            }
+    // Rays are spread from one edge of the image to the other, which needs
+    // two pixels along each axis.
+    if settings.width < 2 || settings.height() < 2 {
+        eprintln!(
+            "The image must be at least 2x2 pixels, but is {}x{}",
+            settings.width,
+            settings.height()
+        );
+        process::exit(1);
+    }
+    if let Some(region) = settings.region {
+        if !region.fits(
+            settings.width.max(0) as usize,
+            settings.height().max(0) as usize,
+        ) {
+            eprintln!(
+                "The region doesn't fit in an image of {}x{}",
+                settings.width,
+                settings.height()
+            );
+            process::exit(1);
+        }
+    }
+    if !(settings.filter.radius > 0.0 && settings.filter.radius.is_finite()) {
+        eprintln!("The filter radius must be positive");
+        process::exit(1);
+    }
+    if args
+        .min_samples
+        .is_some_and(|min_samples| min_samples > settings.samples_per_pixel)
+    {
+        eprintln!(
+            "The minimum samples per pixel can't be more than the {} samples per pixel",
+            settings.samples_per_pixel
+        );
+        process::exit(1);
+    }
+    let white_point = settings.tone_mapping.white_point;
+    if !(white_point > 0.0 && white_point.is_finite()) {
+        eprintln!("The white point must be positive");
+        process::exit(1);
+    }
 
-            pixel_color.color_code(samples_per_pixel)
+    // Render, in passes over the whole image if any of the options for
+    // that are given.
+    let progressive =
+        args.pass_samples.is_some() || args.time_limit.is_some() || args.target_spp.is_some();
+    let time_limit = args.time_limit.map(|seconds| {
+        Duration::try_from_secs_f64(seconds).unwrap_or_else(|_| {
+            eprintln!("The time limit must be a positive number of seconds");
+            process::exit(1);
         })
-        .collect::<Vec<Pixel>>();
-    pixels.extend(pixel_row);
+    });
+    let mut renderer = Renderer::new(&scene, &settings);
+    if let Some(path) = &args.resume {
+        if let Err(error) = renderer.resume(path) {
+            eprintln!("Can't resume from {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = args.checkpoint.as_ref().or(args.resume.as_ref()) {
+        renderer.save_checkpoints(path.clone(), Duration::from_secs(args.checkpoint_interval));
+    }
+    let region = renderer.region();
+    let pixels = region.width * region.height;
+    let dither = Dither::from(args.dither);
+    let write_outputs = |renderer: &Renderer| {
+        let counts = renderer.sample_counts();
+        if settings.adaptive_threshold.is_some() || progressive {
+            eprintln!(
+                "Average samples per pixel: {:.1}",
+                counts.iter().sum::<u64>() as f64 / pixels as f64
+            );
+        }
+
+        // Write the image to the file, or to standard output.
+        let framebuffer = renderer.film().to_framebuffer();
+        if let Err(error) = write_image(
+            &framebuffer,
+            format,
+            args.output.as_deref(),
+            &settings.tone_mapping,
+            dither,
+        ) {
+            eprintln!("Can't write the image: {}", error);
+            process::exit(1);
+        }
+        if let (Some(path), Some(format)) = (&args.sample_heatmap, heatmap_format) {
+            let heatmap = adaptive::sample_count_heatmap(
+                framebuffer.width(),
+                framebuffer.height(),
+                &counts,
+                settings.samples_per_pixel.max(0) as u64,
+            );
+            if let Err(error) = write_image(
+                &heatmap,
+                format,
+                Some(path),
+                &ToneMapping::default(),
+                dither,
+            ) {
+                eprintln!("Can't write the sample heatmap: {}", error);
+                process::exit(1);
+            }
+        }
+    };
 
-    // Write the pixels to the file
-    for pixel in pixels {
-        pixel.write_color();
+    if progressive {
+        // Images written to standard output can't be replaced, so those are
+        // only written once at the end.
+        let deadline = time_limit.map(|limit| Instant::now() + limit);
+        let pass_samples = args.pass_samples.unwrap_or(4);
+        for pass in 1.. {
+            eprintln!("Pass {}", pass);
+            let progress = Progress::new(pixels as u64, true);
+            let finished = renderer.render_pass(pass_samples, deadline, &progress);
+            progress.finish();
+            let finished = finished.unwrap_or_else(|error| {
+                eprintln!("Can't save the checkpoint: {}", error);
+                process::exit(1);
+            });
+            let last = !finished || renderer.is_done();
+            if last || args.output.is_some() {
+                write_outputs(&renderer);
+            }
+            if last {
+                break;
+            }
+        }
+    } else {
+        let progress = Progress::new(pixels as u64, true);
+        if let Err(error) = renderer.render(&progress) {
+            eprintln!("\nCan't save the checkpoint: {}", error);
+            process::exit(1);
+        }
+        progress.finish();
+        write_outputs(&renderer);
     }
 
-    eprintln!("\nDone!");
+    eprintln!("Done!");
 }
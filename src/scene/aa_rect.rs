@@ -0,0 +1,316 @@
+use rand::{Rng, RngCore};
+
+use crate::{
+    geometry::{ray::Ray, vector_3d::Vector3D},
+    util::point::Point3D,
+};
+
+use super::{
+    bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
+    materials::Material,
+};
+
+/// How thick the bounding box of a rectangle is, as it has no thickness of its
+/// own.
+const RECT_BOX_PADDING: f64 = 0.0001;
+
+/// A rectangle lying in a plane where one axis is constant. `XyRect`,
+/// `XzRect` and `YzRect` are this with the axes filled in.
+struct AxisAlignedRect {
+    /// The first axis along the rectangle, which the `u` coordinate follows.
+    a_axis: usize,
+    /// The second axis along the rectangle, which the `v` coordinate follows.
+    b_axis: usize,
+    /// The axis that is constant on the rectangle.
+    k_axis: usize,
+    a_0: f64,
+    a_1: f64,
+    b_0: f64,
+    b_1: f64,
+    k: f64,
+    /// Whether the outward normal points along the negative `k` axis.
+    flipped: bool,
+    material: Box<dyn Material>,
+}
+
+impl AxisAlignedRect {
+    fn new(
+        axes: (usize, usize, usize),
+        a: (f64, f64),
+        b: (f64, f64),
+        k: f64,
+        material: Box<dyn Material>,
+    ) -> AxisAlignedRect {
+        let (a_axis, b_axis, k_axis) = axes;
+        AxisAlignedRect {
+            a_axis,
+            b_axis,
+            k_axis,
+            a_0: a.0.min(a.1),
+            a_1: a.0.max(a.1),
+            b_0: b.0.min(b.1),
+            b_1: b.0.max(b.1),
+            k,
+            flipped: false,
+            material,
+        }
+    }
+
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let t = (self.k - ray.origin[self.k_axis]) / ray.direction[self.k_axis];
+        // Also catches rays parallel to the plane, where t isn't a number.
+        if !(t_min..=t_max).contains(&t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let a = point[self.a_axis];
+        let b = point[self.b_axis];
+        if a < self.a_0 || a > self.a_1 || b < self.b_0 || b > self.b_1 {
+            return None;
+        }
+
+        let mut outward_normal = Vector3D::empty();
+        outward_normal[self.k_axis] = if self.flipped { -1.0 } else { 1.0 };
+        Some(HitRecord::new(
+            point,
+            outward_normal,
+            &*self.material,
+            t,
+            (a - self.a_0) / (self.a_1 - self.a_0),
+            (b - self.b_0) / (self.b_1 - self.b_0),
+            ray,
+        ))
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let mut minimum = Point3D::empty();
+        let mut maximum = Point3D::empty();
+        minimum[self.a_axis] = self.a_0;
+        maximum[self.a_axis] = self.a_1;
+        minimum[self.b_axis] = self.b_0;
+        maximum[self.b_axis] = self.b_1;
+        minimum[self.k_axis] = self.k;
+        maximum[self.k_axis] = self.k;
+        AxisAlignedBoundingBox::new(minimum, maximum).pad(RECT_BOX_PADDING)
+    }
+
+    /// Points are picked evenly over the area, so the density per unit solid
+    /// angle is the squared distance over the area the rectangle shows.
+    fn pdf_value(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        let ray = Ray::new(*origin, *direction, None);
+        match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(record) => {
+                let area = (self.a_1 - self.a_0) * (self.b_1 - self.b_0);
+                let distance_squared = record.t * record.t * direction.length_squared();
+                let cosine = (direction[self.k_axis] / direction.length()).abs();
+                distance_squared / (cosine * area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random_direction(&self, origin: &Point3D, generator: &mut dyn RngCore) -> Vector3D {
+        let mut point = Point3D::empty();
+        point[self.a_axis] = generator.gen_range(self.a_0..=self.a_1);
+        point[self.b_axis] = generator.gen_range(self.b_0..=self.b_1);
+        point[self.k_axis] = self.k;
+        point - *origin
+    }
+}
+
+macro_rules! axis_aligned_rect {
+    ($(#[$meta:meta])* $name:ident, $a:ident, $b:ident, $k:ident, $axes:expr, $normal:literal) => {
+        $(#[$meta])*
+        pub struct $name {
+            rect: AxisAlignedRect,
+        }
+
+        impl $name {
+            #[doc = concat!(
+                "Returns a new rectangle spanning `", stringify!($a), "` and `",
+                stringify!($b), "`, at `", stringify!($k), "`. The outward normal ",
+                "points along ", $normal, "."
+            )]
+            pub fn new(
+                $a: (f64, f64),
+                $b: (f64, f64),
+                $k: f64,
+                material: Box<dyn Material>,
+            ) -> $name {
+                $name {
+                    rect: AxisAlignedRect::new($axes, $a, $b, $k, material),
+                }
+            }
+
+            /// Turns the rectangle around, so the outward normal points the
+            /// other way. Useful to make lights face in to a room.
+            pub fn flipped(mut self) -> $name {
+                self.rect.flipped = !self.rect.flipped;
+                self
+            }
+        }
+
+        impl Hittable for $name {
+            fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+                self.rect.hit(ray, t_min, t_max)
+            }
+
+            fn bounding_box(&self, _time_0: f64, _time_1: f64) -> Option<AxisAlignedBoundingBox> {
+                Some(self.rect.bounding_box())
+            }
+
+            fn pdf_value(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+                self.rect.pdf_value(origin, direction)
+            }
+
+            fn random_direction(&self, origin: &Point3D, generator: &mut dyn RngCore) -> Vector3D {
+                self.rect.random_direction(origin, generator)
+            }
+        }
+    };
+}
+
+axis_aligned_rect!(
+    /// A rectangle parallel to the xy plane, with `u` along x and `v` along y.
+    XyRect, x, y, z, (0, 1, 2), "+z"
+);
+axis_aligned_rect!(
+    /// A rectangle parallel to the xz plane, with `u` along x and `v` along z.
+    XzRect, x, z, y, (0, 2, 1), "+y"
+);
+axis_aligned_rect!(
+    /// A rectangle parallel to the yz plane, with `u` along y and `v` along z.
+    YzRect, y, z, x, (1, 2, 0), "+x"
+);
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{geometry::bounded_volume_hierarchy::BvhNode, scene::materials::Lambertian};
+
+    use super::*;
+
+    fn material() -> Box<dyn Material> {
+        Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn can_hit_rect_with_uv() {
+        let rect = XzRect::new((0.0, 2.0), (0.0, 4.0), 1.0, material());
+        let ray = Ray::new(
+            Point3D::new(0.5, 3.0, 3.0),
+            Vector3D::new(0.0, -1.0, 0.0),
+            None,
+        );
+        let record = rect
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(2.0, record.t);
+        assert_relative_eq!(0.25, record.u);
+        assert_relative_eq!(0.75, record.v);
+        assert_relative_eq!(Vector3D::new(0.0, 1.0, 0.0), record.normal);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn flipped_rect_faces_the_other_way() {
+        let rect = XyRect::new((0.0, 1.0), (0.0, 1.0), 0.0, material()).flipped();
+        let ray = Ray::new(
+            Point3D::new(0.5, 0.5, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        assert!(
+            !rect
+                .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+                .unwrap()
+                .front_face
+        );
+    }
+
+    #[test]
+    fn cant_hit_outside_or_parallel_to_rect() {
+        let rect = YzRect::new((0.0, 1.0), (0.0, 1.0), 0.0, material());
+        let outside = Ray::new(
+            Point3D::new(1.0, 1.5, 0.5),
+            Vector3D::new(-1.0, 0.0, 0.0),
+            None,
+        );
+        assert!(rect
+            .hit(&outside, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+        let parallel = Ray::new(
+            Point3D::new(1.0, 0.5, 0.5),
+            Vector3D::new(0.0, 1.0, 0.0),
+            None,
+        );
+        assert!(rect
+            .hit(&parallel, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn aims_at_rect() {
+        let rect = XzRect::new((-1.0, 1.0), (-2.0, 2.0), 3.0, material());
+        let origin = Point3D::new(0.0, 1.0, 0.0);
+        // Straight at the center the density is the squared distance over
+        // the area.
+        assert_relative_eq!(
+            4.0 / 8.0,
+            rect.pdf_value(&origin, &Vector3D::new(0.0, 1.0, 0.0))
+        );
+        assert_relative_eq!(0.0, rect.pdf_value(&origin, &Vector3D::new(0.0, -1.0, 0.0)));
+
+        let mut generator = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let direction = rect.random_direction(&origin, &mut generator);
+            assert!(rect.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn rect_pdf_adds_up_to_one() {
+        let rect = YzRect::new((-1.0, 1.0), (-1.0, 1.0), 1.0, material());
+        let origin = Point3D::empty();
+        let mut generator = StdRng::seed_from_u64(5);
+        let samples = 100_000;
+        let total: f64 = (0..samples)
+            .map(|_| {
+                let direction = Vector3D::random_unit_vector(&mut generator);
+                rect.pdf_value(&origin, &direction) * 4.0 * PI
+            })
+            .sum();
+        assert_relative_eq!(1.0, total / samples as f64, epsilon = 0.02);
+    }
+
+    #[test]
+    fn rects_work_inside_bvh() {
+        let rects: Vec<Box<dyn Hittable>> = vec![
+            Box::new(XyRect::new((0.0, 1.0), (0.0, 1.0), 5.0, material())),
+            Box::new(XyRect::new((2.0, 3.0), (0.0, 1.0), 5.0, material())),
+        ];
+        let bounding_box = rects[0].bounding_box(0.0, 1.0).unwrap();
+        assert!(bounding_box.maximum.z() > bounding_box.minimum.z());
+
+        let bvh = BvhNode::new(rects, 0.0, 1.0);
+        let ray = Ray::new(
+            Point3D::new(2.5, 0.5, 0.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+            None,
+        );
+        assert!(bvh
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_some());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    geometry::{ray::Ray, vector_3d},
+    util::{color::Color, image::Image},
+};
+
+/// What a ray sees when it doesn't hit anything in the scene.
+pub enum Background {
+    /// The same color in every direction. Black for scenes that are only lit
+    /// by their lights.
+    Solid(Color),
+    /// Blends from one color looking straight down to another looking
+    /// straight up.
+    Gradient { bottom: Color, top: Color },
+    /// An environment map, such as a photo of a sky.
+    Environment(EnvironmentMap),
+}
+
+impl Default for Background {
+    /// A white to light blue sky.
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Background {
+    /// The light coming from the direction of the ray.
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let unit_direction = vector_3d::unit_vector(&ray.direction);
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Background::Environment(map) => map.color(ray),
+        }
+    }
+}
+
+/// An equirectangular (latitude-longitude) image surrounding the scene, with
+/// the top of the image straight up.
+pub struct EnvironmentMap {
+    image: Arc<Image>,
+    intensity: f64,
+    /// Rotation around the vertical axis, in radians.
+    rotation: f64,
+}
+
+impl EnvironmentMap {
+    /// Returns a new environment map.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The environment, usually a high dynamic range image.
+    /// * `intensity` - What to scale the colors of the image by.
+    /// * `rotation` - Degrees to turn the environment around the vertical axis.
+    pub fn new(image: Arc<Image>, intensity: f64, rotation: f64) -> EnvironmentMap {
+        EnvironmentMap {
+            image,
+            intensity,
+            rotation: rotation.to_radians(),
+        }
+    }
+
+    pub fn color(&self, ray: &Ray) -> Color {
+        let width = self.image.width();
+        let height = self.image.height();
+        if width == 0 || height == 0 {
+            return Color::empty();
+        }
+
+        // Same mapping as the texture coordinates of a sphere, seen from the
+        // inside, with u going around and v from the bottom to the top.
+        let direction = vector_3d::unit_vector(&ray.direction);
+        let theta = (-direction.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-direction.z()).atan2(direction.x()) + PI + self.rotation;
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = theta / PI;
+
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = (((1.0 - v) * height as f64) as usize).min(height - 1);
+        self.intensity * self.image.pixel(x, y)
+    }
+}
@@ -1,6 +1,9 @@
-use crate::{geometry::ray::Ray, util::point::Point3D};
+use crate::{
+    geometry::{ray::Ray, vector_3d::Vector3D},
+    util::point::Point3D,
+};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AxisAlignedBoundingBox {
     pub minimum: Point3D,
     pub maximum: Point3D,
@@ -31,6 +34,34 @@ impl AxisAlignedBoundingBox {
         }
     }
 
+    /// The area of the six sides of the box.
+    pub fn surface_area(&self) -> f64 {
+        let size = self.maximum - self.minimum;
+        2.0 * (size.x() * size.y() + size.y() * size.z() + size.z() * size.x())
+    }
+
+    pub fn centroid(&self) -> Point3D {
+        (self.minimum + self.maximum) * 0.5
+    }
+
+    /// Returns a box that is at least `delta` thick along every axis.
+    ///
+    /// Flat primitives (triangles lying in an axis plane, rectangles) have a
+    /// zero-width bounding box on one axis, which makes the slab test
+    /// numerically unreliable. Padding only the degenerate axes keeps the box
+    /// tight everywhere else.
+    pub fn pad(&self, delta: f64) -> AxisAlignedBoundingBox {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < delta {
+                minimum[axis] -= delta / 2.0;
+                maximum[axis] += delta / 2.0;
+            }
+        }
+        AxisAlignedBoundingBox { minimum, maximum }
+    }
+
     /// Checks if a ray intersects this bounding box between times t_min
     /// and t_max.
     ///
@@ -51,7 +82,7 @@ impl AxisAlignedBoundingBox {
     /// * `ray` - The ray for which we want to check if it passes through the line.
     /// * `t_min` - The start time for which we want to check an intersection.
     /// * `t_max` - The end time for which we want to check if we have an
-    /// intersection.
+    ///   intersection.
     pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
         for axis in 0..3 {
             let intersection_0 = (self.minimum[axis] - ray.origin[axis]) / ray.direction[axis];
@@ -67,6 +98,29 @@ impl AxisAlignedBoundingBox {
         true
     }
 
+    /// Same as `hit`, for when one ray is tested against many boxes, given
+    /// `1 / ray.direction` for each axis so the divisions are only done once.
+    pub fn hit_inverse(
+        &self,
+        ray: &Ray,
+        inverse_direction: &Vector3D,
+        t_min: f64,
+        t_max: f64,
+    ) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t_0 = (self.minimum[axis] - ray.origin[axis]) * inverse_direction[axis];
+            let t_1 = (self.maximum[axis] - ray.origin[axis]) * inverse_direction[axis];
+            t_min = t_min.max(t_0.min(t_1));
+            t_max = t_max.min(t_0.max(t_1));
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+
     /// Same as `hit`, but possibly faster? I have my doubts.
     pub fn optimized_hit(&self, ray: Ray, t_min: f64, t_max: f64) -> bool {
         for axis in 0..3 {
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{geometry::ray::Ray, util::point::Point3D};
+
+use super::{
+    aa_rect::{XyRect, XzRect, YzRect},
+    bounding_box::AxisAlignedBoundingBox,
+    hit_record::HitRecord,
+    hittable::Hittable,
+    materials::Material,
+};
+
+/// An axis-aligned box made of six rectangles, with normals pointing out of
+/// the box.
+pub struct BoxShape {
+    minimum: Point3D,
+    maximum: Point3D,
+    sides: Vec<Box<dyn Hittable>>,
+}
+
+impl BoxShape {
+    /// Returns a new box.
+    ///
+    /// # Arguments
+    ///
+    /// * `corner_0`, `corner_1` - Two opposite corners of the box.
+    /// * `material` - What the box is made of, shared by all of its sides.
+    pub fn new(corner_0: Point3D, corner_1: Point3D, material: Box<dyn Material>) -> BoxShape {
+        let minimum = Point3D::new(
+            corner_0.x().min(corner_1.x()),
+            corner_0.y().min(corner_1.y()),
+            corner_0.z().min(corner_1.z()),
+        );
+        let maximum = Point3D::new(
+            corner_0.x().max(corner_1.x()),
+            corner_0.y().max(corner_1.y()),
+            corner_0.z().max(corner_1.z()),
+        );
+
+        let material: Arc<dyn Material> = Arc::from(material);
+        let side_material = || Box::new(Arc::clone(&material)) as Box<dyn Material>;
+        let x = (minimum.x(), maximum.x());
+        let y = (minimum.y(), maximum.y());
+        let z = (minimum.z(), maximum.z());
+        let sides: Vec<Box<dyn Hittable>> = vec![
+            Box::new(XyRect::new(x, y, maximum.z(), side_material())),
+            Box::new(XyRect::new(x, y, minimum.z(), side_material()).flipped()),
+            Box::new(XzRect::new(x, z, maximum.y(), side_material())),
+            Box::new(XzRect::new(x, z, minimum.y(), side_material()).flipped()),
+            Box::new(YzRect::new(y, z, maximum.x(), side_material())),
+            Box::new(YzRect::new(y, z, minimum.x(), side_material()).flipped()),
+        ];
+
+        BoxShape {
+            minimum,
+            maximum,
+            sides,
+        }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        self.sides.hit(ray, t_min, t_max, generator)
+    }
+
+    fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        // The sides are padded already, so a flat box still has some depth.
+        let sides = self.sides.bounding_box(time_0, time_1)?;
+        Some(AxisAlignedBoundingBox::surrounding_box(
+            &AxisAlignedBoundingBox::new(self.minimum, self.maximum),
+            &sides,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{geometry::vector_3d::Vector3D, scene::materials::Lambertian};
+
+    use super::*;
+
+    fn unit_box() -> BoxShape {
+        BoxShape::new(
+            Point3D::new(1.0, 1.0, 1.0),
+            Point3D::new(0.0, 0.0, 0.0),
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn box_normals_point_outwards() {
+        let shape = unit_box();
+        let directions = [
+            Vector3D::new(1.0, 0.0, 0.0),
+            Vector3D::new(0.0, 1.0, 0.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+        ];
+        for direction in directions {
+            for sign in [-1.0, 1.0] {
+                let center = Point3D::new(0.5, 0.5, 0.5);
+                let ray = Ray::new(center + (3.0 * sign) * direction, -sign * direction, None);
+                let record = shape
+                    .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+                    .unwrap();
+                assert!(record.front_face);
+                assert_relative_eq!(2.5, record.t);
+                assert_relative_eq!(sign * direction, record.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn ray_inside_box_hits_back_face() {
+        let shape = unit_box();
+        let ray = Ray::new(
+            Point3D::new(0.5, 0.5, 0.5),
+            Vector3D::new(0.0, -1.0, 0.0),
+            None,
+        );
+        let record = shape
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert!(!record.front_face);
+        assert_relative_eq!(0.5, record.t);
+    }
+}
@@ -0,0 +1,251 @@
+use rand::{Rng, RngCore};
+
+use crate::geometry::{ray::Ray, vector_3d::Vector3D};
+
+use super::{
+    bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
+    materials::Material,
+};
+
+/// A volume of constant density, such as fog or smoke, filling the inside of
+/// a boundary object.
+///
+/// A ray going through the volume scatters somewhere inside it with a chance
+/// depending on the density and on how far it travels through the volume, or
+/// passes straight through. The boundary must be closed, and convex: a ray is
+/// assumed to enter and leave it only once.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    negative_inverse_density: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// Returns a new volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundary` - The shape of the volume. Its material isn't used.
+    /// * `density` - How thick the volume is. The chance of scattering per
+    ///   unit of distance travelled.
+    /// * `phase_function` - What happens to light that scatters, usually
+    ///   `Isotropic`.
+    ///
+    /// # Panics
+    ///
+    /// If the density isn't positive.
+    pub fn new(
+        boundary: Box<dyn Hittable>,
+        density: f64,
+        phase_function: Box<dyn Material>,
+    ) -> ConstantMedium {
+        if density <= 0.0 {
+            panic!("A constant medium needs a positive density.")
+        }
+        ConstantMedium {
+            boundary,
+            negative_inverse_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        // Find where the ray enters and leaves the boundary, even when that is
+        // behind the ray origin, as the ray may start inside the volume.
+        let entry = self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY, f64::INFINITY, generator)?;
+        let exit = self
+            .boundary
+            .hit(ray, entry.t + 0.0001, f64::INFINITY, generator)?;
+
+        let entry_t = entry.t.max(t_min).max(0.0);
+        let exit_t = exit.t.min(t_max);
+        if entry_t >= exit_t {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (exit_t - entry_t) * ray_length;
+        // Never take the log of 0.
+        let random: f64 = generator.gen();
+        let hit_distance = self.negative_inverse_density * (1.0 - random).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = entry_t + hit_distance / ray_length;
+        // Inside a volume there is no surface, so the normal and which side
+        // was hit are arbitrary.
+        Some(HitRecord {
+            p: ray.at(t),
+            normal: Vector3D::new(1.0, 0.0, 0.0),
+            material: &*self.phase_function,
+            t,
+            u: 0.0,
+            v: 0.0,
+            front_face: true,
+        })
+    }
+
+    fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.bounding_box(time_0, time_1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        scene::{box_shape::BoxShape, materials::Isotropic, moving_sphere::MovingSphere},
+        util::{color::Color, point::Point3D},
+    };
+
+    use super::*;
+
+    fn fog(boundary: Box<dyn Hittable>, density: f64) -> ConstantMedium {
+        ConstantMedium::new(
+            boundary,
+            density,
+            Box::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+        )
+    }
+
+    fn unit_box() -> Box<dyn Hittable> {
+        Box::new(BoxShape::new(
+            Point3D::new(-1.0, -1.0, -1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            Box::new(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+        ))
+    }
+
+    #[test]
+    fn dense_medium_scatters_near_the_boundary() {
+        let medium = fog(unit_box(), 1e9);
+        let ray = Ray::new(
+            Point3D::new(0.0, 0.0, 5.0),
+            Vector3D::new(0.0, 0.0, -2.0),
+            None,
+        );
+        let record = medium
+            .hit(&ray, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(2.0, record.t, epsilon = 1e-6);
+        assert!(record.p.z() <= 1.0);
+    }
+
+    #[test]
+    fn thin_medium_mostly_lets_rays_through() {
+        let medium = fog(unit_box(), 0.01);
+        let ray = Ray::new(
+            Point3D::new(0.0, 0.0, 5.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        let mut generator = StdRng::seed_from_u64(0);
+        let hits = (0..1000)
+            .filter(|_| {
+                medium
+                    .hit(&ray, 0.001, f64::INFINITY, &mut generator)
+                    .is_some()
+            })
+            .count();
+        // The chance to scatter over a distance of 2 is 1 - e^-0.02, about 2%.
+        assert!((5..50).contains(&hits), "{} hits", hits);
+    }
+
+    #[test]
+    fn same_seed_scatters_at_same_point() {
+        let medium = fog(unit_box(), 0.5);
+        let ray = Ray::new(
+            Point3D::new(0.3, 0.2, 5.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            Some(0.25),
+        );
+        let scatter = |seed| {
+            medium
+                .hit(&ray, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(seed))
+                .map(|record| record.t)
+        };
+        assert_eq!(scatter(7), scatter(7));
+        assert_ne!(scatter(7), scatter(8));
+    }
+
+    #[test]
+    fn ray_inside_medium_scatters_ahead_of_it() {
+        let medium = fog(unit_box(), 1e9);
+        let ray = Ray::new(Point3D::empty(), Vector3D::new(1.0, 0.0, 0.0), None);
+        let record = medium
+            .hit(&ray, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert!(record.t > 0.0 && record.t < 0.01);
+    }
+
+    #[test]
+    fn cant_hit_medium_beside_ray_or_behind_it() {
+        let medium = fog(unit_box(), 1e9);
+        let beside = Ray::new(
+            Point3D::new(3.0, 0.0, 5.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        assert!(medium
+            .hit(&beside, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+        let behind = Ray::new(
+            Point3D::new(0.0, 0.0, 5.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+            None,
+        );
+        assert!(medium
+            .hit(&behind, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn medium_follows_moving_boundary() {
+        let material = Box::new(Isotropic::new(Color::new(1.0, 1.0, 1.0)));
+        let boundary = Box::new(MovingSphere::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(10.0, 0.0, 0.0),
+            1.0,
+            material,
+            0.0,
+            1.0,
+        ));
+        let medium = fog(boundary, 1e9);
+        let ray_at = |time| {
+            Ray::new(
+                Point3D::new(10.0, 0.0, 5.0),
+                Vector3D::new(0.0, 0.0, -1.0),
+                Some(time),
+            )
+        };
+        let mut generator = StdRng::seed_from_u64(0);
+        assert!(medium
+            .hit(&ray_at(0.0), 0.001, f64::INFINITY, &mut generator)
+            .is_none());
+        assert!(medium
+            .hit(&ray_at(1.0), 0.001, f64::INFINITY, &mut generator)
+            .is_some());
+
+        let bounding_box = medium.bounding_box(0.0, 1.0).unwrap();
+        assert_relative_eq!(11.0, bounding_box.maximum.x());
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_have_zero_density() {
+        fog(unit_box(), 0.0);
+    }
+}
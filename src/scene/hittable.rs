@@ -1,25 +1,85 @@
-use crate::geometry::ray::Ray;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    geometry::{ray::Ray, vector_3d::Vector3D},
+    util::point::Point3D,
+};
 
 use super::{bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord};
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    /// Where `ray` first hits the object between `t_min` and `t_max`.
+    /// `generator` is for objects that are hit at random, like volumes.
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>>;
     fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox>;
+
+    /// The probability density, per unit solid angle, of `random_direction`
+    /// choosing `direction` from `origin`. Objects that can't be aimed at
+    /// give 0.
+    fn pdf_value(&self, _origin: &Point3D, _direction: &Vector3D) -> f64 {
+        0.0
+    }
+
+    /// A random direction from `origin` towards the object, so that lights
+    /// can be aimed at. Its length doesn't matter.
+    fn random_direction(&self, _origin: &Point3D, _generator: &mut dyn RngCore) -> Vector3D {
+        Vector3D::new(1.0, 0.0, 0.0)
+    }
+}
+
+/// Lets one object be in more than one list, such as the objects of a scene
+/// and its lights.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max, generator)
+    }
+
+    fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        (**self).bounding_box(time_0, time_1)
+    }
+
+    fn pdf_value(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Point3D, generator: &mut dyn RngCore) -> Vector3D {
+        (**self).random_direction(origin, generator)
+    }
 }
 
 impl Hittable for Vec<Box<dyn Hittable>> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
         let mut closest_so_far = t_max;
 
         let mut temp_record: Option<HitRecord> = None;
         for object in self.iter() {
-            if let Some(record) = object.hit(ray, t_min, closest_so_far) {
+            if let Some(record) = object.hit(ray, t_min, closest_so_far, generator) {
                 closest_so_far = record.t;
                 temp_record = Some(record)
             }
         }
 
-        return temp_record;
+        temp_record
     }
 
     fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
@@ -31,4 +91,24 @@ impl Hittable for Vec<Box<dyn Hittable>> {
             .into_iter()
             .reduce(|box_0, box_1| AxisAlignedBoundingBox::surrounding_box(&box_0, &box_1))
     }
+
+    /// The average over the objects, as each is equally likely to be aimed
+    /// at.
+    fn pdf_value(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        self.iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum::<f64>()
+            / self.len() as f64
+    }
+
+    /// Aims at one of the objects, picked at random.
+    fn random_direction(&self, origin: &Point3D, generator: &mut dyn RngCore) -> Vector3D {
+        if self.is_empty() {
+            return Vector3D::new(1.0, 0.0, 0.0);
+        }
+        self[generator.gen_range(0..self.len())].random_direction(origin, generator)
+    }
 }
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{
+    geometry::{ray::Ray, transform::Transform, vector_3d},
+    util::point::Point3D,
+};
+
+use super::{bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable};
+
+/// An object placed in the world by a transform. The object itself is shared,
+/// so one mesh can be placed many times without copying its triangles.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    /// From object space to world space.
+    transform: Transform,
+}
+
+impl Instance {
+    /// Returns a new instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `object` - What to place, in its own (object) space.
+    /// * `transform` - How to get from object space to world space.
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance { object, transform }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        // The direction isn't normalized, so `t` means the same in both spaces.
+        let inverse = self.transform.inverse();
+        let object_ray = Ray::new(
+            inverse.point(&ray.origin),
+            inverse.vector(&ray.direction),
+            Some(ray.time),
+        );
+        let mut record = self.object.hit(&object_ray, t_min, t_max, generator)?;
+
+        // The inverse-transpose keeps the sign of the dot product with the
+        // ray direction, so the normal still faces the same way as before.
+        record.p = self.transform.point(&record.p);
+        record.normal = vector_3d::unit_vector(&self.transform.normal(&record.normal));
+        Some(record)
+    }
+
+    fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        let object_box = self.object.bounding_box(time_0, time_1)?;
+
+        // Transform all eight corners, and take the box around them.
+        let mut minimum = Point3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut maximum = -minimum;
+        for corner in 0..8 {
+            let mut point = Point3D::empty();
+            for axis in 0..3 {
+                point[axis] = if corner & (1 << axis) == 0 {
+                    object_box.minimum[axis]
+                } else {
+                    object_box.maximum[axis]
+                };
+            }
+            let point = self.transform.point(&point);
+            for axis in 0..3 {
+                minimum[axis] = minimum[axis].min(point[axis]);
+                maximum[axis] = maximum[axis].max(point[axis]);
+            }
+        }
+        Some(AxisAlignedBoundingBox::new(minimum, maximum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        geometry::{bounded_volume_hierarchy::BvhNode, vector_3d::Vector3D},
+        scene::{box_shape::BoxShape, materials::Lambertian, sphere::Sphere},
+    };
+
+    use super::*;
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point3D::empty(),
+            1.0,
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    #[test]
+    fn can_hit_translated_object() {
+        let instance = Instance::new(
+            unit_sphere(),
+            Transform::translation(Vector3D::new(0.0, 0.0, -5.0)),
+        );
+        let ray = Ray::new(Point3D::empty(), Vector3D::new(0.0, 0.0, -1.0), None);
+        let record = instance
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(4.0, record.t);
+        assert_relative_eq!(Point3D::new(0.0, 0.0, -4.0), record.p);
+        assert_relative_eq!(Vector3D::new(0.0, 0.0, 1.0), record.normal);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn scaled_normals_are_unit_length_and_face_the_ray() {
+        // An ellipsoid stretched along x, hit on its side.
+        let instance = Instance::new(
+            unit_sphere(),
+            Transform::scaling(Vector3D::new(4.0, 1.0, 1.0)),
+        );
+        let ray = Ray::new(
+            Point3D::new(2.0, 5.0, 0.0),
+            Vector3D::new(0.0, -1.0, 0.0),
+            None,
+        );
+        let record = instance
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        let expected_y = 0.75f64.sqrt();
+        assert_relative_eq!(5.0 - expected_y, record.t);
+        assert_relative_eq!(1.0, record.normal.length());
+        // The gradient of x²/16 + y² is (x/8, 2y), at (2, √0.75).
+        let expected = vector_3d::unit_vector(&Vector3D::new(0.25, 2.0 * expected_y, 0.0));
+        assert_relative_eq!(expected, record.normal, epsilon = 1e-9);
+
+        let inside = Ray::new(Point3D::empty(), Vector3D::new(1.0, 0.0, 0.0), None);
+        let record = instance
+            .hit(&inside, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert!(!record.front_face);
+        assert_relative_eq!(4.0, record.t);
+        assert_relative_eq!(Vector3D::new(-1.0, 0.0, 0.0), record.normal);
+    }
+
+    #[test]
+    fn bounding_box_covers_rotated_object() {
+        let cube: Arc<dyn Hittable> = Arc::new(BoxShape::new(
+            Point3D::new(-1.0, -1.0, -1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        ));
+        let transform = Transform::rotation(Vector3D::new(0.0, 1.0, 0.0), 45.0)
+            .then(&Transform::translation(Vector3D::new(10.0, 0.0, 0.0)));
+        let instance = Instance::new(cube, transform);
+        let bounding_box = instance.bounding_box(0.0, 1.0).unwrap();
+        let half_diagonal = 2f64.sqrt();
+        assert_relative_eq!(
+            10.0 - half_diagonal,
+            bounding_box.minimum.x(),
+            epsilon = 1e-3
+        );
+        assert_relative_eq!(
+            10.0 + half_diagonal,
+            bounding_box.maximum.x(),
+            epsilon = 1e-3
+        );
+        assert_relative_eq!(1.0, bounding_box.maximum.y(), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn one_object_can_be_placed_many_times() {
+        let sphere = unit_sphere();
+        let instances: Vec<Box<dyn Hittable>> = (0..3)
+            .map(|i| {
+                let offset = Vector3D::new(3.0 * f64::from(i), 0.0, 0.0);
+                Box::new(Instance::new(
+                    Arc::clone(&sphere),
+                    Transform::translation(offset),
+                )) as Box<dyn Hittable>
+            })
+            .collect();
+        let bvh = BvhNode::new(instances, 0.0, 1.0);
+        for i in 0..3 {
+            let ray = Ray::new(
+                Point3D::new(3.0 * f64::from(i), 0.0, 5.0),
+                Vector3D::new(0.0, 0.0, -1.0),
+                None,
+            );
+            assert_relative_eq!(
+                4.0,
+                bvh.hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+                    .unwrap()
+                    .t
+            );
+        }
+    }
+}
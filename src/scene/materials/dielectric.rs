@@ -1,11 +1,12 @@
+use rand::{Rng, RngCore};
+
 use crate::{
-    geometry::{ray::Ray, vector_3d},
+    geometry::vector_3d::{self, Vector3D},
     scene::hit_record::HitRecord,
     util::color::Color,
 };
-use rand::Rng;
 
-use super::Material;
+use super::{Material, ScatterSample};
 
 pub struct Dielectric {
     index_of_refraction: f64,
@@ -27,18 +28,22 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
+    fn sample(
+        &self,
+        wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
         let refraction_ratio = if record.front_face {
             1.0 / self.index_of_refraction
         } else {
             self.index_of_refraction
         };
-        let unit_direction = vector_3d::unit_vector(&ray.direction);
+        let unit_direction = -wo;
         let cos_theta = (-unit_direction).dot(&record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
-        let mut generator = rand::thread_rng();
         let random_double = generator.gen_range(0.0..1.0);
         let reflectance = Dielectric::reflectance(cos_theta, refraction_ratio);
         let direction = if cannot_refract || reflectance > random_double {
@@ -48,9 +53,11 @@ impl Material for Dielectric {
             unit_direction.refract(&record.normal, refraction_ratio)
         };
 
-        Some((
-            Color::new(1.0, 1.0, 1.0),
-            Ray::new(record.p, direction, Some(ray.time)),
-        ))
+        Some(ScatterSample {
+            direction: vector_3d::unit_vector(&direction),
+            weight: Color::new(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            is_specular: true,
+        })
     }
 }
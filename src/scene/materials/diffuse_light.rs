@@ -0,0 +1,45 @@
+use rand::RngCore;
+
+use crate::{
+    geometry::vector_3d::Vector3D,
+    scene::{
+        hit_record::HitRecord,
+        textures::{SolidColorTexture, Texture},
+    },
+    util::{color::Color, point::Point3D},
+};
+
+use super::{Material, ScatterSample};
+
+/// A material that gives off light equally in every direction, and doesn't
+/// reflect any. Colors brighter than 1 make for stronger lights.
+pub struct DiffuseLight {
+    emit: Box<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(color: Color) -> DiffuseLight {
+        DiffuseLight {
+            emit: Box::new(SolidColorTexture::new(color)),
+        }
+    }
+
+    pub fn from_texture(texture: Box<dyn Texture>) -> DiffuseLight {
+        DiffuseLight { emit: texture }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(
+        &self,
+        _wo: &Vector3D,
+        _record: &HitRecord,
+        _generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
+        None
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: &Point3D) -> Color {
+        self.emit.color(u, v, point)
+    }
+}
@@ -0,0 +1,57 @@
+use std::f64::consts::PI;
+
+use rand::RngCore;
+
+use crate::{
+    geometry::vector_3d::Vector3D,
+    scene::{
+        hit_record::HitRecord,
+        textures::{SolidColorTexture, Texture},
+    },
+    util::color::Color,
+};
+
+use super::{Material, ScatterSample};
+
+/// The phase function of a participating medium such as fog or smoke: light
+/// that scatters goes off in any direction with equal probability.
+pub struct Isotropic {
+    albedo: Box<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic {
+            albedo: Box::new(SolidColorTexture::new(albedo)),
+        }
+    }
+
+    pub fn from_texture(texture: Box<dyn Texture>) -> Isotropic {
+        Isotropic { albedo: texture }
+    }
+}
+
+impl Material for Isotropic {
+    fn sample(
+        &self,
+        _wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
+        Some(ScatterSample {
+            direction: Vector3D::random_unit_vector(generator),
+            weight: self.albedo.color(record.u, record.v, &record.p),
+            pdf: 1.0 / (4.0 * PI),
+            is_specular: false,
+        })
+    }
+
+    fn evaluate(&self, wi: &Vector3D, wo: &Vector3D, record: &HitRecord) -> Color {
+        self.albedo.color(record.u, record.v, &record.p) * self.pdf(wi, wo, record)
+    }
+
+    /// Every direction is as likely.
+    fn pdf(&self, _wi: &Vector3D, _wo: &Vector3D, _record: &HitRecord) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
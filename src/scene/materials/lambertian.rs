@@ -1,5 +1,9 @@
+use std::f64::consts::PI;
+
+use rand::RngCore;
+
 use crate::{
-    geometry::{ray::Ray, vector_3d::Vector3D},
+    geometry::vector_3d::{OrthonormalBasis, Vector3D},
     scene::{
         hit_record::HitRecord,
         textures::{SolidColorTexture, Texture},
@@ -7,7 +11,7 @@ use crate::{
     util::color::Color,
 };
 
-use super::Material;
+use super::{Material, ScatterSample};
 
 pub struct Lambertian<'a> {
     albedo: Box<dyn Texture + 'a>,
@@ -26,19 +30,78 @@ impl<'a> Lambertian<'a> {
 }
 
 impl<'a> Material for Lambertian<'a> {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = record.normal + Vector3D::random_unit_vector();
+    /// Scatters following the cosine of the angle to the normal, which
+    /// cancels the cosine in `evaluate`, leaving the albedo as the weight.
+    fn sample(
+        &self,
+        _wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
+        let local = Vector3D::random_cosine_direction(generator);
+        let direction = OrthonormalBasis::new(&record.normal).local(&local);
+        Some(ScatterSample {
+            direction,
+            weight: self.albedo.color(record.u, record.v, &record.p),
+            pdf: local.z() / PI,
+            is_specular: false,
+        })
+    }
 
-        // Catch a degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = record.normal;
-        }
+    fn evaluate(&self, wi: &Vector3D, wo: &Vector3D, record: &HitRecord) -> Color {
+        self.albedo.color(record.u, record.v, &record.p) * self.pdf(wi, wo, record)
+    }
 
-        let attenuation = self.albedo.color(record.u, record.v, &record.p);
+    fn pdf(&self, wi: &Vector3D, _wo: &Vector3D, record: &HitRecord) -> f64 {
+        wi.dot(&record.normal).max(0.0) / PI
+    }
+}
 
-        Some((
-            attenuation,
-            Ray::new(record.p, scatter_direction, Some(ray.time)),
-        ))
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::util::point::Point3D;
+
+    fn record(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord {
+            p: Point3D::empty(),
+            normal: Vector3D::new(0.0, 1.0, 0.0),
+            material,
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            front_face: true,
+        }
+    }
+
+    #[test]
+    fn samples_agree_with_evaluate_and_pdf() {
+        let material = Lambertian::new(Color::new(0.2, 0.4, 0.6));
+        let record = record(&material);
+        let wo = Vector3D::new(0.6, 0.8, 0.0);
+        let mut generator = StdRng::seed_from_u64(4);
+        for _ in 0..100 {
+            let sample = material.sample(&wo, &record, &mut generator).unwrap();
+            assert!(!sample.is_specular);
+            assert!(sample.direction.y() >= 0.0);
+            assert_relative_eq!(1.0, sample.direction.length(), epsilon = 1e-12);
+            assert_relative_eq!(
+                sample.pdf,
+                material.pdf(&sample.direction, &wo, &record),
+                epsilon = 1e-12
+            );
+            assert_relative_eq!(
+                sample.weight,
+                material.evaluate(&sample.direction, &wo, &record) / sample.pdf,
+                epsilon = 1e-9
+            );
+        }
+        // Nothing scatters in to the surface.
+        let below = Vector3D::new(0.0, -1.0, 0.0);
+        assert_relative_eq!(0.0, material.pdf(&below, &wo, &record));
+        assert_relative_eq!(Color::empty(), material.evaluate(&below, &wo, &record));
     }
 }
@@ -1,5 +1,88 @@
-use crate::{geometry::ray::Ray, scene::hit_record::HitRecord, util::color::Color};
+use std::sync::Arc;
 
+use rand::RngCore;
+
+use crate::{
+    geometry::vector_3d::Vector3D,
+    scene::hit_record::HitRecord,
+    util::{color::Color, point::Point3D},
+};
+
+/// A direction picked by `Material::sample` for light to scatter in to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScatterSample {
+    /// The direction the scattered ray goes in, of unit length.
+    pub direction: Vector3D,
+    /// What the light coming back along the scattered ray is multiplied by:
+    /// `evaluate` over `pdf` for the direction, or the color of a mirror.
+    pub weight: Color,
+    /// The probability density, per unit solid angle, of picking the
+    /// direction. Means nothing for specular samples.
+    pub pdf: f64,
+    /// Whether the material only scatters in to this direction, like a
+    /// mirror or glass does. `evaluate` and `pdf` are 0 for every direction
+    /// then, so lights can't be aimed at.
+    pub is_specular: bool,
+}
+
+/// How light scatters off a material.
+///
+/// Directions point away from the hit point and are of unit length: `wo`
+/// towards where the ray came from, and `wi` along the scattered ray, where
+/// light comes in from.
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)>;
+    /// Picks a direction for a ray that hits the material to scatter in to.
+    /// Returns `None` if the ray is absorbed. Any random choice is made with
+    /// `generator`.
+    fn sample(
+        &self,
+        wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample>;
+
+    /// How much of the light coming in from `wi` leaves towards `wo`, with
+    /// the cosine of the angle of `wi` to the surface for surfaces. Materials
+    /// that only scatter in to single directions give black.
+    fn evaluate(&self, _wi: &Vector3D, _wo: &Vector3D, _record: &HitRecord) -> Color {
+        Color::empty()
+    }
+
+    /// The probability density, per unit solid angle, of `sample` picking
+    /// `wi` for `wo`. Materials that only scatter in to single directions
+    /// give 0.
+    fn pdf(&self, _wi: &Vector3D, _wo: &Vector3D, _record: &HitRecord) -> f64 {
+        0.0
+    }
+
+    /// Light given off by the material at a point. Most materials don't give
+    /// off any light, so this defaults to black.
+    fn emitted(&self, _u: f64, _v: f64, _point: &Point3D) -> Color {
+        Color::empty()
+    }
+}
+
+/// Lets one material be shared by many objects, by giving each object a
+/// `Box` holding a clone of the `Arc`.
+impl<T: Material + ?Sized> Material for Arc<T> {
+    fn sample(
+        &self,
+        wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
+        (**self).sample(wo, record, generator)
+    }
+
+    fn evaluate(&self, wi: &Vector3D, wo: &Vector3D, record: &HitRecord) -> Color {
+        (**self).evaluate(wi, wo, record)
+    }
+
+    fn pdf(&self, wi: &Vector3D, wo: &Vector3D, record: &HitRecord) -> f64 {
+        (**self).pdf(wi, wo, record)
+    }
+
+    fn emitted(&self, u: f64, v: f64, point: &Point3D) -> Color {
+        (**self).emitted(u, v, point)
+    }
 }
@@ -1,13 +1,12 @@
+use rand::RngCore;
+
 use crate::{
-    geometry::{
-        ray::Ray,
-        vector_3d::{self, Vector3D},
-    },
+    geometry::vector_3d::{self, Vector3D},
     scene::hit_record::HitRecord,
     util::color::Color,
 };
 
-use super::Material;
+use super::{Material, ScatterSample};
 
 pub struct Metal {
     albedo: Color,
@@ -25,16 +24,23 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Color, Ray)> {
-        let reflected = vector_3d::unit_vector(&ray.direction).reflect(&record.normal);
-        let scattered = Ray::new(
-            record.p,
-            reflected + self.fuzz * Vector3D::random_in_unit_sphere(),
-            Some(ray.time),
-        );
-        let attenuation = self.albedo;
-        if vector_3d::dot(&scattered.direction, &record.normal) > 0.0 {
-            return Some((attenuation, scattered));
+    /// Fuzzy reflections are still treated as specular, as the spread of
+    /// directions has no density to evaluate.
+    fn sample(
+        &self,
+        wo: &Vector3D,
+        record: &HitRecord,
+        generator: &mut dyn RngCore,
+    ) -> Option<ScatterSample> {
+        let reflected = (-wo).reflect(&record.normal);
+        let direction = reflected + self.fuzz * Vector3D::random_in_unit_sphere(generator);
+        if vector_3d::dot(&direction, &record.normal) > 0.0 {
+            return Some(ScatterSample {
+                direction: vector_3d::unit_vector(&direction),
+                weight: self.albedo,
+                pdf: 0.0,
+                is_specular: true,
+            });
         }
         None
     }
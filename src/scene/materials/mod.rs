@@ -1,9 +1,13 @@
 mod dielectric;
+mod diffuse_light;
+mod isotropic;
 mod lambertian;
 mod material;
 mod metal;
 
 pub use dielectric::Dielectric;
+pub use diffuse_light::DiffuseLight;
+pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
-pub use material::Material;
+pub use material::{Material, ScatterSample};
 pub use metal::Metal;
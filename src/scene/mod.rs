@@ -1,9 +1,20 @@
+pub mod aa_rect;
+pub mod background;
 pub mod bounding_box;
+pub mod box_shape;
+pub mod constant_medium;
 pub mod hit_record;
 pub mod hittable;
+pub mod instance;
 pub mod materials;
 pub mod moving_sphere;
+#[allow(clippy::module_inception)]
 pub mod scene;
+pub mod scene_file;
+pub mod settings;
 pub mod sphere;
 pub mod textures;
+pub mod triangle;
+pub mod triangle_mesh;
+pub mod wavefront;
 pub mod world;
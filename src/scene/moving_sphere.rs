@@ -1,5 +1,7 @@
 use std::f64::consts::PI;
 
+use rand::RngCore;
+
 use crate::{
     geometry::{
         ray::Ray,
@@ -41,12 +43,16 @@ impl MovingSphere {
         }
     }
 
-    fn center(&self, time: f64) -> Point3D {
+    pub(super) fn center(&self, time: f64) -> Point3D {
         self.start_center
             + ((time - self.start_time) / (self.end_time - self.start_time))
                 * (self.end_center - self.start_center)
     }
 
+    pub(super) fn radius(&self) -> f64 {
+        self.radius
+    }
+
     fn get_sphere_uv(point: &Point3D) -> (f64, f64) {
         let theta = (-point.y()).acos();
         let phi = (-point.z()).atan2(point.x()) + PI;
@@ -58,7 +64,13 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.center(ray.time);
         let a = ray.direction.length_squared();
         let half_b = vector_3d::dot(&oc, &ray.direction);
@@ -89,7 +101,7 @@ impl Hittable for MovingSphere {
             root,
             u,
             v,
-            &ray,
+            ray,
         );
 
         Some(record)
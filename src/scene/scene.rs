@@ -1,18 +1,26 @@
+use rand::RngCore;
+
 use crate::{
-    geometry::{bounded_volume_hierarchy::BvhNode, vector_3d::Vector3D},
+    geometry::{flat_bvh::FlatBvh, vector_3d::Vector3D},
     util::{camera::Camera, point::Point3D},
 };
 
-use super::world;
+use super::{background::Background, hittable::Hittable, world};
 
 pub struct Scene {
     pub camera: Camera,
-    pub objects: BvhNode,
+    pub objects: FlatBvh,
+    /// Objects that give off light, which are also among `objects`. Shadow
+    /// rays are aimed at them, so small lights don't need lucky bounces to
+    /// be found.
+    pub lights: Vec<Box<dyn Hittable>>,
+    /// What rays that don't hit any objects see.
+    pub background: Background,
 }
 
 impl Scene {
     pub fn two_balls(aspect_ratio: f64) -> Scene {
-        let objects = BvhNode::new(world::two_balls(), 0.0, 1.0);
+        let objects = FlatBvh::new(world::two_balls(), 0.0, 1.0);
 
         // Camera
         let look_from = Point3D::new(13.0, 2.0, 3.0);
@@ -33,11 +41,17 @@ impl Scene {
             1.0,
         );
 
-        Scene { camera, objects }
+        Scene {
+            camera,
+            objects,
+            lights: vec![],
+            background: Background::default(),
+        }
     }
 
-    pub fn random_scene(aspect_ratio: f64) -> Scene {
-        let objects = BvhNode::new(world::random_scene(), 0.0, 1.0);
+    /// The scene of `world::random_scene`, made with `generator`.
+    pub fn random_scene(aspect_ratio: f64, generator: &mut dyn RngCore) -> Scene {
+        let objects = FlatBvh::new(world::random_scene(generator), 0.0, 1.0);
 
         // Camera
         let look_from = Point3D::new(13.0, 2.0, 3.0);
@@ -57,6 +71,11 @@ impl Scene {
             1.0,
         );
 
-        Scene { camera, objects }
+        Scene {
+            camera,
+            objects,
+            lights: vec![],
+            background: Background::default(),
+        }
     }
 }
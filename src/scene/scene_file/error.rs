@@ -0,0 +1,70 @@
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong when loading a scene file.
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// A file couldn't be opened or read.
+    Io { path: PathBuf, error: io::Error },
+    /// The scene file doesn't describe a valid scene.
+    Invalid {
+        path: PathBuf,
+        /// Line number of the offending statement, starting at 1, if the
+        /// problem is with a single statement.
+        line: Option<usize>,
+        /// The key of the statement with the problem, if it's a single value.
+        key: Option<String>,
+        message: String,
+    },
+}
+
+impl SceneFileError {
+    pub(super) fn invalid(
+        path: &Path,
+        line: Option<usize>,
+        key: Option<&str>,
+        message: String,
+    ) -> SceneFileError {
+        SceneFileError::Invalid {
+            path: path.to_path_buf(),
+            line,
+            key: key.map(String::from),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneFileError::Invalid {
+                path,
+                line,
+                key,
+                message,
+            } => {
+                write!(f, "{}", path.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                if let Some(key) = key {
+                    write!(f, ": `{}`", key)?;
+                }
+                write!(f, ": {}", message)
+            }
+        }
+    }
+}
+
+impl Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneFileError::Io { error, .. } => Some(error),
+            SceneFileError::Invalid { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,1087 @@
+//! A text format describing a scene, so scenes can be changed without
+//! recompiling.
+//!
+//! Each line is a statement: a keyword, positional arguments, and
+//! `key=value` pairs. Vectors and colors are written as `x,y,z`, and `#`
+//! starts a comment. For example:
+//!
+//! ```text
+//! camera look_from=13,2,3 look_at=0,0,0 vfov=20 aperture=0.1
+//! render width=400 aspect_ratio=3:2 samples=100 max_depth=50
+//!
+//! texture checks checker even=0.2,0.3,0.1 odd=0.9,0.9,0.9
+//! material ground lambertian albedo=checks
+//! material glass dielectric ior=1.5
+//!
+//! sphere center=0,-1000,0 radius=1000 material=ground
+//! sphere center=0,1,0 radius=1 material=glass
+//! ```
+//!
+//! Statements:
+//!
+//! * `camera` - `look_from`, `look_at`, `up`, `vfov`, `aperture`,
+//!   `focus_distance` and `shutter` (open and close time).
+//! * `render` - `width`, `aspect_ratio` (`3:2` or `1.5`), `samples`,
+//!   `max_depth`, and for 8-bit images `tone_map` (`clamp`, `reinhard`,
+//!   `extended_reinhard`, `aces` or `hable`), `exposure` in stops and
+//!   `white_point` (for `extended_reinhard`).
+//! * `background <type>` - `solid` (`color`), `gradient` (`bottom` and
+//!   `top`), or `environment` (`file`, an equirectangular Radiance `.hdr`
+//!   image, `intensity` and `rotation` in degrees). Defaults to a sky
+//!   gradient.
+//! * `texture <name> <type>` - `solid` (`color`), `checker` (`even` and
+//!   `odd`) or `image` (`file`, a PPM image).
+//! * `material <name> <type>` - `lambertian` (`albedo`), `metal` (`albedo`
+//!   and `fuzz`), `dielectric` (`ior`), `diffuse_light` (`emit`, a
+//!   texture whose colors may be brighter than 1) or `isotropic` (`albedo`,
+//!   for volumes).
+//! * `sphere` - `center`, `radius` and `material`.
+//! * `moving_sphere` - `from`, `to`, `time`, `radius` and `material`.
+//! * `triangle` - `a`, `b`, `c` and `material`.
+//! * `xy_rect`, `xz_rect`, `yz_rect` - the ranges along the two axes in the
+//!   plane (`x=0,555`, low end first), the position along the third axis, `material`, and
+//!   `flip=true` to turn the normal towards the negative axis.
+//! * `box` - `min`, `max` and `material`.
+//! * `mesh` - `file`, an OBJ file with its own materials.
+//!
+//! Every object can also be given `scale` (per axis), `rotate` (degrees
+//! around x, then y, then z) and `translate`, applied in that order. A mesh
+//! file placed like this is loaded once, however often it is used.
+//!
+//! Spheres, moving spheres and boxes can be filled with smoke or fog by
+//! giving them a `density`. Other objects aren't closed, so they can't hold a
+//! volume. The object is then the boundary of the volume, and its
+//! material (usually `isotropic`) decides how light scatters inside.
+//!
+//! Spheres and rectangles with a `diffuse_light` material, and no transform
+//! or density, are also lights that shadow rays are aimed at. Other objects
+//! still give off light, but are only found by rays that happen to hit them.
+//!
+//! Wherever a texture is expected, a color can be given instead. Files are
+//! relative to the scene file.
+
+mod error;
+mod statement;
+
+pub use error::SceneFileError;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    geometry::{flat_bvh::FlatBvh, transform::Transform, vector_3d::Vector3D},
+    util::{camera::Camera, color::Color, image, point::Point3D, tone_map::ToneMapOperator},
+};
+
+use self::statement::Statement;
+
+use super::{
+    aa_rect::{XyRect, XzRect, YzRect},
+    background::{Background, EnvironmentMap},
+    box_shape::BoxShape,
+    constant_medium::ConstantMedium,
+    hittable::Hittable,
+    instance::Instance,
+    materials::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal},
+    moving_sphere::MovingSphere,
+    scene::Scene,
+    settings::RenderSettings,
+    sphere::Sphere,
+    textures::{CheckerTexture, ImageTexture, SolidColorTexture, Texture},
+    triangle::Triangle,
+    wavefront,
+};
+
+/// Where the camera is, and how it is set up.
+struct CameraDescription {
+    look_from: Point3D,
+    look_at: Point3D,
+    up: Vector3D,
+    vertical_fov: f64,
+    aperture: f64,
+    /// Defaults to the distance to `look_at`.
+    focus_distance: Option<f64>,
+    shutter: (f64, f64),
+}
+
+/// A scene loaded from a scene file, ready to be turned in to a `Scene` once
+/// the aspect ratio of the image is known.
+pub struct SceneDescription {
+    camera: CameraDescription,
+    objects: Vec<Box<dyn Hittable>>,
+    lights: Vec<Box<dyn Hittable>>,
+    background: Background,
+    width: Option<i32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    tone_map: Option<ToneMapOperator>,
+    exposure: Option<f64>,
+    white_point: Option<f64>,
+}
+
+impl SceneDescription {
+    /// Loads and validates a scene file.
+    pub fn load(path: impl AsRef<Path>) -> Result<SceneDescription, SceneFileError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| SceneFileError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        SceneDescription::parse(BufReader::new(file), path)
+    }
+
+    /// Parses and validates a scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Where to read the scene from.
+    /// * `path` - Where the scene came from. Used in error messages, and
+    ///   files named in the scene are relative to it.
+    pub fn parse(reader: impl BufRead, path: &Path) -> Result<SceneDescription, SceneFileError> {
+        let mut parser = Parser {
+            directory: path.parent().unwrap_or_else(|| Path::new("")),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            light_materials: HashSet::new(),
+            camera: None,
+            render_line: None,
+            background: None,
+            objects: vec![],
+            lights: vec![],
+            meshes: HashMap::new(),
+            width: None,
+            aspect_ratio: None,
+            samples_per_pixel: None,
+            max_depth: None,
+            tone_map: None,
+            exposure: None,
+            white_point: None,
+        };
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|error| SceneFileError::Io {
+                path: path.to_path_buf(),
+                error,
+            })?;
+            if let Some(statement) = Statement::parse(path, index + 1, &line)? {
+                parser.statement(statement)?;
+            }
+        }
+
+        let camera = parser.camera.ok_or_else(|| {
+            SceneFileError::invalid(path, None, None, String::from("no `camera` statement"))
+        })?;
+        if parser.objects.is_empty() {
+            return Err(SceneFileError::invalid(
+                path,
+                None,
+                None,
+                String::from("the scene has no objects"),
+            ));
+        }
+
+        Ok(SceneDescription {
+            camera,
+            objects: parser.objects,
+            lights: parser.lights,
+            background: parser.background.unwrap_or_default(),
+            width: parser.width,
+            aspect_ratio: parser.aspect_ratio,
+            samples_per_pixel: parser.samples_per_pixel,
+            max_depth: parser.max_depth,
+            tone_map: parser.tone_map,
+            exposure: parser.exposure,
+            white_point: parser.white_point,
+        })
+    }
+
+    /// Overrides the settings with those given in the `render` statement.
+    pub fn apply_settings(&self, settings: &mut RenderSettings) {
+        if let Some(width) = self.width {
+            settings.width = width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            settings.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            settings.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(operator) = self.tone_map {
+            settings.tone_mapping.operator = operator;
+        }
+        if let Some(exposure) = self.exposure {
+            settings.tone_mapping.exposure = exposure;
+        }
+        if let Some(white_point) = self.white_point {
+            settings.tone_mapping.white_point = white_point;
+        }
+    }
+
+    /// Builds the scene, with a camera for an image of the given aspect ratio.
+    pub fn into_scene(self, aspect_ratio: f64) -> Scene {
+        let camera = &self.camera;
+        let (start_time, end_time) = camera.shutter;
+        let focus_distance = camera
+            .focus_distance
+            .unwrap_or_else(|| (camera.look_from - camera.look_at).length());
+        let camera = Camera::new(
+            camera.look_from,
+            camera.look_at,
+            camera.up,
+            camera.vertical_fov,
+            aspect_ratio,
+            camera.aperture,
+            focus_distance,
+            start_time,
+            end_time,
+        );
+        let objects = FlatBvh::new(self.objects, start_time, end_time);
+        Scene {
+            camera,
+            objects,
+            lights: self.lights,
+            background: self.background,
+        }
+    }
+}
+
+/// State while going through the statements of a scene file.
+struct Parser<'a> {
+    directory: &'a Path,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    /// The names of the materials that give off light.
+    light_materials: HashSet<String>,
+    camera: Option<CameraDescription>,
+    render_line: Option<usize>,
+    background: Option<Background>,
+    objects: Vec<Box<dyn Hittable>>,
+    /// Objects that are also in `objects`, that shadow rays are aimed at.
+    lights: Vec<Box<dyn Hittable>>,
+    /// Mesh files that have been placed with a transform, so they can be
+    /// shared between instances.
+    meshes: HashMap<PathBuf, Arc<dyn Hittable>>,
+    width: Option<i32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    tone_map: Option<ToneMapOperator>,
+    exposure: Option<f64>,
+    white_point: Option<f64>,
+}
+
+impl<'a> Parser<'a> {
+    fn statement(&mut self, mut statement: Statement) -> Result<(), SceneFileError> {
+        match statement.keyword.as_str() {
+            "camera" => self.camera(&mut statement)?,
+            "render" => self.render(&mut statement)?,
+            "background" => self.background(&mut statement)?,
+            "texture" => self.texture(&mut statement)?,
+            "material" => self.material(&mut statement)?,
+            "sphere" | "moving_sphere" | "triangle" | "xy_rect" | "xz_rect" | "yz_rect" | "box"
+            | "mesh" => self.object(&mut statement)?,
+            keyword => {
+                return Err(statement.error(None, format!("unknown statement `{}`", keyword)))
+            }
+        }
+        statement.finish()
+    }
+
+    fn camera(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&[])?;
+        if self.camera.is_some() {
+            return Err(statement.error(None, String::from("the camera is already set up")));
+        }
+
+        let aperture = statement.optional("aperture")?.unwrap_or(0.0);
+        if aperture < 0.0 {
+            return Err(statement.error(Some("aperture"), String::from("can't be negative")));
+        }
+        let focus_distance = statement.optional("focus_distance")?;
+        if focus_distance.is_some_and(|distance| distance <= 0.0) {
+            return Err(statement.error(Some("focus_distance"), String::from("must be positive")));
+        }
+        let vertical_fov = statement.optional("vfov")?.unwrap_or(20.0);
+        if !(0.0..180.0).contains(&vertical_fov) || vertical_fov == 0.0 {
+            return Err(statement.error(
+                Some("vfov"),
+                String::from("must be between 0 and 180 degrees"),
+            ));
+        }
+        let shutter: (f64, f64) = statement.optional("shutter")?.unwrap_or((0.0, 1.0));
+        if shutter.1 < shutter.0 {
+            return Err(statement.error(Some("shutter"), String::from("must close after it opens")));
+        }
+
+        self.camera = Some(CameraDescription {
+            look_from: statement.required("look_from")?,
+            look_at: statement.required("look_at")?,
+            up: statement
+                .optional("up")?
+                .unwrap_or_else(|| Vector3D::new(0.0, 1.0, 0.0)),
+            vertical_fov,
+            aperture,
+            focus_distance,
+            shutter,
+        });
+        Ok(())
+    }
+
+    fn render(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&[])?;
+        if let Some(line) = self.render_line {
+            return Err(statement.error(
+                None,
+                format!("render settings were already given on line {}", line),
+            ));
+        }
+        self.render_line = Some(statement.line);
+
+        for (key, value) in [
+            ("width", &mut self.width),
+            ("samples", &mut self.samples_per_pixel),
+            ("max_depth", &mut self.max_depth),
+        ] {
+            *value = statement.optional(key)?;
+            if value.is_some_and(|value| value <= 0) {
+                return Err(statement.error(Some(key), String::from("must be positive")));
+            }
+        }
+
+        if let Some(text) = statement.take_raw("aspect_ratio") {
+            let ratio = match text.split_once(':') {
+                Some((width, height)) => width
+                    .parse::<f64>()
+                    .and_then(|width| height.parse::<f64>().map(|height| width / height))
+                    .ok(),
+                None => text.parse::<f64>().ok(),
+            };
+            match ratio {
+                Some(ratio) if ratio.is_finite() && ratio > 0.0 => self.aspect_ratio = Some(ratio),
+                _ => {
+                    return Err(statement.error(
+                        Some("aspect_ratio"),
+                        format!("expected a ratio like `3:2` or `1.5`, found {:?}", text),
+                    ))
+                }
+            }
+        }
+
+        self.tone_map = statement.optional("tone_map")?;
+        self.exposure = statement.optional("exposure")?;
+        self.white_point = statement.optional("white_point")?;
+        if self
+            .white_point
+            .is_some_and(|white_point| !(white_point > 0.0 && white_point.is_finite()))
+        {
+            return Err(statement.error(Some("white_point"), String::from("must be positive")));
+        }
+        Ok(())
+    }
+
+    fn background(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&["type"])?;
+        if self.background.is_some() {
+            return Err(statement.error(None, String::from("the background is already set")));
+        }
+
+        let background = match statement.arguments[0].as_str() {
+            "solid" => Background::Solid(statement.required("color")?),
+            "gradient" => Background::Gradient {
+                bottom: statement.required("bottom")?,
+                top: statement.required("top")?,
+            },
+            "environment" => {
+                let file: String = statement.required("file")?;
+                let intensity = statement.optional("intensity")?.unwrap_or(1.0);
+                let rotation = statement.optional("rotation")?.unwrap_or(0.0);
+                let image_path = self.directory.join(&file);
+                let image = File::open(&image_path)
+                    .and_then(|file| image::read_hdr(&mut BufReader::new(file)))
+                    .map_err(|error| {
+                        statement.error(
+                            Some("file"),
+                            format!("can't load {}: {}", image_path.display(), error),
+                        )
+                    })?;
+                Background::Environment(EnvironmentMap::new(Arc::new(image), intensity, rotation))
+            }
+            kind => {
+                return Err(statement.error(
+                    None,
+                    format!(
+                        "unknown background type `{}`, expected solid, gradient or environment",
+                        kind
+                    ),
+                ))
+            }
+        };
+        self.background = Some(background);
+        Ok(())
+    }
+
+    fn texture(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&["name", "type"])?;
+        let name = statement.arguments[0].clone();
+        if self.textures.contains_key(&name) {
+            return Err(statement.error(None, format!("texture `{}` already exists", name)));
+        }
+
+        let texture: Arc<dyn Texture> = match statement.arguments[1].as_str() {
+            "solid" => Arc::new(SolidColorTexture::new(statement.required("color")?)),
+            "checker" => {
+                let even = self.texture_value(statement, "even")?;
+                let odd = self.texture_value(statement, "odd")?;
+                Arc::new(CheckerTexture::from_textures(Box::new(even), Box::new(odd)))
+            }
+            "image" => {
+                let file: String = statement.required("file")?;
+                let image_path = self.directory.join(&file);
+                let image = File::open(&image_path)
+                    .and_then(|file| image::read_ppm(&mut BufReader::new(file)))
+                    .map_err(|error| {
+                        statement.error(
+                            Some("file"),
+                            format!("can't load {}: {}", image_path.display(), error),
+                        )
+                    })?;
+                Arc::new(ImageTexture::new(Arc::new(image)))
+            }
+            kind => {
+                return Err(statement.error(
+                    None,
+                    format!(
+                        "unknown texture type `{}`, expected solid, checker or image",
+                        kind
+                    ),
+                ))
+            }
+        };
+        self.textures.insert(name, texture);
+        Ok(())
+    }
+
+    fn material(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&["name", "type"])?;
+        let name = statement.arguments[0].clone();
+        if self.materials.contains_key(&name) {
+            return Err(statement.error(None, format!("material `{}` already exists", name)));
+        }
+
+        let material: Arc<dyn Material> = match statement.arguments[1].as_str() {
+            "lambertian" => {
+                let albedo = self.texture_value(statement, "albedo")?;
+                Arc::new(Lambertian::from_texture(Box::new(albedo)))
+            }
+            "metal" => {
+                let albedo: Color = statement.required("albedo")?;
+                let fuzz = statement.optional("fuzz")?.unwrap_or(0.0);
+                Arc::new(Metal::new(albedo, fuzz))
+            }
+            "dielectric" => Arc::new(Dielectric::new(statement.required("ior")?)),
+            "diffuse_light" => {
+                let emit = self.texture_value(statement, "emit")?;
+                self.light_materials.insert(name.clone());
+                Arc::new(DiffuseLight::from_texture(Box::new(emit)))
+            }
+            "isotropic" => {
+                let albedo = self.texture_value(statement, "albedo")?;
+                Arc::new(Isotropic::from_texture(Box::new(albedo)))
+            }
+            kind => {
+                return Err(statement.error(
+                    None,
+                    format!(
+                        "unknown material type `{}`, expected lambertian, metal, dielectric, diffuse_light or isotropic",
+                        kind
+                    ),
+                ))
+            }
+        };
+        self.materials.insert(name, material);
+        Ok(())
+    }
+
+    fn object(&mut self, statement: &mut Statement) -> Result<(), SceneFileError> {
+        statement.expect_arguments(&[])?;
+        let transform = self.transform(statement)?;
+        let density: Option<f64> = statement.optional("density")?;
+        if density.is_some_and(|density| density <= 0.0) {
+            return Err(statement.error(Some("density"), String::from("must be positive")));
+        }
+        // A ray in a volume has to be able to find where it leaves.
+        let closed = matches!(
+            statement.keyword.as_str(),
+            "sphere" | "moving_sphere" | "box"
+        );
+        if density.is_some() && !closed {
+            return Err(statement.error(
+                Some("density"),
+                format!(
+                    "a {} isn't closed, so it can't be the boundary of a volume",
+                    statement.keyword
+                ),
+            ));
+        }
+        // The material is also the phase function of a volume.
+        let phase_function = statement
+            .peek_raw("material")
+            .and_then(|name| self.materials.get(name))
+            .cloned();
+        // Only these shapes can be aimed at, and only where they were placed.
+        let is_light = matches!(
+            statement.keyword.as_str(),
+            "sphere" | "xy_rect" | "xz_rect" | "yz_rect"
+        ) && transform.is_none()
+            && density.is_none()
+            && statement
+                .peek_raw("material")
+                .is_some_and(|name| self.light_materials.contains(name));
+        match statement.keyword.as_str() {
+            "sphere" => {
+                let center = statement.required("center")?;
+                let radius = self.radius(statement)?;
+                let material = self.material_value(statement)?;
+                self.objects
+                    .push(Box::new(Sphere::new(center, radius, material)));
+            }
+            "moving_sphere" => {
+                let from = statement.required("from")?;
+                let to = statement.required("to")?;
+                let (start_time, end_time) = statement.optional("time")?.unwrap_or((0.0, 1.0));
+                if end_time <= start_time {
+                    return Err(
+                        statement.error(Some("time"), String::from("must end after it starts"))
+                    );
+                }
+                let radius = self.radius(statement)?;
+                let material = self.material_value(statement)?;
+                self.objects.push(Box::new(MovingSphere::new(
+                    from, to, radius, material, start_time, end_time,
+                )));
+            }
+            "triangle" => {
+                let a = statement.required("a")?;
+                let b = statement.required("b")?;
+                let c = statement.required("c")?;
+                let material = self.material_value(statement)?;
+                self.objects
+                    .push(Box::new(Triangle::new(a, b, c, material)));
+            }
+            "xy_rect" => {
+                let x = self.range(statement, "x")?;
+                let y = self.range(statement, "y")?;
+                let z = statement.required("z")?;
+                let material = self.material_value(statement)?;
+                let rect = XyRect::new(x, y, z, material);
+                self.push_rect(statement, rect, XyRect::flipped)?;
+            }
+            "xz_rect" => {
+                let x = self.range(statement, "x")?;
+                let z = self.range(statement, "z")?;
+                let y = statement.required("y")?;
+                let material = self.material_value(statement)?;
+                let rect = XzRect::new(x, z, y, material);
+                self.push_rect(statement, rect, XzRect::flipped)?;
+            }
+            "yz_rect" => {
+                let y = self.range(statement, "y")?;
+                let z = self.range(statement, "z")?;
+                let x = statement.required("x")?;
+                let material = self.material_value(statement)?;
+                let rect = YzRect::new(y, z, x, material);
+                self.push_rect(statement, rect, YzRect::flipped)?;
+            }
+            "box" => {
+                let minimum = statement.required("min")?;
+                let maximum = statement.required("max")?;
+                let material = self.material_value(statement)?;
+                self.objects
+                    .push(Box::new(BoxShape::new(minimum, maximum, material)));
+            }
+            _ => {
+                let file: String = statement.required("file")?;
+                let path = self.directory.join(&file);
+                let transform = match transform {
+                    Some(transform) => transform,
+                    None => {
+                        let triangles = wavefront::load_obj(&path)
+                            .map_err(|error| statement.error(Some("file"), error.to_string()))?;
+                        self.objects.extend(triangles);
+                        return Ok(());
+                    }
+                };
+                let mesh = match self.meshes.get(&path) {
+                    Some(mesh) => Arc::clone(mesh),
+                    None => {
+                        let triangles = wavefront::load_obj(&path)
+                            .map_err(|error| statement.error(Some("file"), error.to_string()))?;
+                        if triangles.is_empty() {
+                            return Err(statement.error(
+                                Some("file"),
+                                format!("{} has no faces to place", path.display()),
+                            ));
+                        }
+                        let mesh: Arc<dyn Hittable> = Arc::new(FlatBvh::new(triangles, 0.0, 1.0));
+                        self.meshes.insert(path, Arc::clone(&mesh));
+                        mesh
+                    }
+                };
+                self.objects.push(Box::new(Instance::new(mesh, transform)));
+                return Ok(());
+            }
+        }
+
+        if let Some(transform) = transform {
+            let object = self.objects.pop().expect("an object was just added");
+            self.objects
+                .push(Box::new(Instance::new(Arc::from(object), transform)));
+        }
+        if let (Some(density), Some(phase_function)) = (density, phase_function) {
+            let boundary = self.objects.pop().expect("an object was just added");
+            self.objects.push(Box::new(ConstantMedium::new(
+                boundary,
+                density,
+                Box::new(phase_function),
+            )));
+        }
+        if is_light {
+            let light: Arc<dyn Hittable> =
+                Arc::from(self.objects.pop().expect("an object was just added"));
+            self.objects.push(Box::new(Arc::clone(&light)));
+            self.lights.push(Box::new(light));
+        }
+        Ok(())
+    }
+
+    /// Reads the optional `scale`, `rotate` and `translate` keys of an
+    /// object. Returns `None` if none of them were given.
+    fn transform(&self, statement: &mut Statement) -> Result<Option<Transform>, SceneFileError> {
+        let scale: Option<Vector3D> = statement.optional("scale")?;
+        let rotate: Option<Vector3D> = statement.optional("rotate")?;
+        let translate: Option<Vector3D> = statement.optional("translate")?;
+        if scale.is_none() && rotate.is_none() && translate.is_none() {
+            return Ok(None);
+        }
+
+        let mut transform = Transform::identity();
+        if let Some(scale) = scale {
+            if (0..3).any(|axis| scale[axis] == 0.0) {
+                return Err(
+                    statement.error(Some("scale"), String::from("can't be zero along any axis"))
+                );
+            }
+            transform = transform.then(&Transform::scaling(scale));
+        }
+        if let Some(rotate) = rotate {
+            let axes = [
+                Vector3D::new(1.0, 0.0, 0.0),
+                Vector3D::new(0.0, 1.0, 0.0),
+                Vector3D::new(0.0, 0.0, 1.0),
+            ];
+            for (axis, direction) in axes.iter().enumerate() {
+                transform = transform.then(&Transform::rotation(*direction, rotate[axis]));
+            }
+        }
+        if let Some(translate) = translate {
+            transform = transform.then(&Transform::translation(translate));
+        }
+        Ok(Some(transform))
+    }
+
+    /// Adds a rectangle, turned around if the statement has `flip=true`.
+    fn push_rect<T: Hittable + 'static>(
+        &mut self,
+        statement: &mut Statement,
+        rect: T,
+        flip: fn(T) -> T,
+    ) -> Result<(), SceneFileError> {
+        let rect = if statement.optional("flip")?.unwrap_or(false) {
+            flip(rect)
+        } else {
+            rect
+        };
+        self.objects.push(Box::new(rect));
+        Ok(())
+    }
+
+    fn radius(&self, statement: &mut Statement) -> Result<f64, SceneFileError> {
+        let radius: f64 = statement.required("radius")?;
+        if radius <= 0.0 {
+            return Err(statement.error(Some("radius"), String::from("must be positive")));
+        }
+        Ok(radius)
+    }
+
+    /// Reads one side of a rectangle, which can't be empty.
+    fn range(&self, statement: &mut Statement, key: &str) -> Result<(f64, f64), SceneFileError> {
+        let range: (f64, f64) = statement.required(key)?;
+        if range.1 <= range.0 {
+            return Err(statement.error(Some(key), String::from("must end after it starts")));
+        }
+        Ok(range)
+    }
+
+    /// Looks up the material named by the `material` key.
+    fn material_value(
+        &self,
+        statement: &mut Statement,
+    ) -> Result<Box<dyn Material>, SceneFileError> {
+        let name: String = statement.required("material")?;
+        match self.materials.get(&name) {
+            Some(material) => Ok(Box::new(Arc::clone(material))),
+            None => Err(statement.error(Some("material"), format!("no material named `{}`", name))),
+        }
+    }
+
+    /// Reads a value that is either a color, or the name of a texture.
+    fn texture_value(
+        &self,
+        statement: &mut Statement,
+        key: &str,
+    ) -> Result<Arc<dyn Texture>, SceneFileError> {
+        let text = statement
+            .take_raw(key)
+            .ok_or_else(|| statement.error(Some(key), String::from("is required")))?;
+        if let Some(texture) = self.textures.get(&text) {
+            return Ok(Arc::clone(texture));
+        }
+        match <Color as statement::Value>::parse_value(&text) {
+            Ok(color) => Ok(Arc::new(SolidColorTexture::new(color))),
+            Err(_) => Err(statement.error(
+                Some(key),
+                format!(
+                    "expected a color like `0.5,0.5,0.5` or a texture name, found {:?}",
+                    text
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::util::tone_map::ToneMapping;
+
+    use super::*;
+
+    /// The path of an example scene shipped with the source.
+    fn example_scene() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/example.scene")
+    }
+
+    fn parse(text: &str) -> Result<SceneDescription, SceneFileError> {
+        SceneDescription::parse(text.as_bytes(), Path::new("test.scene"))
+    }
+
+    /// Asserts that parsing fails on the given line and key.
+    fn assert_invalid(text: &str, expected_line: usize, expected_key: Option<&str>) {
+        match parse(text) {
+            Err(SceneFileError::Invalid { line, key, .. }) => {
+                assert_eq!(Some(expected_line), line);
+                assert_eq!(expected_key, key.as_deref());
+            }
+            Err(error) => panic!("Expected a validation error, got {}", error),
+            Ok(_) => panic!("Expected a validation error"),
+        }
+    }
+
+    const MINIMAL: &str = "camera look_from=0,0,-5 look_at=0,0,0\n\
+                           material white lambertian albedo=1,1,1\n\
+                           sphere center=0,0,0 radius=1 material=white\n";
+
+    #[test]
+    fn can_load_example_scene() {
+        let description = SceneDescription::load(example_scene()).unwrap();
+        let mut settings = RenderSettings::default();
+        description.apply_settings(&mut settings);
+        assert_eq!(300, settings.width);
+        assert_eq!(16.0 / 9.0, settings.aspect_ratio);
+        assert_eq!(20, settings.samples_per_pixel);
+        description.into_scene(settings.aspect_ratio);
+    }
+
+    #[test]
+    fn can_load_two_balls() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/two_balls.scene");
+        let description = SceneDescription::load(path).unwrap();
+        assert_eq!(2, description.objects.len());
+        assert!(description.lights.is_empty());
+    }
+
+    #[test]
+    fn can_load_cornell_box() {
+        for name in ["cornell_box", "cornell_smoke"] {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("scenes")
+                .join(format!("{}.scene", name));
+            let description = SceneDescription::load(path).unwrap();
+            assert_eq!(8, description.objects.len());
+            assert_eq!(1, description.lights.len());
+            assert!(matches!(description.background, Background::Solid(_)));
+        }
+    }
+
+    #[test]
+    fn lights_can_be_aimed_at() {
+        let text = format!(
+            "{}material lamp diffuse_light emit=4,4,4\n\
+             sphere center=0,3,0 radius=0.5 material=lamp\n\
+             xz_rect x=0,1 z=0,1 y=5 material=lamp flip=true\n\
+             triangle a=0,0,0 b=1,0,0 c=0,1,0 material=lamp\n\
+             sphere center=0,3,0 radius=0.5 material=lamp translate=1,0,0\n\
+             sphere center=0,-3,0 radius=0.5 material=white\n",
+            MINIMAL
+        );
+        let description = parse(&text).unwrap();
+        assert_eq!(6, description.objects.len());
+        assert_eq!(2, description.lights.len());
+        let origin = Point3D::empty();
+        let light = &description.lights[0];
+        let direction = light.random_direction(&origin, &mut StdRng::seed_from_u64(0));
+        assert!(light.pdf_value(&origin, &direction) > 0.0);
+    }
+
+    #[test]
+    fn objects_can_be_transformed() {
+        let text = format!(
+            "{}box min=0,0,0 max=1,1,1 material=white rotate=0,15,0 translate=2,0,0\n",
+            MINIMAL
+        );
+        let description = parse(&text).unwrap();
+        let bounding_box = description.objects[1].bounding_box(0.0, 1.0).unwrap();
+        assert!(bounding_box.minimum.x() > 1.5);
+        assert!(bounding_box.maximum.x() > 3.0);
+        assert_invalid(
+            &format!(
+                "{}sphere center=0,0,0 radius=1 material=white scale=1,0,1\n",
+                MINIMAL
+            ),
+            4,
+            Some("scale"),
+        );
+    }
+
+    #[test]
+    fn objects_can_be_volumes() {
+        let text = format!(
+            "{}material smoke isotropic albedo=0,0,0\n\
+             box min=0,0,0 max=1,1,1 material=smoke density=0.5 translate=3,0,0\n",
+            MINIMAL
+        );
+        assert_eq!(2, parse(&text).unwrap().objects.len());
+        assert_invalid(
+            &format!(
+                "{}sphere center=0,0,0 radius=1 material=white density=0\n",
+                MINIMAL
+            ),
+            4,
+            Some("density"),
+        );
+        for open in [
+            "mesh file=teapot.obj",
+            "triangle a=0,0,0 b=1,0,0 c=0,1,0 material=white",
+            "xz_rect x=0,1 z=0,1 y=0 material=white",
+        ] {
+            assert_invalid(
+                &format!("{}{} density=1\n", MINIMAL, open),
+                4,
+                Some("density"),
+            );
+        }
+    }
+
+    #[test]
+    fn can_set_tone_mapping() {
+        let text = format!(
+            "{}render tone_map=extended_reinhard exposure=-1.5 white_point=8\n",
+            MINIMAL
+        );
+        let mut settings = RenderSettings::default();
+        parse(&text).unwrap().apply_settings(&mut settings);
+        assert_eq!(
+            ToneMapping {
+                operator: ToneMapOperator::ExtendedReinhard,
+                exposure: -1.5,
+                white_point: 8.0,
+            },
+            settings.tone_mapping
+        );
+        assert_invalid(
+            &format!("{}render tone_map=filmic\n", MINIMAL),
+            4,
+            Some("tone_map"),
+        );
+        for white_point in ["0", "-1", "inf", "NaN"] {
+            assert_invalid(
+                &format!("{}render white_point={}\n", MINIMAL, white_point),
+                4,
+                Some("white_point"),
+            );
+        }
+    }
+
+    #[test]
+    fn reports_bad_camera() {
+        for (key, value) in [
+            ("aperture", "-0.1"),
+            ("focus_distance", "0"),
+            ("focus_distance", "-1"),
+            ("shutter", "1,0"),
+        ] {
+            assert_invalid(
+                &format!("camera look_from=0,0,-5 look_at=0,0,0 {}={}\n", key, value),
+                1,
+                Some(key),
+            );
+        }
+    }
+
+    #[test]
+    fn reports_empty_rect() {
+        for (rect, key) in [
+            ("xy_rect x=1,1 y=0,1 z=0", "x"),
+            ("xz_rect x=0,1 z=1,0 y=0", "z"),
+            ("yz_rect y=2,2 z=0,1 x=0", "y"),
+        ] {
+            assert_invalid(
+                &format!("{}{} material=white\n", MINIMAL, rect),
+                4,
+                Some(key),
+            );
+        }
+    }
+
+    #[test]
+    fn reports_bad_flip() {
+        assert_invalid(
+            &format!(
+                "{}xy_rect x=0,1 y=0,1 z=0 material=white flip=yes\n",
+                MINIMAL
+            ),
+            4,
+            Some("flip"),
+        );
+    }
+
+    #[test]
+    fn render_settings_are_optional() {
+        let description = parse(MINIMAL).unwrap();
+        let mut settings = RenderSettings::default();
+        description.apply_settings(&mut settings);
+        assert_eq!(RenderSettings::default(), settings);
+    }
+
+    #[test]
+    fn textures_and_materials_can_be_shared() {
+        let text = "camera look_from=0,0,-5 look_at=0,0,0\n\
+                    texture red solid color=1,0,0\n\
+                    texture checks checker even=red odd=\"0, 0, 1\"\n\
+                    material checked lambertian albedo=checks # comment\n\
+                    sphere center=0,0,0 radius=1 material=checked\n\
+                    sphere center=0,3,0 radius=1 material=checked\n";
+        assert_eq!(2, parse(text).unwrap().objects.len());
+    }
+
+    #[test]
+    fn can_set_background() {
+        let description = parse(&format!("{}background solid color=0,0,0\n", MINIMAL)).unwrap();
+        assert!(matches!(description.background, Background::Solid(_)));
+        assert_invalid(&format!("{}background sky\n", MINIMAL), 4, None);
+        assert_invalid(
+            &format!("{}background environment file=missing.hdr\n", MINIMAL),
+            4,
+            Some("file"),
+        );
+    }
+
+    #[test]
+    fn reports_unknown_statement() {
+        assert_invalid(&format!("{}cube size=1\n", MINIMAL), 4, None);
+    }
+
+    #[test]
+    fn reports_unknown_key() {
+        assert_invalid(
+            "camera look_from=0,0,-5 look_at=0,0,0 zoom=2\n",
+            1,
+            Some("zoom"),
+        );
+    }
+
+    #[test]
+    fn reports_missing_key() {
+        assert_invalid(
+            &format!("{}sphere center=0,0,0 radius=1\n", MINIMAL),
+            4,
+            Some("material"),
+        );
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        assert_invalid(
+            &format!("{}sphere center=0,0 radius=1 material=white\n", MINIMAL),
+            4,
+            Some("center"),
+        );
+        assert_invalid(
+            &format!("{}render samples=many\n", MINIMAL),
+            4,
+            Some("samples"),
+        );
+        assert_invalid(
+            &format!("{}render aspect_ratio=3x2\n", MINIMAL),
+            4,
+            Some("aspect_ratio"),
+        );
+    }
+
+    #[test]
+    fn reports_unknown_material_and_texture() {
+        assert_invalid(
+            &format!("{}\nsphere center=0,0,0 radius=1 material=gold\n", MINIMAL),
+            5,
+            Some("material"),
+        );
+        assert_invalid(
+            "material ground lambertian albedo=grass\n",
+            1,
+            Some("albedo"),
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_names() {
+        assert_invalid(
+            &format!("{}material white metal albedo=1,1,1\n", MINIMAL),
+            4,
+            None,
+        );
+    }
+
+    #[test]
+    fn requires_camera_and_objects() {
+        assert!(matches!(
+            parse("material white lambertian albedo=1,1,1\nsphere center=0,0,0 radius=1 material=white\n"),
+            Err(SceneFileError::Invalid { line: None, .. })
+        ));
+        assert!(matches!(
+            parse("camera look_from=0,0,-5 look_at=0,0,0\n"),
+            Err(SceneFileError::Invalid { line: None, .. })
+        ));
+    }
+}
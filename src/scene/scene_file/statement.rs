@@ -0,0 +1,222 @@
+use std::path::Path;
+
+use crate::{geometry::vector_3d::Vector3D, util::tone_map::ToneMapOperator};
+
+use super::SceneFileError;
+
+/// A value of a `key=value` pair that can be parsed from the scene file.
+pub(super) trait Value: Sized {
+    /// Parses the value, or describes what was expected.
+    fn parse_value(text: &str) -> Result<Self, String>;
+}
+
+impl Value for f64 {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        text.parse()
+            .map_err(|_| format!("expected a number, found {:?}", text))
+    }
+}
+
+impl Value for i32 {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        text.parse()
+            .map_err(|_| format!("expected a whole number, found {:?}", text))
+    }
+}
+
+impl Value for bool {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        text.parse()
+            .map_err(|_| format!("expected `true` or `false`, found {:?}", text))
+    }
+}
+
+impl Value for ToneMapOperator {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        match text {
+            "clamp" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "extended_reinhard" => Ok(ToneMapOperator::ExtendedReinhard),
+            "aces" => Ok(ToneMapOperator::Aces),
+            "hable" => Ok(ToneMapOperator::Hable),
+            _ => Err(format!(
+                "expected clamp, reinhard, extended_reinhard, aces or hable, found {:?}",
+                text
+            )),
+        }
+    }
+}
+
+impl Value for String {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        Ok(String::from(text))
+    }
+}
+
+impl Value for (f64, f64) {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        match parse_list(text).as_deref() {
+            Some([a, b]) => Ok((*a, *b)),
+            _ => Err(format!("expected two numbers like `0,1`, found {:?}", text)),
+        }
+    }
+}
+
+impl Value for Vector3D {
+    fn parse_value(text: &str) -> Result<Self, String> {
+        match parse_list(text).as_deref() {
+            Some([x, y, z]) => Ok(Vector3D::new(*x, *y, *z)),
+            _ => Err(format!(
+                "expected three numbers like `0.5,1,0`, found {:?}",
+                text
+            )),
+        }
+    }
+}
+
+fn parse_list(text: &str) -> Option<Vec<f64>> {
+    text.split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+/// One line of a scene file: a keyword, followed by positional arguments and
+/// `key=value` pairs.
+pub(super) struct Statement<'a> {
+    path: &'a Path,
+    pub line: usize,
+    pub keyword: String,
+    pub arguments: Vec<String>,
+    /// The `key=value` pairs that haven't been used yet.
+    values: Vec<(String, String)>,
+}
+
+impl<'a> Statement<'a> {
+    /// Splits a line in to a statement. Returns `None` for blank lines and
+    /// comments.
+    ///
+    /// Tokens are separated by whitespace, and values may be put in double
+    /// quotes to include whitespace. Everything after a `#` is a comment.
+    pub fn parse(
+        path: &'a Path,
+        line: usize,
+        text: &str,
+    ) -> Result<Option<Statement<'a>>, SceneFileError> {
+        let mut tokens: Vec<String> = vec![];
+        let mut token: Option<String> = None;
+        let mut quoted = false;
+        for character in text.chars() {
+            match character {
+                '"' => {
+                    quoted = !quoted;
+                    token.get_or_insert_with(String::new);
+                }
+                '#' if !quoted => break,
+                character if character.is_whitespace() && !quoted => {
+                    tokens.extend(token.take());
+                }
+                character => token.get_or_insert_with(String::new).push(character),
+            }
+        }
+        if quoted {
+            return Err(SceneFileError::invalid(
+                path,
+                Some(line),
+                None,
+                String::from("unterminated quote"),
+            ));
+        }
+        tokens.extend(token);
+
+        let mut tokens = tokens.into_iter();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(None),
+        };
+        let mut statement = Statement {
+            path,
+            line,
+            keyword,
+            arguments: vec![],
+            values: vec![],
+        };
+        for token in tokens {
+            match token.split_once('=') {
+                Some((key, value)) => {
+                    if statement.values.iter().any(|(existing, _)| existing == key) {
+                        return Err(statement.error(Some(key), String::from("given twice")));
+                    }
+                    statement
+                        .values
+                        .push((String::from(key), String::from(value)));
+                }
+                None => statement.arguments.push(token),
+            }
+        }
+        Ok(Some(statement))
+    }
+
+    /// An error about this statement, or one of its keys.
+    pub fn error(&self, key: Option<&str>, message: String) -> SceneFileError {
+        SceneFileError::invalid(self.path, Some(self.line), key, message)
+    }
+
+    /// Checks that the statement has exactly the positional arguments in
+    /// `usage`, for example `["name", "type"]`.
+    pub fn expect_arguments(&self, usage: &[&str]) -> Result<(), SceneFileError> {
+        if self.arguments.len() != usage.len() {
+            let usage: Vec<String> = usage.iter().map(|name| format!("<{}>", name)).collect();
+            return Err(self.error(
+                None,
+                format!(
+                    "expected `{} {} key=value...`",
+                    self.keyword,
+                    usage.join(" ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Looks at the raw text of a value without taking it.
+    pub fn peek_raw(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Takes the raw text of a value, if it was given.
+    pub fn take_raw(&mut self, key: &str) -> Option<String> {
+        let index = self.values.iter().position(|(name, _)| name == key)?;
+        Some(self.values.remove(index).1)
+    }
+
+    /// Takes and parses a value, if it was given.
+    pub fn optional<T: Value>(&mut self, key: &str) -> Result<Option<T>, SceneFileError> {
+        match self.take_raw(key) {
+            Some(text) => T::parse_value(&text)
+                .map(Some)
+                .map_err(|message| self.error(Some(key), message)),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes and parses a value that must be given.
+    pub fn required<T: Value>(&mut self, key: &str) -> Result<T, SceneFileError> {
+        self.optional(key)?
+            .ok_or_else(|| self.error(Some(key), String::from("is required")))
+    }
+
+    /// Checks that every value of the statement was used, so that typos in
+    /// keys don't go unnoticed.
+    pub fn finish(self) -> Result<(), SceneFileError> {
+        match self.values.first() {
+            Some((key, _)) => Err(self.error(
+                Some(key),
+                format!("isn't a known key for `{}`", self.keyword),
+            )),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use crate::util::{
+    adaptive::AdaptiveSampling, filter::Filter, sampler::SamplerKind, tone_map::ToneMapping,
+};
+
+/// How a scene is rendered, as opposed to what is in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    /// Width in pixels of the image.
+    pub width: i32,
+    /// Width of the image divided by its height.
+    pub aspect_ratio: f64,
+    /// Samples to shoot per pixel, or the most a pixel gets with adaptive
+    /// sampling.
+    pub samples_per_pixel: i32,
+    /// How many times a ray may bounce before it is considered absorbed.
+    pub max_depth: i32,
+    /// How the linear image is turned in to one for 8-bit formats.
+    pub tone_mapping: ToneMapping,
+    /// Seed for every random choice, so the same seed gives the same image.
+    pub seed: u64,
+    /// How samples are spread over each pixel and over the lens.
+    pub sampler: SamplerKind,
+    /// How samples are weighed in to the pixels around them.
+    pub filter: Filter,
+    /// Stop sampling a pixel once the 95% confidence interval of its
+    /// brightness is within this fraction of it. Without it every pixel
+    /// gets all of its samples.
+    pub adaptive_threshold: Option<f64>,
+    /// Samples every pixel gets before adaptive sampling may stop it.
+    pub min_samples: i32,
+    /// The part of the image to render, or all of it. The rest stays black.
+    pub region: Option<Region>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 400,
+            aspect_ratio: 3.0 / 2.0,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            tone_mapping: ToneMapping::default(),
+            seed: 0,
+            sampler: SamplerKind::default(),
+            filter: Filter::default(),
+            adaptive_threshold: None,
+            min_samples: 16,
+            region: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Height in pixels of the image.
+    pub fn height(&self) -> i32 {
+        ((self.width as f64) / self.aspect_ratio) as i32
+    }
+
+    /// When to stop sampling a pixel.
+    pub fn adaptive_sampling(&self) -> AdaptiveSampling {
+        let max_samples = self.samples_per_pixel.max(0) as u64;
+        match self.adaptive_threshold {
+            Some(threshold) => AdaptiveSampling {
+                min_samples: self.min_samples.max(0) as u64,
+                max_samples,
+                threshold,
+            },
+            None => AdaptiveSampling::fixed(max_samples),
+        }
+    }
+
+    /// The part of the image to render.
+    pub fn render_region(&self) -> Region {
+        self.region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: self.width.max(0) as usize,
+            height: self.height().max(0) as usize,
+        })
+    }
+}
+
+/// A rectangle of pixels, from the top left of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// The leftmost column.
+    pub x: usize,
+    /// The top row.
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// Whether the region is inside an image of the given size.
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        self.x + self.width <= width && self.y + self.height <= height
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    /// Reads a region written as `x,y,width,height`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let values = text
+            .split(',')
+            .map(|value| value.trim().parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| format!("Expected x,y,width,height as whole numbers, got {}", text))?;
+        match values[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Region {
+                x,
+                y,
+                width,
+                height,
+            }),
+            [_, _, _, _] => Err(format!("The region {} is empty", text)),
+            _ => Err(format!("Expected x,y,width,height, got {}", text)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_regions() {
+        assert_eq!(
+            Ok(Region {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            }),
+            "10,20, 30,40".parse()
+        );
+        assert!("10,20,30".parse::<Region>().is_err());
+        assert!("10,20,0,40".parse::<Region>().is_err());
+        assert!("10,-20,30,40".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn regions_fit_in_image() {
+        let region: Region = "10,20,30,40".parse().unwrap();
+        assert!(region.fits(40, 60));
+        assert!(!region.fits(39, 60));
+        assert!(!region.fits(40, 59));
+    }
+}
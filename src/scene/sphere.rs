@@ -1,4 +1,14 @@
-use crate::{geometry::ray::Ray, util::point::Point3D};
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    geometry::{
+        ray::Ray,
+        vector_3d::{OrthonormalBasis, Vector3D},
+    },
+    util::point::Point3D,
+};
 
 use super::{
     bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
@@ -16,14 +26,123 @@ impl Sphere {
             sphere: MovingSphere::new(center, center, radius, material, 0.0, 1.0),
         }
     }
+
+    /// The cosine of the angle between the direction to the center and the
+    /// edge of the sphere, as seen from `origin`. `None` from inside the
+    /// sphere, which is seen in every direction.
+    fn cos_theta_max(&self, origin: &Point3D) -> Option<f64> {
+        let radius = self.sphere.radius();
+        let distance_squared = (self.sphere.center(0.0) - *origin).length_squared();
+        if distance_squared <= radius * radius {
+            return None;
+        }
+        Some((1.0 - radius * radius / distance_squared).sqrt())
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.sphere.hit(ray, t_min, t_max)
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        self.sphere.hit(ray, t_min, t_max, generator)
     }
 
     fn bounding_box(&self, time_0: f64, time_1: f64) -> Option<AxisAlignedBoundingBox> {
         self.sphere.bounding_box(time_0, time_1)
     }
+
+    /// Directions are picked evenly over the cone the sphere fills.
+    fn pdf_value(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return 1.0 / (4.0 * PI),
+        };
+        // Only directions inside the cone reach the sphere.
+        let to_center = self.sphere.center(0.0) - *origin;
+        if direction.dot(&to_center) < cos_theta_max * direction.length() * to_center.length() {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random_direction(&self, origin: &Point3D, generator: &mut dyn RngCore) -> Vector3D {
+        let cos_theta_max = match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return Vector3D::random_unit_vector(generator),
+        };
+        let z = 1.0 + generator.gen::<f64>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * generator.gen::<f64>();
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        OrthonormalBasis::new(&(self.sphere.center(0.0) - *origin)).local(&Vector3D::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            z,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{scene::materials::Lambertian, util::color::Color};
+
+    fn sphere() -> Sphere {
+        Sphere::new(
+            Point3D::new(0.0, 0.0, -4.0),
+            2.0,
+            Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn aims_at_sphere() {
+        let sphere = sphere();
+        let origin = Point3D::empty();
+        let mut generator = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let direction = sphere.random_direction(&origin, &mut generator);
+            assert!(sphere
+                .hit(
+                    &Ray::new(origin, direction, None),
+                    0.001,
+                    f64::INFINITY,
+                    &mut generator
+                )
+                .is_some());
+        }
+        // The sphere fills a cone with a half angle of 30 degrees.
+        let solid_angle = 2.0 * PI * (1.0 - (3.0_f64).sqrt() / 2.0);
+        assert_relative_eq!(
+            1.0 / solid_angle,
+            sphere.pdf_value(&origin, &Vector3D::new(0.0, 0.1, -1.0)),
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(
+            0.0,
+            sphere.pdf_value(&origin, &Vector3D::new(0.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn sphere_pdf_adds_up_to_one() {
+        let sphere = sphere();
+        let mut generator = StdRng::seed_from_u64(2);
+        let samples = 100_000;
+        for origin in [Point3D::empty(), Point3D::new(0.5, 0.0, -4.0)] {
+            let total: f64 = (0..samples)
+                .map(|_| {
+                    let direction = Vector3D::random_unit_vector(&mut generator);
+                    sphere.pdf_value(&origin, &direction) * 4.0 * PI
+                })
+                .sum();
+            assert_relative_eq!(1.0, total / samples as f64, epsilon = 0.02);
+        }
+    }
 }
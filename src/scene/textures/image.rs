@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use crate::util::{color::Color, image::Image, point::Point3D};
+
+use super::Texture;
+
+/// A texture that looks up the color in an image using the `(u, v)`
+/// coordinates of the hit, with `(0, 0)` at the bottom left of the image.
+pub struct ImageTexture {
+    image: Arc<Image>,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> ImageTexture {
+        ImageTexture { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color(&self, u: f64, v: f64, _point: &Point3D) -> Color {
+        let width = self.image.width();
+        let height = self.image.height();
+        if width == 0 || height == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Clamp the coordinates, and flip v since images are stored top down.
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        self.image.pixel(x, y)
+    }
+}
@@ -1,7 +1,9 @@
 mod checker;
+mod image;
 mod solid;
 mod texture;
 
 pub use checker::CheckerTexture;
+pub use image::ImageTexture;
 pub use solid::SolidColorTexture;
 pub use texture::Texture;
@@ -1,5 +1,14 @@
+use std::sync::Arc;
+
 use crate::util::{color::Color, point::Point3D};
 
 pub trait Texture: Send + Sync {
     fn color(&self, u: f64, v: f64, point: &Point3D) -> Color;
 }
+
+/// Lets one texture be shared by many materials.
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn color(&self, u: f64, v: f64, point: &Point3D) -> Color {
+        (**self).color(u, v, point)
+    }
+}
@@ -0,0 +1,299 @@
+use rand::RngCore;
+
+use crate::{
+    geometry::{
+        ray::Ray,
+        vector_3d::{self, Vector3D},
+    },
+    util::point::Point3D,
+};
+
+use super::{
+    bounding_box::AxisAlignedBoundingBox, hit_record::HitRecord, hittable::Hittable,
+    materials::Material,
+};
+
+/// Thickness given to the bounding box of a triangle lying in an axis plane.
+pub(crate) const TRIANGLE_BOX_PADDING: f64 = 0.0001;
+
+/// Where a ray crossed a triangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TriangleIntersection {
+    /// Distance along the ray.
+    pub t: f64,
+    /// Barycentric weight of the second vertex.
+    pub u: f64,
+    /// Barycentric weight of the third vertex.
+    pub v: f64,
+}
+
+/// Intersects a ray with the triangle `(a, b, c)` using the Möller–Trumbore
+/// algorithm.
+///
+/// Rather than intersecting the plane of the triangle and then checking if
+/// the point lies inside of it, this solves directly for the distance along
+/// the ray and the barycentric coordinates `(u, v)` of the hit, where the
+/// point on the triangle is `(1 - u - v) * a + u * b + v * c`. The hit is
+/// inside the triangle when `u`, `v` and `u + v` are all between 0 and 1.
+///
+/// # Arguments
+///
+/// * `ray` - The ray that we want to intersect with the triangle.
+/// * `a`, `b`, `c` - The corners of the triangle, counter-clockwise when
+///   looking at the front face.
+/// * `t_min` - The closest distance along the ray that counts as a hit.
+/// * `t_max` - The furthest distance along the ray that counts as a hit.
+pub(crate) fn intersect(
+    ray: &Ray,
+    a: &Point3D,
+    b: &Point3D,
+    c: &Point3D,
+    t_min: f64,
+    t_max: f64,
+) -> Option<TriangleIntersection> {
+    const EPSILON: f64 = 1e-12;
+
+    let edge_1 = *b - *a;
+    let edge_2 = *c - *a;
+    let p = ray.direction.cross(&edge_2);
+    let determinant = edge_1.dot(&p);
+
+    // The ray is parallel to the plane of the triangle.
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = ray.origin - *a;
+    let u = s.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(&edge_1);
+    let v = ray.direction.dot(&q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(&q) * inverse_determinant;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some(TriangleIntersection { t, u, v })
+}
+
+/// Bounding box around the three corners of a triangle.
+pub(crate) fn bounding_box(a: &Point3D, b: &Point3D, c: &Point3D) -> AxisAlignedBoundingBox {
+    let minimum = Point3D::new(
+        a.x().min(b.x()).min(c.x()),
+        a.y().min(b.y()).min(c.y()),
+        a.z().min(b.z()).min(c.z()),
+    );
+    let maximum = Point3D::new(
+        a.x().max(b.x()).max(c.x()),
+        a.y().max(b.y()).max(c.y()),
+        a.z().max(b.z()).max(c.z()),
+    );
+    AxisAlignedBoundingBox::new(minimum, maximum).pad(TRIANGLE_BOX_PADDING)
+}
+
+/// Interpolates per-vertex normals with barycentric weights, and flips the
+/// result so it lies on the same side as the geometric normal.
+pub(crate) fn shading_normal(
+    geometric_normal: &Vector3D,
+    normals: [&Vector3D; 3],
+    u: f64,
+    v: f64,
+) -> Vector3D {
+    let interpolated = (1.0 - u - v) * normals[0] + u * normals[1] + v * normals[2];
+    let normal = vector_3d::unit_vector(&interpolated);
+    if normal.dot(geometric_normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// A single, stand-alone triangle.
+///
+/// The `u` and `v` of the hit record are the barycentric coordinates of the
+/// hit. For many triangles sharing a material and vertices, use a
+/// `TriangleMesh` instead.
+pub struct Triangle {
+    vertices: [Point3D; 3],
+    normals: Option<[Vector3D; 3]>,
+    material: Box<dyn Material>,
+}
+
+impl Triangle {
+    /// Returns a new flat-shaded triangle.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`, `b`, `c` - The corners of the triangle. The front face is the
+    ///   one from which the corners appear counter-clockwise.
+    /// * `material` - What the triangle is made of.
+    pub fn new(a: Point3D, b: Point3D, c: Point3D, material: Box<dyn Material>) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            material,
+        }
+    }
+
+    /// Returns a new triangle with a normal per corner, which are
+    /// interpolated across the face for smooth shading.
+    pub fn with_normals(
+        vertices: [Point3D; 3],
+        normals: [Vector3D; 3],
+        material: Box<dyn Material>,
+    ) -> Triangle {
+        Triangle {
+            vertices,
+            normals: Some(normals),
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        let [a, b, c] = &self.vertices;
+        let intersection = intersect(ray, a, b, c, t_min, t_max)?;
+
+        let geometric_normal = vector_3d::unit_vector(&(*b - *a).cross(&(*c - *a)));
+        let normal = match &self.normals {
+            Some([n_a, n_b, n_c]) => shading_normal(
+                &geometric_normal,
+                [n_a, n_b, n_c],
+                intersection.u,
+                intersection.v,
+            ),
+            None => geometric_normal,
+        };
+
+        Some(HitRecord::new(
+            ray.at(intersection.t),
+            normal,
+            &*self.material,
+            intersection.t,
+            intersection.u,
+            intersection.v,
+            ray,
+        ))
+    }
+
+    fn bounding_box(&self, _time_0: f64, _time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        let [a, b, c] = &self.vertices;
+        Some(bounding_box(a, b, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::scene::materials::Lambertian;
+
+    use super::*;
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn can_hit_triangle_from_the_front() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(
+            Point3D::new(0.25, 0.25, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        let record = triangle
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(1.0, record.t);
+        assert_relative_eq!(Point3D::new(0.25, 0.25, 0.0), record.p);
+        assert_relative_eq!(Vector3D::new(0.0, 0.0, 1.0), record.normal);
+        assert!(record.front_face);
+    }
+
+    #[test]
+    fn hit_reports_barycentric_coordinates() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(
+            Point3D::new(0.2, 0.7, -1.0),
+            Vector3D::new(0.0, 0.0, 1.0),
+            None,
+        );
+        let record = triangle
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(0.2, record.u, epsilon = 1e-9);
+        assert_relative_eq!(0.7, record.v, epsilon = 1e-9);
+        assert!(!record.front_face);
+        assert_relative_eq!(Vector3D::new(0.0, 0.0, -1.0), record.normal);
+    }
+
+    #[test]
+    fn cant_hit_outside_of_triangle() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(
+            Point3D::new(0.75, 0.75, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        assert!(triangle
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn cant_hit_triangle_with_parallel_ray() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(
+            Point3D::new(-1.0, 0.25, 0.0),
+            Vector3D::new(1.0, 0.0, 0.0),
+            None,
+        );
+        assert!(triangle
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn cant_hit_triangle_outside_time_window() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(
+            Point3D::new(0.25, 0.25, 5.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        assert!(triangle
+            .hit(&ray, 0.0, 4.0, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn flat_triangle_has_padded_bounding_box() {
+        let triangle = unit_triangle();
+        let bounding_box = triangle.bounding_box(0.0, 1.0).unwrap();
+        assert!(bounding_box.maximum.z() > bounding_box.minimum.z());
+        assert_relative_eq!(0.0, bounding_box.minimum.x());
+        assert_relative_eq!(1.0, bounding_box.maximum.x());
+    }
+}
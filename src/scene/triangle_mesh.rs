@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{
+    geometry::{
+        ray::Ray,
+        vector_3d::{self, Vector3D},
+    },
+    util::point::Point3D,
+};
+
+use super::{
+    bounding_box::AxisAlignedBoundingBox,
+    hit_record::HitRecord,
+    hittable::Hittable,
+    materials::Material,
+    triangle::{self, TriangleIntersection},
+};
+
+/// One triangle of a mesh, as indices in to the shared arrays of the mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshFace {
+    /// Indices of the corner positions.
+    pub positions: [usize; 3],
+    /// Indices of the corner normals, if the face is smooth shaded.
+    pub normals: Option<[usize; 3]>,
+    /// Indices of the corner texture coordinates, if the face is textured.
+    /// The `u` and `v` of a hit on a textured face are then the texture
+    /// coordinates interpolated over the face, rather than the barycentric
+    /// coordinates of a hit on an untextured face, as textures look up `u`
+    /// and `v`.
+    pub texture_coordinates: Option<[usize; 3]>,
+}
+
+impl MeshFace {
+    /// A face with only positions, which is flat shaded and uses the
+    /// barycentric coordinates as texture coordinates.
+    pub fn new(positions: [usize; 3]) -> MeshFace {
+        MeshFace {
+            positions,
+            normals: None,
+            texture_coordinates: None,
+        }
+    }
+}
+
+/// A collection of triangles that share vertex data and a single material.
+///
+/// Positions, normals and texture coordinates are stored once and referenced
+/// by index from each face, so a vertex shared by many faces is only stored
+/// once, and the whole mesh only has one material.
+pub struct TriangleMesh {
+    positions: Vec<Point3D>,
+    normals: Vec<Vector3D>,
+    texture_coordinates: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    material: Box<dyn Material>,
+}
+
+impl TriangleMesh {
+    /// Returns a new triangle mesh.
+    ///
+    /// Panics if any face refers to a vertex, normal or texture coordinate
+    /// that does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `positions` - The corners of the triangles.
+    /// * `normals` - Normals for smooth shading, may be empty.
+    /// * `texture_coordinates` - `(u, v)` coordinates, may be empty.
+    /// * `faces` - The triangles, as indices in to the arrays above.
+    /// * `material` - What the whole mesh is made of.
+    pub fn new(
+        positions: Vec<Point3D>,
+        normals: Vec<Vector3D>,
+        texture_coordinates: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: Box<dyn Material>,
+    ) -> TriangleMesh {
+        for face in &faces {
+            let in_bounds = |indices: Option<[usize; 3]>, len: usize| match indices {
+                Some(indices) => indices.iter().all(|&i| i < len),
+                None => true,
+            };
+            if !in_bounds(Some(face.positions), positions.len())
+                || !in_bounds(face.normals, normals.len())
+                || !in_bounds(face.texture_coordinates, texture_coordinates.len())
+            {
+                panic!(
+                    "Mesh face {:?} refers to a vertex that doesn't exist.",
+                    face
+                );
+            }
+        }
+
+        TriangleMesh {
+            positions,
+            normals,
+            texture_coordinates,
+            faces,
+            material,
+        }
+    }
+
+    /// Number of triangles in the mesh.
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// Returns true if the mesh has no triangles.
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// Splits the mesh in to one hittable per face, so they can be sorted in
+    /// to a `BvhNode`. The faces all keep a reference to the shared mesh data.
+    pub fn triangles(self) -> Vec<Box<dyn Hittable>> {
+        let mesh = Arc::new(self);
+        (0..mesh.faces.len())
+            .map(|face| {
+                Box::new(MeshTriangle {
+                    mesh: Arc::clone(&mesh),
+                    face,
+                }) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    fn corners(&self, face: &MeshFace) -> [&Point3D; 3] {
+        let [a, b, c] = face.positions;
+        [&self.positions[a], &self.positions[b], &self.positions[c]]
+    }
+
+    fn texture_coordinates(&self, face: &MeshFace, hit: &TriangleIntersection) -> (f64, f64) {
+        match face.texture_coordinates {
+            Some([a, b, c]) => {
+                let w = 1.0 - hit.u - hit.v;
+                let (a, b, c) = (
+                    self.texture_coordinates[a],
+                    self.texture_coordinates[b],
+                    self.texture_coordinates[c],
+                );
+                (
+                    w * a.0 + hit.u * b.0 + hit.v * c.0,
+                    w * a.1 + hit.u * b.1 + hit.v * c.1,
+                )
+            }
+            None => (hit.u, hit.v),
+        }
+    }
+}
+
+/// A single face of a `TriangleMesh`.
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _generator: &mut dyn RngCore,
+    ) -> Option<HitRecord<'_>> {
+        let face = &self.mesh.faces[self.face];
+        let [a, b, c] = self.mesh.corners(face);
+        let intersection = triangle::intersect(ray, a, b, c, t_min, t_max)?;
+
+        let geometric_normal = vector_3d::unit_vector(&(*b - *a).cross(&(*c - *a)));
+        let normal = match face.normals {
+            Some([n_a, n_b, n_c]) => triangle::shading_normal(
+                &geometric_normal,
+                [
+                    &self.mesh.normals[n_a],
+                    &self.mesh.normals[n_b],
+                    &self.mesh.normals[n_c],
+                ],
+                intersection.u,
+                intersection.v,
+            ),
+            None => geometric_normal,
+        };
+        let (u, v) = self.mesh.texture_coordinates(face, &intersection);
+
+        Some(HitRecord::new(
+            ray.at(intersection.t),
+            normal,
+            &*self.mesh.material,
+            intersection.t,
+            u,
+            v,
+            ray,
+        ))
+    }
+
+    fn bounding_box(&self, _time_0: f64, _time_1: f64) -> Option<AxisAlignedBoundingBox> {
+        let [a, b, c] = self.mesh.corners(&self.mesh.faces[self.face]);
+        Some(triangle::bounding_box(a, b, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{geometry::bounded_volume_hierarchy::BvhNode, scene::materials::Lambertian};
+
+    use super::*;
+
+    /// A unit square in the xy plane made of two triangles.
+    fn square(texture_coordinates: Vec<(f64, f64)>, textured: bool) -> TriangleMesh {
+        let positions = vec![
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(1.0, 0.0, 0.0),
+            Point3D::new(1.0, 1.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+        ];
+        let faces = vec![[0, 1, 2], [0, 2, 3]]
+            .into_iter()
+            .map(|positions| MeshFace {
+                positions,
+                normals: None,
+                texture_coordinates: if textured { Some(positions) } else { None },
+            })
+            .collect();
+        TriangleMesh::new(
+            positions,
+            vec![],
+            texture_coordinates,
+            faces,
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn mesh_splits_in_to_one_hittable_per_face() {
+        let mesh = square(vec![], false);
+        assert_eq!(2, mesh.len());
+        assert_eq!(2, mesh.triangles().len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_construct_mesh_with_missing_vertex() {
+        TriangleMesh::new(
+            vec![Point3D::empty()],
+            vec![],
+            vec![],
+            vec![MeshFace::new([0, 1, 2])],
+            Box::new(Lambertian::new(Vector3D::new(0.5, 0.5, 0.5))),
+        );
+    }
+
+    #[test]
+    fn can_hit_mesh_through_bvh() {
+        let bvh = BvhNode::new(square(vec![], false).triangles(), 0.0, 1.0);
+        for (x, y) in [(0.75, 0.25), (0.25, 0.75)] {
+            let ray = Ray::new(Point3D::new(x, y, 1.0), Vector3D::new(0.0, 0.0, -1.0), None);
+            let record = bvh
+                .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+                .unwrap();
+            assert_relative_eq!(Point3D::new(x, y, 0.0), record.p, epsilon = 1e-9);
+        }
+
+        let ray = Ray::new(
+            Point3D::new(1.5, 0.5, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        assert!(bvh
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .is_none());
+    }
+
+    #[test]
+    fn untextured_mesh_gives_barycentric_coordinates() {
+        let triangles = square(vec![], false).triangles();
+        let ray = Ray::new(
+            Point3D::new(0.25, 0.75, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        // The hit is a quarter of the way to the second corner and half way
+        // to the third.
+        let record = triangles[1]
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(0.25, record.u, epsilon = 1e-9);
+        assert_relative_eq!(0.5, record.v, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn mesh_interpolates_texture_coordinates() {
+        let texture_coordinates = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let triangles = square(texture_coordinates, true).triangles();
+        let ray = Ray::new(
+            Point3D::new(0.25, 0.75, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        let record = triangles[1]
+            .hit(&ray, 0.0, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_relative_eq!(0.25, record.u, epsilon = 1e-9);
+        assert_relative_eq!(0.75, record.v, epsilon = 1e-9);
+    }
+}
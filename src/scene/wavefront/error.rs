@@ -0,0 +1,53 @@
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong when loading a Wavefront OBJ or MTL file.
+#[derive(Debug)]
+pub enum WavefrontError {
+    /// The file couldn't be opened or read.
+    Io { path: PathBuf, error: io::Error },
+    /// A line of the file couldn't be understood.
+    Parse {
+        path: PathBuf,
+        /// Line number, starting at 1.
+        line: usize,
+        message: String,
+    },
+}
+
+impl WavefrontError {
+    pub(super) fn parse(path: &Path, line: usize, message: String) -> WavefrontError {
+        WavefrontError::Parse {
+            path: path.to_path_buf(),
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for WavefrontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavefrontError::Io { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+            WavefrontError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for WavefrontError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WavefrontError::Io { error, .. } => Some(error),
+            WavefrontError::Parse { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Loading of Wavefront OBJ geometry and the MTL materials that go with it.
+
+mod error;
+mod mtl;
+mod obj;
+
+pub use error::WavefrontError;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    scene::{
+        hittable::Hittable,
+        materials::{Lambertian, Material},
+        triangle_mesh::{MeshFace, TriangleMesh},
+    },
+    util::{
+        color::Color,
+        image::{self, Image},
+    },
+};
+
+use self::{
+    mtl::{parse_mtl, MtlMaterial},
+    obj::{parse_obj, ObjData, ObjGroup},
+};
+
+/// Loads an OBJ file, and any MTL files it refers to, in to triangles that
+/// can be put in to a `BvhNode`.
+///
+/// Every group of faces that shares a name and a material becomes one
+/// `TriangleMesh`. Material libraries are looked up relative to the OBJ file,
+/// and textures relative to the library that uses them. Faces without a
+/// material are a light grey `Lambertian`.
+///
+/// # Arguments
+///
+/// * `path` - The OBJ file to load.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Vec<Box<dyn Hittable>>, WavefrontError> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let data = parse_obj(open(path)?, path)?;
+
+    // Materials by name, with the library that defined them.
+    let mut materials: HashMap<String, (PathBuf, MtlMaterial)> = HashMap::new();
+    for (library, line) in &data.material_libraries {
+        let library_path = directory.join(library);
+        let reader = open(&library_path).map_err(|error| {
+            WavefrontError::parse(
+                path,
+                *line,
+                format!("can't load material library: {}", error),
+            )
+        })?;
+        for material in parse_mtl(reader, &library_path)? {
+            materials.insert(material.name.clone(), (library_path.clone(), material));
+        }
+    }
+
+    let mut textures: HashMap<PathBuf, Arc<Image>> = HashMap::new();
+    let mut hittables: Vec<Box<dyn Hittable>> = vec![];
+    for group in &data.groups {
+        let material: Box<dyn Material> = match &group.material {
+            None => Box::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+            Some((name, line)) => {
+                let (library, description) = materials.get(name).ok_or_else(|| {
+                    WavefrontError::parse(path, *line, format!("unknown material {:?}", name))
+                })?;
+                let diffuse_map = match &description.diffuse_map {
+                    Some((file, line)) => Some(load_texture(&mut textures, file, library, *line)?),
+                    None => None,
+                };
+                description.to_material(diffuse_map)
+            }
+        };
+        hittables.extend(build_mesh(&data, group, material).triangles());
+    }
+
+    Ok(hittables)
+}
+
+/// Opens a file for reading, wrapping errors with the path.
+fn open(path: &Path) -> Result<BufReader<File>, WavefrontError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|error| WavefrontError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+/// Loads a texture named by a material library, reusing it if another
+/// material already loaded it.
+fn load_texture(
+    textures: &mut HashMap<PathBuf, Arc<Image>>,
+    file: &str,
+    library: &Path,
+    line: usize,
+) -> Result<Arc<Image>, WavefrontError> {
+    let directory = library.parent().unwrap_or_else(|| Path::new(""));
+    let texture_path = directory.join(file);
+    if let Some(image) = textures.get(&texture_path) {
+        return Ok(Arc::clone(image));
+    }
+
+    let image = open(&texture_path)
+        .and_then(|mut reader| {
+            image::read_ppm(&mut reader).map_err(|error| WavefrontError::Io {
+                path: texture_path.clone(),
+                error,
+            })
+        })
+        .map_err(|error| {
+            WavefrontError::parse(
+                library,
+                line,
+                format!("can't load texture (only PPM is supported): {}", error),
+            )
+        })?;
+    let image = Arc::new(image);
+    textures.insert(texture_path, Arc::clone(&image));
+    Ok(image)
+}
+
+/// Builds a mesh for one group, only keeping the vertex data it uses.
+fn build_mesh(data: &ObjData, group: &ObjGroup, material: Box<dyn Material>) -> TriangleMesh {
+    fn remap<T: Copy>(
+        indices: [usize; 3],
+        source: &[T],
+        target: &mut Vec<T>,
+        seen: &mut HashMap<usize, usize>,
+    ) -> [usize; 3] {
+        indices.map(|index| {
+            *seen.entry(index).or_insert_with(|| {
+                target.push(source[index]);
+                target.len() - 1
+            })
+        })
+    }
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut texture_coordinates = vec![];
+    let mut seen_positions = HashMap::new();
+    let mut seen_normals = HashMap::new();
+    let mut seen_texture_coordinates = HashMap::new();
+
+    let faces = group
+        .faces
+        .iter()
+        .map(|face| MeshFace {
+            positions: remap(
+                face.positions,
+                &data.positions,
+                &mut positions,
+                &mut seen_positions,
+            ),
+            normals: face
+                .normals
+                .map(|indices| remap(indices, &data.normals, &mut normals, &mut seen_normals)),
+            texture_coordinates: face.texture_coordinates.map(|indices| {
+                remap(
+                    indices,
+                    &data.texture_coordinates,
+                    &mut texture_coordinates,
+                    &mut seen_texture_coordinates,
+                )
+            }),
+        })
+        .collect();
+
+    TriangleMesh::new(positions, normals, texture_coordinates, faces, material)
+}
+
+/// Splits an OBJ or MTL file in to statements, yielding the line number, the
+/// keyword and the arguments of each non-empty line. Comments are removed.
+fn statements<'a>(
+    reader: impl BufRead + 'a,
+    path: &'a Path,
+) -> impl Iterator<Item = Result<(usize, String, Vec<String>), WavefrontError>> + 'a {
+    reader.lines().enumerate().filter_map(move |(index, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                return Some(Err(WavefrontError::Io {
+                    path: path.to_path_buf(),
+                    error,
+                }))
+            }
+        };
+        let content = line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace().map(String::from);
+        let keyword = tokens.next()?;
+        Some(Ok((index + 1, keyword, tokens.collect())))
+    })
+}
+
+/// Parses exactly `N` numbers from the arguments of a statement.
+fn parse_floats<const N: usize>(
+    arguments: &[String],
+    path: &Path,
+    line: usize,
+) -> Result<[f64; N], WavefrontError> {
+    if arguments.len() != N {
+        return Err(WavefrontError::parse(
+            path,
+            line,
+            format!("expected {} numbers, found {}", N, arguments.len()),
+        ));
+    }
+    let mut numbers = [0.0; N];
+    for (number, argument) in numbers.iter_mut().zip(arguments) {
+        *number = argument.parse().map_err(|_| {
+            WavefrontError::parse(path, line, format!("invalid number {:?}", argument))
+        })?;
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        geometry::{bounded_volume_hierarchy::BvhNode, ray::Ray, vector_3d::Vector3D},
+        util::point::Point3D,
+    };
+
+    use super::*;
+
+    /// Writes files to a fresh directory for a test, returning its path.
+    fn write_files(test: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "raytracer-wavefront-{}-{}",
+            test,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        for (name, contents) in files {
+            fs::write(directory.join(name), contents).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn can_load_obj_with_materials_and_texture() {
+        let obj = b"mtllib quad.mtl\nv -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                    vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                    g front\nusemtl checker\nf 1/1 2/2 3/3 4/4\n\
+                    v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+                    g back\nusemtl mirror\nf -1 -2 -3 -4\n";
+        let mtl = b"newmtl checker\nmap_Kd checker.ppm\nnewmtl mirror\nKs 1 1 1\nillum 3\n";
+        let directory = write_files(
+            "materials",
+            &[
+                ("quad.obj", obj),
+                ("quad.mtl", mtl),
+                (
+                    "checker.ppm",
+                    b"P3 2 2 255 0 0 0 255 255 255 255 255 255 0 0 0",
+                ),
+            ],
+        );
+
+        let hittables = load_obj(directory.join("quad.obj")).unwrap();
+        assert_eq!(4, hittables.len());
+
+        let bvh = BvhNode::new(hittables, 0.0, 1.0);
+        let ray = Ray::new(
+            Point3D::new(0.5, 0.5, 1.0),
+            Vector3D::new(0.0, 0.0, -1.0),
+            None,
+        );
+        let record = bvh
+            .hit(&ray, 0.001, f64::INFINITY, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert!((record.u - 0.75).abs() < 1e-9 && (record.v - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reports_unknown_material() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n";
+        let directory = write_files("unknown-material", &[("tri.obj", obj)]);
+        match load_obj(directory.join("tri.obj")) {
+            Err(WavefrontError::Parse { line, message, .. }) => {
+                assert_eq!(4, line);
+                assert!(message.contains("missing"));
+            }
+            Err(error) => panic!("Expected a parse error, got {}", error),
+            Ok(_) => panic!("Expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn reports_missing_material_library() {
+        let obj = b"v 0 0 0\nmtllib nowhere.mtl\n";
+        let directory = write_files("missing-library", &[("tri.obj", obj)]);
+        match load_obj(directory.join("tri.obj")) {
+            Err(WavefrontError::Parse { line, .. }) => assert_eq!(2, line),
+            Err(error) => panic!("Expected a parse error, got {}", error),
+            Ok(_) => panic!("Expected a parse error"),
+        }
+    }
+}
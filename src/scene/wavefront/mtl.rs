@@ -0,0 +1,230 @@
+use std::{io::BufRead, path::Path, sync::Arc};
+
+use crate::{
+    scene::{
+        materials::{Dielectric, Lambertian, Material, Metal},
+        textures::ImageTexture,
+    },
+    util::{color::Color, image::Image},
+};
+
+use super::{parse_floats, statements, WavefrontError};
+
+/// A material as described by an MTL file.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MtlMaterial {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: Color,
+    /// `Ks`
+    pub specular: Color,
+    /// `Ns`, the Phong exponent.
+    pub specular_exponent: Option<f64>,
+    /// `Ni`
+    pub index_of_refraction: f64,
+    /// `d`, or one minus `Tr`.
+    pub dissolve: f64,
+    /// `illum`, the illumination model.
+    pub illumination: Option<u32>,
+    /// `map_Kd`, and the line where it appeared.
+    pub diffuse_map: Option<(String, usize)>,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> MtlMaterial {
+        MtlMaterial {
+            name,
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::empty(),
+            specular_exponent: None,
+            index_of_refraction: 1.0,
+            dissolve: 1.0,
+            illumination: None,
+            diffuse_map: None,
+        }
+    }
+
+    /// Picks the closest of our materials to what the MTL describes.
+    ///
+    /// * Transparent materials (`d` below 1, or an illumination model with
+    ///   refraction) become `Dielectric`, using `Ni` as the index of
+    ///   refraction, or glass if it isn't set.
+    /// * Materials with a reflective illumination model, or that are only
+    ///   specular, become `Metal` tinted by `Ks`, with a fuzz derived from how
+    ///   wide the `Ns` highlight is, or a sharp mirror without `Ns`.
+    /// * Everything else becomes `Lambertian`, using `map_Kd` as the texture
+    ///   if there is one, and `Kd` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `diffuse_map` - The loaded `map_Kd` image, if the material has one.
+    pub fn to_material(&self, diffuse_map: Option<Arc<Image>>) -> Box<dyn Material> {
+        let refractive = matches!(self.illumination, Some(4) | Some(6) | Some(7) | Some(9));
+        let reflective = matches!(self.illumination, Some(3) | Some(5) | Some(8));
+        let only_specular =
+            self.illumination.is_none() && self.diffuse.near_zero() && !self.specular.near_zero();
+
+        if self.dissolve < 1.0 || refractive {
+            let index_of_refraction = if self.index_of_refraction > 1.0 {
+                self.index_of_refraction
+            } else {
+                1.5
+            };
+            Box::new(Dielectric::new(index_of_refraction))
+        } else if reflective || only_specular {
+            Box::new(Metal::new(self.specular, self.fuzz()))
+        } else {
+            match diffuse_map {
+                Some(image) => {
+                    Box::new(Lambertian::from_texture(Box::new(ImageTexture::new(image))))
+                }
+                None => Box::new(Lambertian::new(self.diffuse)),
+            }
+        }
+    }
+
+    /// The fuzz of the `Metal` the material becomes: the Phong exponent
+    /// turned in to a roughness, or 0 for a sharp reflection without one.
+    fn fuzz(&self) -> f64 {
+        match self.specular_exponent {
+            Some(exponent) => (2.0 / (exponent.max(0.0) + 2.0)).sqrt(),
+            None => 0.0,
+        }
+    }
+}
+
+/// Parses the materials in an MTL file.
+///
+/// # Arguments
+///
+/// * `reader` - Where to read the MTL file from.
+/// * `path` - Where the file came from, used in error messages.
+pub(super) fn parse_mtl(
+    reader: impl BufRead,
+    path: &Path,
+) -> Result<Vec<MtlMaterial>, WavefrontError> {
+    let mut materials: Vec<MtlMaterial> = vec![];
+
+    for statement in statements(reader, path) {
+        let (line, keyword, arguments) = statement?;
+        if keyword == "newmtl" {
+            if arguments.len() != 1 {
+                return Err(WavefrontError::parse(
+                    path,
+                    line,
+                    String::from("newmtl needs exactly one material name"),
+                ));
+            }
+            materials.push(MtlMaterial::new(arguments[0].clone()));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => {
+                return Err(WavefrontError::parse(
+                    path,
+                    line,
+                    format!("{} before any newmtl", keyword),
+                ))
+            }
+        };
+        match keyword.as_str() {
+            "Kd" | "Ks" => {
+                let [r, g, b] = parse_floats(&arguments, path, line)?;
+                if keyword == "Kd" {
+                    material.diffuse = Color::new(r, g, b);
+                } else {
+                    material.specular = Color::new(r, g, b);
+                }
+            }
+            "Ns" => {
+                material.specular_exponent = Some(parse_floats::<1>(&arguments, path, line)?[0])
+            }
+            "Ni" => material.index_of_refraction = parse_floats::<1>(&arguments, path, line)?[0],
+            "d" => material.dissolve = parse_floats::<1>(&arguments, path, line)?[0],
+            "Tr" => material.dissolve = 1.0 - parse_floats::<1>(&arguments, path, line)?[0],
+            "illum" => {
+                let model = arguments.first().and_then(|token| token.parse().ok());
+                if arguments.len() != 1 || model.is_none() {
+                    return Err(WavefrontError::parse(
+                        path,
+                        line,
+                        String::from("illum needs one illumination model number"),
+                    ));
+                }
+                material.illumination = model;
+            }
+            "map_Kd" => {
+                // Options such as `-s 1 1 1` may come first, the file is last.
+                match arguments.last() {
+                    Some(file) => material.diffuse_map = Some((file.clone(), line)),
+                    None => {
+                        return Err(WavefrontError::parse(
+                            path,
+                            line,
+                            String::from("map_Kd needs a file name"),
+                        ))
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Vec<MtlMaterial>, WavefrontError> {
+        parse_mtl(text.as_bytes(), Path::new("test.mtl"))
+    }
+
+    #[test]
+    fn can_parse_materials() {
+        let text = "# exported\nnewmtl red\nKd 1 0 0\nKs 0.5 0.5 0.5\nNs 10\nillum 2\n\n\
+                    newmtl glass\nNi 1.33\nd 0.2\nmap_Kd -s 1 1 1 glass.ppm\n";
+        let materials = parse(text).unwrap();
+        assert_eq!(2, materials.len());
+
+        assert_eq!("red", materials[0].name);
+        assert_relative_eq!(Color::new(1.0, 0.0, 0.0), materials[0].diffuse);
+        assert_relative_eq!(Color::new(0.5, 0.5, 0.5), materials[0].specular);
+        assert_eq!(Some(10.0), materials[0].specular_exponent);
+        assert_eq!(Some(2), materials[0].illumination);
+
+        assert_eq!("glass", materials[1].name);
+        assert_relative_eq!(1.33, materials[1].index_of_refraction);
+        assert_relative_eq!(0.2, materials[1].dissolve);
+        assert_eq!(
+            Some((String::from("glass.ppm"), 11)),
+            materials[1].diffuse_map
+        );
+    }
+
+    #[test]
+    fn mirror_without_exponent_is_sharp() {
+        let materials = parse("newmtl mirror\nKs 1 1 1\nillum 3\n").unwrap();
+        assert_eq!(None, materials[0].specular_exponent);
+        assert_relative_eq!(0.0, materials[0].fuzz());
+        let materials = parse("newmtl brushed\nKs 1 1 1\nNs 10\nillum 3\n").unwrap();
+        assert_relative_eq!((2.0_f64 / 12.0).sqrt(), materials[0].fuzz());
+    }
+
+    #[test]
+    fn reports_line_of_bad_statement() {
+        match parse("newmtl red\n\nKd 1 0\n") {
+            Err(WavefrontError::Parse { line, .. }) => assert_eq!(3, line),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        match parse("Kd 1 0 0\n") {
+            Err(WavefrontError::Parse { line, .. }) => assert_eq!(1, line),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::HashMap, io::BufRead, path::Path};
+
+use crate::{geometry::vector_3d::Vector3D, scene::triangle_mesh::MeshFace, util::point::Point3D};
+
+use super::{parse_floats, statements, WavefrontError};
+
+/// Faces that share a group name and a material.
+#[derive(Debug, PartialEq)]
+pub(super) struct ObjGroup {
+    pub name: String,
+    /// Name of the material from `usemtl`, and the line where it was chosen.
+    pub material: Option<(String, usize)>,
+    /// Triangles, indexing in to the arrays of the whole file.
+    pub faces: Vec<MeshFace>,
+}
+
+/// The contents of an OBJ file.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct ObjData {
+    pub positions: Vec<Point3D>,
+    pub normals: Vec<Vector3D>,
+    pub texture_coordinates: Vec<(f64, f64)>,
+    pub groups: Vec<ObjGroup>,
+    /// Files named by `mtllib`, and the line they were named on.
+    pub material_libraries: Vec<(String, usize)>,
+}
+
+/// One corner of a face: indices of the position, texture coordinate and
+/// normal.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+impl ObjData {
+    /// Resolves a 1-based (or negative, relative to the end) OBJ index in to a
+    /// 0-based index in to an array of `count` elements.
+    fn resolve_index(
+        token: &str,
+        count: usize,
+        kind: &str,
+        path: &Path,
+        line: usize,
+    ) -> Result<usize, WavefrontError> {
+        let index: i64 = token.parse().map_err(|_| {
+            WavefrontError::parse(path, line, format!("invalid {} index {:?}", kind, token))
+        })?;
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            count as i64 + index
+        };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(WavefrontError::parse(
+                path,
+                line,
+                format!(
+                    "{} index {} is out of range, {} defined so far",
+                    kind, index, count
+                ),
+            ));
+        }
+        Ok(resolved as usize)
+    }
+
+    /// Parses a face corner such as `3`, `3/1`, `3//2` or `3/1/2`.
+    fn parse_corner(
+        &self,
+        token: &str,
+        path: &Path,
+        line: usize,
+    ) -> Result<Corner, WavefrontError> {
+        let mut parts = token.split('/');
+        let position = ObjData::resolve_index(
+            parts.next().unwrap_or(""),
+            self.positions.len(),
+            "vertex",
+            path,
+            line,
+        )?;
+        let texture_coordinate = match parts.next() {
+            Some("") | None => None,
+            Some(part) => Some(ObjData::resolve_index(
+                part,
+                self.texture_coordinates.len(),
+                "texture coordinate",
+                path,
+                line,
+            )?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(part) => Some(ObjData::resolve_index(
+                part,
+                self.normals.len(),
+                "normal",
+                path,
+                line,
+            )?),
+        };
+        if parts.next().is_some() {
+            return Err(WavefrontError::parse(
+                path,
+                line,
+                format!("invalid face corner {:?}", token),
+            ));
+        }
+        Ok((position, texture_coordinate, normal))
+    }
+}
+
+/// Turns a polygon in to a fan of triangles around its first corner.
+fn triangulate(corners: &[Corner]) -> Vec<MeshFace> {
+    let all_textured = corners.iter().all(|corner| corner.1.is_some());
+    let all_normals = corners.iter().all(|corner| corner.2.is_some());
+
+    (1..corners.len() - 1)
+        .map(|i| {
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            MeshFace {
+                positions: triangle.map(|corner| corner.0),
+                texture_coordinates: if all_textured {
+                    Some(triangle.map(|corner| corner.1.unwrap()))
+                } else {
+                    None
+                },
+                normals: if all_normals {
+                    Some(triangle.map(|corner| corner.2.unwrap()))
+                } else {
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/// Parses the OBJ statements that describe geometry.
+///
+/// Supported are vertices (`v`), texture coordinates (`vt`), normals (`vn`),
+/// polygonal faces (`f`, triangulated as a fan), groups and objects (`g`,
+/// `o`), and the material statements `mtllib` and `usemtl`. Other statements,
+/// such as smoothing groups, lines and free-form geometry, are ignored.
+///
+/// # Arguments
+///
+/// * `reader` - Where to read the OBJ file from.
+/// * `path` - Where the file came from, used in error messages.
+pub(super) fn parse_obj(reader: impl BufRead, path: &Path) -> Result<ObjData, WavefrontError> {
+    let mut data = ObjData::default();
+    let mut group_name = String::from("default");
+    let mut material: Option<(String, usize)> = None;
+    let mut group_indices: HashMap<(String, Option<String>), usize> = HashMap::new();
+
+    for statement in statements(reader, path) {
+        let (line, keyword, arguments) = statement?;
+        match keyword.as_str() {
+            "v" => {
+                // An optional fourth component (w) is allowed, but not used.
+                let [x, y, z] = parse_floats(&arguments[..arguments.len().min(3)], path, line)?;
+                data.positions.push(Point3D::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&arguments, path, line)?;
+                data.normals.push(Vector3D::new(x, y, z));
+            }
+            "vt" => {
+                // The optional w coordinate is ignored, and v defaults to 0.
+                let [u] = parse_floats(&arguments[..1.min(arguments.len())], path, line)?;
+                let v = match arguments.get(1) {
+                    Some(_) => parse_floats::<1>(&arguments[1..2], path, line)?[0],
+                    None => 0.0,
+                };
+                data.texture_coordinates.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(WavefrontError::parse(
+                        path,
+                        line,
+                        format!("face needs at least 3 corners, found {}", arguments.len()),
+                    ));
+                }
+                let corners = arguments
+                    .iter()
+                    .map(|token| data.parse_corner(token, path, line))
+                    .collect::<Result<Vec<Corner>, WavefrontError>>()?;
+
+                let key = (
+                    group_name.clone(),
+                    material.as_ref().map(|(name, _)| name.clone()),
+                );
+                let index = *group_indices.entry(key).or_insert_with(|| {
+                    data.groups.push(ObjGroup {
+                        name: group_name.clone(),
+                        material: material.clone(),
+                        faces: vec![],
+                    });
+                    data.groups.len() - 1
+                });
+                data.groups[index].faces.extend(triangulate(&corners));
+            }
+            "g" | "o" => {
+                group_name = if arguments.is_empty() {
+                    String::from("default")
+                } else {
+                    arguments.join(" ")
+                };
+            }
+            "usemtl" => {
+                if arguments.len() != 1 {
+                    return Err(WavefrontError::parse(
+                        path,
+                        line,
+                        String::from("usemtl needs exactly one material name"),
+                    ));
+                }
+                material = Some((arguments[0].clone(), line));
+            }
+            "mtllib" => {
+                if arguments.is_empty() {
+                    return Err(WavefrontError::parse(
+                        path,
+                        line,
+                        String::from("mtllib needs a file name"),
+                    ));
+                }
+                data.material_libraries
+                    .extend(arguments.into_iter().map(|library| (library, line)));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ObjData, WavefrontError> {
+        parse_obj(text.as_bytes(), Path::new("test.obj"))
+    }
+
+    fn error_line(result: Result<ObjData, WavefrontError>) -> usize {
+        match result {
+            Err(WavefrontError::Parse { line, .. }) => line,
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn can_parse_triangle() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 0 1 0 1.0\nf 1 2 3\n").unwrap();
+        assert_eq!(3, data.positions.len());
+        assert_eq!(1, data.groups.len());
+        assert_eq!(vec![MeshFace::new([0, 1, 2])], data.groups[0].faces);
+    }
+
+    #[test]
+    fn triangulates_polygons_as_fan() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        assert_eq!(
+            vec![MeshFace::new([0, 1, 2]), MeshFace::new([0, 2, 3])],
+            data.groups[0].faces
+        );
+    }
+
+    #[test]
+    fn can_parse_corners_with_texture_coordinates_and_normals() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+                    f 1/1/1 2/2/1 3/3/1\nf -3//1 -2//1 -1//1\n";
+        let faces = &parse(text).unwrap().groups[0].faces;
+        assert_eq!(Some([0, 1, 2]), faces[0].texture_coordinates);
+        assert_eq!(Some([0, 0, 0]), faces[0].normals);
+        assert_eq!([0, 1, 2], faces[1].positions);
+        assert_eq!(None, faces[1].texture_coordinates);
+    }
+
+    #[test]
+    fn splits_groups_by_name_and_material() {
+        let text = "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+                    g first\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 2 3\n\
+                    g second\nf 1 2 3\ng first\nusemtl red\nf 3 2 1\n";
+        let data = parse(text).unwrap();
+        assert_eq!(
+            vec![(String::from("scene.mtl"), 1)],
+            data.material_libraries
+        );
+        let summary: Vec<(&str, Option<&str>, usize)> = data
+            .groups
+            .iter()
+            .map(|group| {
+                (
+                    group.name.as_str(),
+                    group.material.as_ref().map(|(name, _)| name.as_str()),
+                    group.faces.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("first", Some("red"), 2),
+                ("first", Some("blue"), 1),
+                ("second", Some("blue"), 1)
+            ],
+            summary
+        );
+    }
+
+    #[test]
+    fn ignores_comments_and_unknown_statements() {
+        let text = "# comment\n\ns 1\nv 0 0 0 # trailing\nv 1 0 0\nv 0 1 0\nl 1 2\nf 1 2 3\n";
+        assert_eq!(1, parse(text).unwrap().groups[0].faces.len());
+    }
+
+    #[test]
+    fn reports_line_of_bad_vertex() {
+        assert_eq!(2, error_line(parse("v 0 0 0\nv 1 zero 0\n")));
+        assert_eq!(1, error_line(parse("v 1 0\n")));
+    }
+
+    #[test]
+    fn reports_line_of_out_of_range_index() {
+        assert_eq!(4, error_line(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n")));
+        assert_eq!(2, error_line(parse("v 0 0 0\nf 1 0 1\n")));
+        assert_eq!(2, error_line(parse("v 0 0 0\nf 1/1 1/1 1/1\n")));
+    }
+
+    #[test]
+    fn reports_line_of_degenerate_face() {
+        assert_eq!(3, error_line(parse("v 0 0 0\nv 1 0 0\nf 1 2\n")));
+    }
+}
@@ -1,4 +1,4 @@
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 use crate::{
     geometry::vector_3d::Vector3D,
@@ -13,7 +13,9 @@ use super::{
     textures::CheckerTexture,
 };
 
-pub fn random_scene() -> Vec<Box<dyn Hittable>> {
+/// Many small balls around three big ones, placed and colored at random with
+/// `generator`.
+pub fn random_scene(generator: &mut dyn RngCore) -> Vec<Box<dyn Hittable>> {
     let mut world: Vec<Box<dyn Hittable>> = vec![];
 
     let checker_texture = Box::new(CheckerTexture::from_colors(
@@ -27,8 +29,6 @@ pub fn random_scene() -> Vec<Box<dyn Hittable>> {
         checker_material,
     )));
 
-    let mut generator = rand::thread_rng();
-
     for a in -11..11 {
         for b in -11..11 {
             let choosen_material = generator.gen_range(0.0..0.5);
@@ -41,10 +41,10 @@ pub fn random_scene() -> Vec<Box<dyn Hittable>> {
             if (center - Point3D::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 let sphere_material: Box<dyn Material> = if choosen_material < 0.8 {
                     // diffuse
-                    let albedo = Color::all_random() * Color::all_random();
+                    let albedo = Color::all_random(generator) * Color::all_random(generator);
                     Box::new(Lambertian::new(albedo))
                 } else if choosen_material < 0.95 {
-                    let albedo = Color::random(0.5, 1.0);
+                    let albedo = Color::random(0.5, 1.0, generator);
                     let fuzz = generator.gen_range(0.0..0.5);
                     Box::new(Metal::new(albedo, fuzz))
                 } else {
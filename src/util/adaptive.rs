@@ -0,0 +1,251 @@
+//! Spending samples on the pixels that need them. A pixel of flat sky is
+//! as good after a few samples as after hundreds, while one on the edge of
+//! a soft shadow keeps getting better.
+
+use std::io::{self, Read, Write};
+
+use super::{
+    checkpoint::{read_color, read_f64, read_u64, write_color, write_f64, write_u64},
+    color::Color,
+    framebuffer::Framebuffer,
+};
+
+/// How many standard errors the true brightness of a pixel may be from its
+/// mean, for a 95% confidence interval.
+const CONFIDENCE: f64 = 1.96;
+/// Pixels darker than this are compared against it instead, so that the
+/// error allowed in nearly black pixels doesn't shrink to nothing.
+const DARKEST: f64 = 0.01;
+
+/// The brightness of a linear color, by the Rec. 709 weights.
+fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// The running mean of the samples of a pixel, and the variance of their
+/// brightness, kept with Welford's algorithm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelStatistics {
+    count: u64,
+    sum: Color,
+    mean_luminance: f64,
+    /// The sum of the squared differences between the brightness of every
+    /// sample and the mean.
+    squared_deviations: f64,
+}
+
+impl PixelStatistics {
+    /// Returns statistics of no samples.
+    pub fn new() -> PixelStatistics {
+        PixelStatistics {
+            count: 0,
+            sum: Color::empty(),
+            mean_luminance: 0.0,
+            squared_deviations: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, color: &Color) {
+        self.count += 1;
+        self.sum += color;
+        let luminance = luminance(color);
+        let deviation = luminance - self.mean_luminance;
+        self.mean_luminance += deviation / self.count as f64;
+        self.squared_deviations += deviation * (luminance - self.mean_luminance);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean color of the samples, or black if there are none.
+    pub fn mean(&self) -> Color {
+        if self.count == 0 {
+            return Color::empty();
+        }
+        self.sum / self.count as f64
+    }
+
+    /// The sample variance of the brightness of the samples.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.squared_deviations / (self.count - 1) as f64
+    }
+
+    /// Writes the statistics, for a checkpoint.
+    pub fn write_to(&self, output: &mut dyn Write) -> io::Result<()> {
+        write_u64(output, self.count)?;
+        write_color(output, &self.sum)?;
+        write_f64(output, self.mean_luminance)?;
+        write_f64(output, self.squared_deviations)
+    }
+
+    /// Reads statistics written by `write_to`.
+    pub fn read_from(input: &mut dyn Read) -> io::Result<PixelStatistics> {
+        Ok(PixelStatistics {
+            count: read_u64(input)?,
+            sum: read_color(input)?,
+            mean_luminance: read_f64(input)?,
+            squared_deviations: read_f64(input)?,
+        })
+    }
+
+    /// Half the width of the 95% confidence interval of the mean brightness.
+    pub fn error(&self) -> f64 {
+        if self.count == 0 {
+            return f64::INFINITY;
+        }
+        CONFIDENCE * (self.variance() / self.count as f64).sqrt()
+    }
+}
+
+impl Default for PixelStatistics {
+    fn default() -> Self {
+        PixelStatistics::new()
+    }
+}
+
+/// When to stop sampling a pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel gets before it may stop. At least two are always
+    /// taken, as one sample says nothing about the variance.
+    pub min_samples: u64,
+    /// Samples no pixel goes past.
+    pub max_samples: u64,
+    /// How large the confidence interval may be, as a fraction of the
+    /// brightness of the pixel.
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    /// Gives every pixel exactly `samples` samples.
+    pub fn fixed(samples: u64) -> AdaptiveSampling {
+        AdaptiveSampling {
+            min_samples: samples,
+            max_samples: samples,
+            threshold: 0.0,
+        }
+    }
+
+    /// Whether a pixel with these statistics has had enough samples.
+    pub fn is_done(&self, statistics: &PixelStatistics) -> bool {
+        let count = statistics.count();
+        if count >= self.max_samples {
+            return true;
+        }
+        count >= self.min_samples.max(2)
+            && statistics.error() <= self.threshold * statistics.mean_luminance.max(DARKEST)
+    }
+}
+
+/// An image of how many samples every pixel got, from black for none,
+/// through blue and red, to white for `max_samples`.
+///
+/// # Arguments
+///
+/// * `counts` - The number of samples of every pixel, row by row from the
+///   top left.
+///
+/// # Panics
+///
+/// If the number of counts doesn't match the dimensions.
+pub fn sample_count_heatmap(
+    width: usize,
+    height: usize,
+    counts: &[u64],
+    max_samples: u64,
+) -> Framebuffer {
+    const RAMP: [(f64, f64, f64); 5] = [
+        (0.0, 0.0, 0.0),
+        (0.05, 0.05, 0.8),
+        (0.9, 0.1, 0.1),
+        (1.0, 0.85, 0.1),
+        (1.0, 1.0, 1.0),
+    ];
+    let pixels = counts
+        .iter()
+        .map(|&count| {
+            let position =
+                (count as f64 / max_samples.max(1) as f64).min(1.0) * (RAMP.len() - 1) as f64;
+            let index = (position as usize).min(RAMP.len() - 2);
+            let (low, high) = (RAMP[index], RAMP[index + 1]);
+            let t = position - index as f64;
+            Color::new(
+                low.0 + (high.0 - low.0) * t,
+                low.1 + (high.1 - low.1) * t,
+                low.2 + (high.2 - low.2) * t,
+            )
+        })
+        .collect();
+    Framebuffer::from_pixels(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn gray(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn tracks_mean_and_variance() {
+        let mut statistics = PixelStatistics::new();
+        assert_relative_eq!(Color::empty(), statistics.mean());
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            statistics.add(&gray(value));
+        }
+        assert_eq!(8, statistics.count());
+        assert_relative_eq!(gray(5.0), statistics.mean(), epsilon = 1e-12);
+        assert_relative_eq!(32.0 / 7.0, statistics.variance(), epsilon = 1e-12);
+        assert_relative_eq!(
+            1.96 * (32.0 / 7.0 / 8.0f64).sqrt(),
+            statistics.error(),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn stops_flat_pixels_early() {
+        let adaptive = AdaptiveSampling {
+            min_samples: 4,
+            max_samples: 64,
+            threshold: 0.05,
+        };
+        let mut flat = PixelStatistics::new();
+        while !adaptive.is_done(&flat) {
+            flat.add(&gray(0.7));
+        }
+        assert_eq!(4, flat.count());
+
+        // Samples that are either black or white never settle enough.
+        let mut noisy = PixelStatistics::new();
+        while !adaptive.is_done(&noisy) {
+            noisy.add(&gray((noisy.count() % 2) as f64));
+        }
+        assert_eq!(64, noisy.count());
+    }
+
+    #[test]
+    fn fixed_sampling_takes_every_sample() {
+        let adaptive = AdaptiveSampling::fixed(10);
+        let mut statistics = PixelStatistics::new();
+        while !adaptive.is_done(&statistics) {
+            statistics.add(&gray(1.0));
+        }
+        assert_eq!(10, statistics.count());
+    }
+
+    #[test]
+    fn heatmap_goes_from_black_to_white() {
+        let heatmap = sample_count_heatmap(3, 1, &[0, 50, 100], 100);
+        assert_relative_eq!(Color::empty(), heatmap.pixel(0, 0));
+        assert_relative_eq!(Color::new(0.9, 0.1, 0.1), heatmap.pixel(1, 0));
+        assert_relative_eq!(gray(1.0), heatmap.pixel(2, 0));
+    }
+}
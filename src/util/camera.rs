@@ -1,4 +1,4 @@
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 use crate::geometry::{
     ray::Ray,
@@ -42,6 +42,7 @@ impl Camera {
     /// * `focus_distance` - What distance the camera is focusing on.
     /// * `start_time` - When the shutter opens.
     /// * `end_time` - When the shutter closes.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Point3D,
         look_at: Point3D,
@@ -82,17 +83,72 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vector3D::random_in_unit_disk();
+    /// The ray through a point of the image, from a point of the lens at a
+    /// random time while the shutter is open. A shutter that closes as soon
+    /// as it opens gives every ray the same time.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - How far across the image the point is, from 0 at the left to
+    ///   1 at the right.
+    /// * `t` - How far up the image the point is, from 0 at the bottom to 1
+    ///   at the top.
+    /// * `lens` - Two numbers from 0 up to 1 that pick the point of the lens
+    ///   the ray starts at. Numbers spread evenly over the square give points
+    ///   spread evenly over the lens.
+    /// * `generator` - Where the random time comes from.
+    pub fn get_ray(&self, s: f64, t: f64, lens: [f64; 2], generator: &mut dyn RngCore) -> Ray {
+        let rd = self.lens_radius * Vector3D::in_unit_disk(lens[0], lens[1]);
         let offset = self.u * rd.x() + self.v * rd.y();
-        let mut rng = rand::thread_rng();
 
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + (s * self.horizontal) + (t * self.vertical)
                 - self.origin
                 - offset,
-            Some(rng.gen_range(self.start_time..self.end_time)),
+            Some(if self.start_time < self.end_time {
+                generator.gen_range(self.start_time..self.end_time)
+            } else {
+                self.start_time
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn camera(start_time: f64, end_time: f64) -> Camera {
+        Camera::new(
+            Point3D::new(0.0, 0.0, -5.0),
+            Point3D::empty(),
+            Vector3D::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.0,
+            5.0,
+            start_time,
+            end_time,
         )
     }
+
+    #[test]
+    fn rays_are_timed_while_the_shutter_is_open() {
+        let camera = camera(0.25, 0.75);
+        let mut generator = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let time = camera.get_ray(0.5, 0.5, [0.5, 0.5], &mut generator).time;
+            assert!((0.25..0.75).contains(&time));
+        }
+    }
+
+    #[test]
+    fn instant_shutter_gives_its_time() {
+        let camera = camera(0.5, 0.5);
+        let ray = camera.get_ray(0.5, 0.5, [0.5, 0.5], &mut StdRng::seed_from_u64(0));
+        assert_eq!(0.5, ray.time);
+    }
 }
@@ -0,0 +1,245 @@
+//! Saving a render in progress, so that it can be carried on after it was
+//! stopped.
+//!
+//! A checkpoint holds the weighted sums of the film and the statistics of
+//! the samples of every pixel, in little endian binary, after a header with
+//! the settings the samples depend on. As every sample has its own
+//! generator, carrying on from a checkpoint gives the same image as
+//! rendering without stopping, but for rounding, as the samples are added
+//! up in a different order.
+
+use std::io::{self, Read, Write};
+
+use super::{
+    color::Color,
+    filter::{Filter, FilterKind},
+    sampler::SamplerKind,
+};
+use crate::scene::settings::RenderSettings;
+
+const MAGIC: &[u8; 8] = b"RTCHECK2";
+
+/// The settings that decide which samples a render takes. A checkpoint can
+/// only be carried on with the same ones; others, like the number of
+/// samples, can change. The stratified sampler sizes its grid from the
+/// number of samples, so with it that can't change either.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointHeader {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub max_depth: i32,
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub samples_per_pixel: u64,
+}
+
+impl CheckpointHeader {
+    pub fn new(settings: &RenderSettings) -> CheckpointHeader {
+        CheckpointHeader {
+            width: settings.width.max(0) as usize,
+            height: settings.height().max(0) as usize,
+            seed: settings.seed,
+            max_depth: settings.max_depth,
+            sampler: settings.sampler,
+            filter: settings.filter,
+            samples_per_pixel: settings.samples_per_pixel.max(0) as u64,
+        }
+    }
+
+    pub fn write_to(&self, output: &mut dyn Write) -> io::Result<()> {
+        output.write_all(MAGIC)?;
+        write_u64(output, self.width as u64)?;
+        write_u64(output, self.height as u64)?;
+        write_u64(output, self.seed)?;
+        write_u64(output, self.max_depth as u64)?;
+        let sampler = match self.sampler {
+            SamplerKind::Independent => 0,
+            SamplerKind::Stratified => 1,
+            SamplerKind::Halton => 2,
+            SamplerKind::Sobol => 3,
+        };
+        let filter = match self.filter.kind {
+            FilterKind::Box => 0,
+            FilterKind::Tent => 1,
+            FilterKind::Gaussian => 2,
+            FilterKind::Mitchell => 3,
+            FilterKind::Lanczos => 4,
+        };
+        output.write_all(&[sampler, filter])?;
+        write_f64(output, self.filter.radius)?;
+        write_u64(output, self.samples_per_pixel)
+    }
+
+    pub fn read_from(input: &mut dyn Read) -> io::Result<CheckpointHeader> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a checkpoint file".to_string()));
+        }
+        let width = read_u64(input)? as usize;
+        let height = read_u64(input)? as usize;
+        let seed = read_u64(input)?;
+        let max_depth = read_u64(input)? as i32;
+        let mut kinds = [0; 2];
+        input.read_exact(&mut kinds)?;
+        let sampler = match kinds[0] {
+            0 => SamplerKind::Independent,
+            1 => SamplerKind::Stratified,
+            2 => SamplerKind::Halton,
+            3 => SamplerKind::Sobol,
+            code => return Err(invalid_data(format!("Unknown sampler {}", code))),
+        };
+        let kind = match kinds[1] {
+            0 => FilterKind::Box,
+            1 => FilterKind::Tent,
+            2 => FilterKind::Gaussian,
+            3 => FilterKind::Mitchell,
+            4 => FilterKind::Lanczos,
+            code => return Err(invalid_data(format!("Unknown filter {}", code))),
+        };
+        let radius = read_f64(input)?;
+        let samples_per_pixel = read_u64(input)?;
+        Ok(CheckpointHeader {
+            width,
+            height,
+            seed,
+            max_depth,
+            sampler,
+            filter: Filter { kind, radius },
+            samples_per_pixel,
+        })
+    }
+
+    /// Returns an error naming the first setting that differs from those
+    /// of the render that would carry the checkpoint on.
+    pub fn check_matches(&self, render: &CheckpointHeader) -> io::Result<()> {
+        let differences = [
+            (
+                (self.width, self.height) != (render.width, render.height),
+                "image size",
+            ),
+            (self.seed != render.seed, "seed"),
+            (self.max_depth != render.max_depth, "maximum depth"),
+            (self.sampler != render.sampler, "sampler"),
+            (self.filter != render.filter, "filter"),
+            (
+                self.sampler == SamplerKind::Stratified
+                    && self.samples_per_pixel != render.samples_per_pixel,
+                "number of samples, which the stratified sampler needs to be the same",
+            ),
+        ];
+        match differences.iter().find(|(differs, _)| *differs) {
+            Some((_, setting)) => Err(invalid_data(format!(
+                "The checkpoint was made with a different {}",
+                setting
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn write_u64(output: &mut dyn Write, value: u64) -> io::Result<()> {
+    output.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u64(input: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn write_f64(output: &mut dyn Write, value: f64) -> io::Result<()> {
+    output.write_all(&value.to_le_bytes())
+}
+
+pub fn read_f64(input: &mut dyn Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+pub fn write_color(output: &mut dyn Write, color: &Color) -> io::Result<()> {
+    for channel in [color.x(), color.y(), color.z()] {
+        write_f64(output, channel)?;
+    }
+    Ok(())
+}
+
+pub fn read_color(input: &mut dyn Read) -> io::Result<Color> {
+    Ok(Color::new(
+        read_f64(input)?,
+        read_f64(input)?,
+        read_f64(input)?,
+    ))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> CheckpointHeader {
+        CheckpointHeader {
+            width: 640,
+            height: 480,
+            seed: 99,
+            max_depth: 20,
+            sampler: SamplerKind::Sobol,
+            filter: Filter::new(FilterKind::Mitchell),
+            samples_per_pixel: 64,
+        }
+    }
+
+    #[test]
+    fn header_survives_round_trip() {
+        let mut bytes = Vec::new();
+        header().write_to(&mut bytes).unwrap();
+        let read = CheckpointHeader::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(header(), read);
+        assert!(read.check_matches(&header()).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_files_and_settings() {
+        let error = CheckpointHeader::read_from(&mut &b"P3\n1 1\n255\n0 0 0\n"[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        let other = CheckpointHeader {
+            seed: 100,
+            ..header()
+        };
+        let error = header().check_matches(&other).unwrap_err();
+        assert_eq!(
+            "The checkpoint was made with a different seed",
+            error.to_string()
+        );
+        let other = CheckpointHeader {
+            filter: Filter {
+                kind: FilterKind::Mitchell,
+                radius: 1.0,
+            },
+            ..header()
+        };
+        assert!(header().check_matches(&other).is_err());
+
+        // Only the stratified sampler needs the same number of samples.
+        let more_samples = CheckpointHeader {
+            samples_per_pixel: 128,
+            ..header()
+        };
+        assert!(header().check_matches(&more_samples).is_ok());
+        let stratified = CheckpointHeader {
+            sampler: SamplerKind::Stratified,
+            ..header()
+        };
+        let error = stratified
+            .check_matches(&CheckpointHeader {
+                sampler: SamplerKind::Stratified,
+                ..more_samples
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("number of samples"));
+    }
+}
@@ -0,0 +1,301 @@
+//! Where samples are gathered in to pixels. A sample counts toward every
+//! pixel whose center is within the radius of the filter, weighted by the
+//! filter, and every pixel ends up as the weighted mean of its samples.
+//!
+//! Samples are added to tiles, which can be filled on separate threads and
+//! are merged in to the film one after another. Merging tiles in the same
+//! order gives exactly the same image, whichever threads filled them.
+
+use std::io::{self, Read, Write};
+
+use crate::scene::settings::Region;
+
+use super::{
+    checkpoint::{read_color, read_f64, write_color, write_f64},
+    color::Color,
+    filter::Filter,
+    framebuffer::Framebuffer,
+};
+
+/// The weighted sums of the samples of every pixel of an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    /// The pixels samples may count toward.
+    region: Region,
+    /// Sums of the weighted colors, row by row from the top left.
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    /// Returns a film with no samples.
+    pub fn new(width: usize, height: usize, filter: Filter) -> Film {
+        Film {
+            width,
+            height,
+            filter,
+            region: Region {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            sums: vec![Color::empty(); width * height],
+            weights: vec![0.0; width * height],
+        }
+    }
+
+    /// Keeps samples from counting toward pixels outside the region, so
+    /// when only part of an image is rendered the rest stays black.
+    ///
+    /// # Panics
+    ///
+    /// If the region doesn't fit in the film.
+    pub fn with_region(mut self, region: Region) -> Film {
+        assert!(
+            region.fits(self.width, self.height),
+            "The region {:?} doesn't fit in the film.",
+            region
+        );
+        self.region = region;
+        self
+    }
+
+    /// Returns an empty tile for the samples of a rectangle of pixels. The
+    /// tile also covers the pixels around the rectangle that its samples
+    /// reach, within the region of the film.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The leftmost column of the rectangle.
+    /// * `y` - The top row of the rectangle.
+    /// * `width` - How many columns the rectangle spans.
+    /// * `height` - How many rows the rectangle spans.
+    pub fn tile(&self, x: usize, y: usize, width: usize, height: usize) -> FilmTile {
+        // Samples are in their pixel, so they reach as far as the radius
+        // from its edge, past the center of the pixel next to it.
+        let border = (self.filter.radius + 0.5).ceil() as usize;
+        let region = &self.region;
+        let left = x.saturating_sub(border).max(region.x);
+        let top = y.saturating_sub(border).max(region.y);
+        let right = (x + width)
+            .saturating_add(border)
+            .min(region.x + region.width);
+        let bottom = (y + height)
+            .saturating_add(border)
+            .min(region.y + region.height);
+        let size = (right - left) * (bottom - top);
+        FilmTile {
+            left,
+            top,
+            width: right - left,
+            height: bottom - top,
+            filter: self.filter,
+            sums: vec![Color::empty(); size],
+            weights: vec![0.0; size],
+        }
+    }
+
+    /// Adds the samples of a tile to the film.
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for row in 0..tile.height {
+            for column in 0..tile.width {
+                let own = (tile.top + row) * self.width + tile.left + column;
+                let index = row * tile.width + column;
+                self.sums[own] += &tile.sums[index];
+                self.weights[own] += tile.weights[index];
+            }
+        }
+    }
+
+    /// Writes the sums of every pixel, for a checkpoint.
+    pub fn write_to(&self, output: &mut dyn Write) -> io::Result<()> {
+        for (sum, &weight) in self.sums.iter().zip(&self.weights) {
+            write_color(output, sum)?;
+            write_f64(output, weight)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the sums of every pixel with those written by `write_to`
+    /// for a film of the same size.
+    pub fn read_from(&mut self, input: &mut dyn Read) -> io::Result<()> {
+        for (sum, weight) in self.sums.iter_mut().zip(&mut self.weights) {
+            *sum = read_color(input)?;
+            *weight = read_f64(input)?;
+        }
+        Ok(())
+    }
+
+    /// The weighted mean of the samples of every pixel. Filters with
+    /// negative lobes can make dark pixels next to bright ones negative,
+    /// which are clamped to black, as are pixels with no samples.
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        let pixels = self
+            .sums
+            .iter()
+            .zip(&self.weights)
+            .map(|(sum, &weight)| {
+                if weight <= 0.0 {
+                    return Color::empty();
+                }
+                let color = sum / weight;
+                Color::new(color.x().max(0.0), color.y().max(0.0), color.z().max(0.0))
+            })
+            .collect();
+        Framebuffer::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+/// The weighted sums of the samples of a part of a film.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilmTile {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl FilmTile {
+    /// Adds a sample to every pixel of the tile it reaches.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Where the sample is, in pixels from the left edge of the
+    ///   image.
+    /// * `y` - Where the sample is, in pixels from the top edge of the image.
+    /// * `color` - The color of the sample.
+    pub fn add_sample(&mut self, x: f64, y: f64, color: &Color) {
+        let radius = self.filter.radius;
+        // The pixels whose centers are within the radius.
+        let first = |position: f64, start: usize| {
+            ((position - 0.5 - radius).ceil().max(0.0) as usize).max(start)
+        };
+        let end = |position: f64, start: usize, size: usize| {
+            (((position - 0.5 + radius).floor() + 1.0).max(0.0) as usize).min(start + size)
+        };
+        for row in first(y, self.top)..end(y, self.top, self.height) {
+            let offset_y = row as f64 + 0.5 - y;
+            for column in first(x, self.left)..end(x, self.left, self.width) {
+                let weight = self.filter.weight(column as f64 + 0.5 - x, offset_y);
+                if weight == 0.0 {
+                    continue;
+                }
+                let index = (row - self.top) * self.width + column - self.left;
+                self.sums[index] += &(*color * weight);
+                self.weights[index] += weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::util::filter::FilterKind;
+
+    #[test]
+    fn box_filter_averages_samples_of_pixel() {
+        let mut film = Film::new(3, 2, Filter::default());
+        let mut tile = film.tile(0, 0, 3, 2);
+        tile.add_sample(1.2, 0.3, &Color::new(1.0, 0.0, 0.0));
+        tile.add_sample(1.7, 0.9, &Color::new(0.0, 0.0, 1.0));
+        film.merge(&tile);
+        let framebuffer = film.to_framebuffer();
+        assert_relative_eq!(Color::new(0.5, 0.0, 0.5), framebuffer.pixel(1, 0));
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(0, 0));
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(1, 1));
+    }
+
+    #[test]
+    fn wide_filters_reach_neighbouring_pixels() {
+        let filter = Filter::new(FilterKind::Tent);
+        let mut film = Film::new(3, 3, filter);
+        let mut tile = film.tile(1, 1, 1, 1);
+        let white = Color::new(1.0, 1.0, 1.0);
+        tile.add_sample(1.2, 1.5, &white);
+        tile.add_sample(1.8, 1.5, &Color::empty());
+        film.merge(&tile);
+        let framebuffer = film.to_framebuffer();
+        // Both samples are as far from the center of their pixel.
+        assert_relative_eq!(white * 0.5, framebuffer.pixel(1, 1), epsilon = 1e-12);
+        // Each reaches the pixel on its own side.
+        assert_relative_eq!(white, framebuffer.pixel(0, 1), epsilon = 1e-12);
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(2, 1));
+        // The rows above and below are just out of reach.
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(1, 0));
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(1, 2));
+    }
+
+    #[test]
+    fn samples_stay_in_region() {
+        let filter = Filter::new(FilterKind::Tent);
+        let region = Region {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 1,
+        };
+        let mut film = Film::new(4, 3, filter).with_region(region);
+        let mut tile = film.tile(1, 1, 2, 1);
+        let white = Color::new(1.0, 1.0, 1.0);
+        for (x, y) in [(1.2, 1.5), (2.8, 1.5), (1.5, 1.2), (2.5, 1.8)] {
+            tile.add_sample(x, y, &white);
+        }
+        film.merge(&tile);
+        let framebuffer = film.to_framebuffer();
+        for y in 0..3 {
+            for x in 0..4 {
+                let inside = (1..3).contains(&x) && y == 1;
+                let expected = if inside { white } else { Color::empty() };
+                assert_relative_eq!(expected, framebuffer.pixel(x, y), epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_filter_covers_whole_film() {
+        let mut filter = Filter::new(FilterKind::Box);
+        filter.radius = 1e300;
+        let tile = Film::new(4, 3, filter).tile(1, 1, 1, 1);
+        assert_eq!((0, 0, 4, 3), (tile.left, tile.top, tile.width, tile.height));
+    }
+
+    #[test]
+    fn tiles_add_up_to_whole_film() {
+        let filter = Filter::new(FilterKind::Gaussian);
+        let samples = [
+            (0.2, 0.1, Color::new(1.0, 0.5, 0.0)),
+            (2.5, 1.5, Color::new(0.0, 1.0, 0.0)),
+            (3.9, 3.9, Color::new(0.2, 0.2, 4.0)),
+            (1.1, 3.2, Color::new(0.3, 0.0, 0.0)),
+        ];
+        let mut whole = Film::new(4, 4, filter);
+        let mut tile = whole.tile(0, 0, 4, 4);
+        for (x, y, color) in &samples {
+            tile.add_sample(*x, *y, color);
+        }
+        whole.merge(&tile);
+
+        let mut tiled = Film::new(4, 4, filter);
+        for (x, y, color) in &samples {
+            let mut tile = tiled.tile(*x as usize, *y as usize, 1, 1);
+            tile.add_sample(*x, *y, color);
+            tiled.merge(&tile);
+        }
+        let (whole, tiled) = (whole.to_framebuffer(), tiled.to_framebuffer());
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_relative_eq!(whole.pixel(x, y), tiled.pixel(x, y), epsilon = 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::f64::consts::PI;
+
+/// The shapes of filter that turn samples in to pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FilterKind {
+    /// Every sample within the radius counts the same. With a radius of half
+    /// a pixel this is plain averaging.
+    #[default]
+    Box,
+    /// Weights fall off in a straight line to the radius.
+    Tent,
+    /// A Gaussian with a standard deviation of a third of the radius, shifted
+    /// down to reach 0 at the radius.
+    Gaussian,
+    /// Mitchell and Netravali's cubic with B = C = 1/3, which sharpens a
+    /// little with small negative lobes.
+    Mitchell,
+    /// A sinc windowed by a wider sinc, the sharpest, with lobes out to the
+    /// radius.
+    Lanczos,
+}
+
+impl FilterKind {
+    /// The radius in pixels the filter is usually used with.
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+/// Weighs how much a sample counts toward a pixel by how far it is from the
+/// center of the pixel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// How far from the center of a pixel, in pixels along each axis,
+    /// samples still count toward it.
+    pub radius: f64,
+}
+
+impl Filter {
+    /// Returns a filter of the given kind with its usual radius.
+    pub fn new(kind: FilterKind) -> Filter {
+        Filter {
+            kind,
+            radius: kind.default_radius(),
+        }
+    }
+
+    /// The weight of a sample that is `x` pixels across and `y` pixels down
+    /// from the center of a pixel. The weight can be negative for filters
+    /// that sharpen.
+    pub fn weight(&self, x: f64, y: f64) -> f64 {
+        self.weight_1d(x) * self.weight_1d(y)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x > self.radius {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => self.radius - x,
+            FilterKind::Gaussian => {
+                let gaussian = |x: f64| {
+                    let deviation = self.radius / 3.0;
+                    (-x * x / (2.0 * deviation * deviation)).exp()
+                };
+                gaussian(x) - gaussian(self.radius)
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / self.radius),
+            FilterKind::Lanczos => sinc(x) * sinc(x / self.radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::default())
+    }
+}
+
+/// The Mitchell-Netravali cubic with B = C = 1/3, which reaches 0 at 2.
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * B - 6.0 * C) * x.powi(3)
+            + (-18.0 + 12.0 * B + 6.0 * C) * x.powi(2)
+            + (6.0 - 2.0 * B)
+    } else if x < 2.0 {
+        (-B - 6.0 * C) * x.powi(3)
+            + (6.0 * B + 30.0 * C) * x.powi(2)
+            + (-12.0 * B - 48.0 * C) * x
+            + (8.0 * B + 24.0 * C)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn weights_peak_in_the_center_and_end_at_the_radius() {
+        for kind in KINDS {
+            let filter = Filter::new(kind);
+            let center = filter.weight(0.0, 0.0);
+            assert!(center > 0.0, "{:?}", kind);
+            for step in 1..20 {
+                let x = filter.radius * f64::from(step) / 10.0;
+                assert!(filter.weight(x, 0.0) <= center, "{:?}", kind);
+                assert_relative_eq!(filter.weight(x, 0.3), filter.weight(-x, -0.3));
+            }
+            assert_relative_eq!(0.0, filter.weight(filter.radius * 1.01, 0.0));
+            assert_relative_eq!(0.0, filter.weight(0.0, filter.radius + 0.1));
+        }
+    }
+
+    #[test]
+    fn weights_match_known_values() {
+        let weight = |kind, x| Filter::new(kind).weight_1d(x);
+        assert_relative_eq!(1.0, weight(FilterKind::Box, 0.5));
+        assert_relative_eq!(0.25, weight(FilterKind::Tent, 0.75));
+        assert_relative_eq!(0.0, weight(FilterKind::Gaussian, 1.5));
+        assert_relative_eq!(8.0 / 9.0, weight(FilterKind::Mitchell, 0.0));
+        assert_relative_eq!(0.0, weight(FilterKind::Mitchell, 2.0), epsilon = 1e-12);
+        assert_relative_eq!(0.0, weight(FilterKind::Lanczos, 1.0), epsilon = 1e-12);
+        // The sharpening filters have negative lobes.
+        assert!(weight(FilterKind::Mitchell, 1.5) < 0.0);
+        assert!(weight(FilterKind::Lanczos, 1.5) < 0.0);
+        // The two pieces of the cubic meet.
+        assert_relative_eq!(mitchell(1.0 - 1e-9), mitchell(1.0), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn radius_stretches_filter() {
+        let wide = Filter {
+            kind: FilterKind::Tent,
+            radius: 2.0,
+        };
+        assert_relative_eq!(1.0, wide.weight_1d(1.0));
+        assert_relative_eq!(0.0, wide.weight_1d(2.5));
+    }
+}
@@ -0,0 +1,102 @@
+use super::{color::Color, image::Image, tone_map::ToneMapping};
+
+/// The linear radiance of every pixel of a render, before it is turned in to
+/// something to display. Colors can be brighter than 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    /// Pixels stored row by row, starting at the top left.
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    /// Returns a black framebuffer.
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::empty(); width * height],
+        }
+    }
+
+    /// Returns a framebuffer holding the given pixels, row by row from the top
+    /// left. Panics if the number of pixels doesn't match the dimensions.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Framebuffer {
+        if pixels.len() != width * height {
+            panic!(
+                "Framebuffer of {}x{} can't hold {} pixels.",
+                width,
+                height,
+                pixels.len()
+            );
+        }
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the pixel at column `x` and row `y`, where row 0 is the top.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// The linear colors as an image, for high dynamic range formats.
+    pub fn to_image(&self) -> Image {
+        Image::new(self.width, self.height, self.pixels.clone())
+    }
+
+    /// The tone mapped, sRGB encoded colors as an image, for 8-bit formats.
+    pub fn to_display_image(&self, tone_mapping: &ToneMapping) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|pixel| tone_mapping.apply(pixel))
+            .collect();
+        Image::new(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn keeps_colors_brighter_than_one() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.set_pixel(1, 0, Color::new(4.0, 0.25, 0.0));
+        assert_relative_eq!(
+            Color::new(4.0, 0.25, 0.0),
+            framebuffer.to_image().pixel(1, 0)
+        );
+        let display = framebuffer.to_display_image(&ToneMapping::default());
+        assert_relative_eq!(
+            Color::new(1.0, 0.537, 0.0),
+            display.pixel(1, 0),
+            epsilon = 1e-3
+        );
+        assert_relative_eq!(Color::empty(), framebuffer.pixel(0, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cant_hold_wrong_number_of_pixels() {
+        Framebuffer::from_pixels(2, 2, vec![Color::empty(); 3]);
+    }
+}
@@ -0,0 +1,293 @@
+//! A small deflate compressor, enough to write PNG files without pulling in a
+//! compression library. It finds repeated strings with a hash chain, and codes
+//! them with the fixed Huffman codes of the format, so no code tables have to
+//! be built or stored.
+
+/// Deflate can refer back this far.
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash to try, trading speed for
+/// size.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: usize = 15;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Collects bits, least significant first, as deflate wants them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: vec![],
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Writes the lowest `count` bits of `value`.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+
+    /// Writes a literal byte, or a length code, with the fixed Huffman code.
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASES
+            .iter()
+            .rposition(|&base| usize::from(base) <= length)
+            .unwrap();
+        self.write_symbol(257 + index as u16);
+        self.write_bits(
+            (length - usize::from(LENGTH_BASES[index])) as u32,
+            u32::from(LENGTH_EXTRA_BITS[index]),
+        );
+
+        let index = DISTANCE_BASES
+            .iter()
+            .rposition(|&base| usize::from(base) <= distance)
+            .unwrap();
+        self.write_code(index as u32, 5);
+        self.write_bits(
+            (distance - usize::from(DISTANCE_BASES[index])) as u32,
+            u32::from(DISTANCE_EXTRA_BITS[index]),
+        );
+    }
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// The Adler-32 checksum that ends a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Compresses data in to a zlib stream.
+pub(super) fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // A final block, using the fixed Huffman codes.
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    // Remembers the string starting at a position, so later ones can refer
+    // back to it.
+    let insert = |position: usize, head: &mut [usize], previous: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(&data[position..]);
+            previous[position] = head[hash];
+            head[hash] = position;
+        }
+    };
+
+    let mut position = 0;
+    while position < data.len() {
+        // Find the longest earlier match for the string starting here.
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(&data[position..])];
+            let mut chain = 0;
+            while candidate != usize::MAX
+                && position - candidate <= WINDOW_SIZE
+                && chain < MAX_CHAIN
+            {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            writer.write_match(best_length, best_distance);
+            for skipped in position..position + best_length {
+                insert(skipped, &mut head, &mut previous);
+            }
+            position += best_length;
+        } else {
+            writer.write_symbol(u16::from(data[position]));
+            insert(position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+    writer.write_symbol(256);
+
+    // Header for deflate with a 32K window and default compression, which
+    // makes the first two bytes a multiple of 31.
+    let mut stream = vec![0x78, 0x9c];
+    stream.extend(writer.finish());
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+/// Decompresses a zlib stream made by `zlib_compress`, which only uses blocks
+/// with fixed Huffman codes.
+#[cfg(test)]
+pub(super) fn zlib_decompress(stream: &[u8]) -> Vec<u8> {
+    let mut bit = 16;
+    let mut read_bits = |count: usize| -> usize {
+        let mut value = 0;
+        for i in 0..count {
+            value |= usize::from(stream[bit / 8] >> (bit % 8) & 1) << i;
+            bit += 1;
+        }
+        value
+    };
+
+    let mut output: Vec<u8> = vec![];
+    loop {
+        let last = read_bits(1) == 1;
+        assert_eq!(1, read_bits(2), "Only fixed Huffman blocks are supported");
+        loop {
+            // Read a code most significant bit first, until it is complete.
+            let mut code = 0;
+            let mut length = 0;
+            let symbol = loop {
+                code = code << 1 | read_bits(1);
+                length += 1;
+                match (length, code) {
+                    (7, 0..=23) => break 256 + code,
+                    (8, 0x30..=0xbf) => break code - 0x30,
+                    (8, 0xc0..=0xc7) => break 280 + code - 0xc0,
+                    (9, 0x190..=0x1ff) => break 144 + code - 0x190,
+                    _ => assert!(length < 9, "Invalid code"),
+                }
+            };
+            match symbol {
+                0..=255 => output.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let index = symbol - 257;
+                    let length = usize::from(LENGTH_BASES[index])
+                        + read_bits(usize::from(LENGTH_EXTRA_BITS[index]));
+                    let mut index = 0;
+                    for _ in 0..5 {
+                        index = index << 1 | read_bits(1);
+                    }
+                    let distance = usize::from(DISTANCE_BASES[index])
+                        + read_bits(usize::from(DISTANCE_EXTRA_BITS[index]));
+                    for _ in 0..length {
+                        output.push(output[output.len() - distance]);
+                    }
+                }
+            }
+        }
+        if last {
+            break;
+        }
+    }
+
+    let checksum_start = bit.div_ceil(8);
+    let checksum = &stream[checksum_start..checksum_start + 4];
+    assert_eq!(adler32(&output).to_be_bytes(), checksum);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adler32_matches_known_value() {
+        assert_eq!(0x11e60398, adler32(b"Wikipedia"));
+        assert_eq!(1, adler32(b""));
+    }
+
+    #[test]
+    fn can_round_trip_data() {
+        let repetitive: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let noisy: Vec<u8> = (0u32..5000)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        for data in [
+            vec![],
+            vec![7],
+            b"abcabcabcabcabcd".to_vec(),
+            repetitive,
+            noisy,
+        ] {
+            let compressed = zlib_compress(&data);
+            assert_eq!(0, u16::from_be_bytes([compressed[0], compressed[1]]) % 31);
+            assert_eq!(data, zlib_decompress(&compressed));
+        }
+    }
+
+    #[test]
+    fn compresses_repetitive_data() {
+        let data = vec![42; 100_000];
+        assert!(zlib_compress(&data).len() < 1000);
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, Write};
+
+use super::{Image, ImageWriter};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Format version 2, for a single-part scanline file with short names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+/// The pixel type of 32-bit floats.
+const PIXEL_TYPE_FLOAT: i32 = 2;
+/// Channels are stored in alphabetical order of their names.
+const CHANNELS: [(&str, usize); 3] = [("B", 2), ("G", 1), ("R", 0)];
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// Writes OpenEXR images: a single part of uncompressed scanlines, with 32-bit
+/// float red, green and blue channels. This is a small part of what the
+/// format allows, but any OpenEXR reader can read it.
+pub struct ExrWriter;
+
+impl ImageWriter for ExrWriter {
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "OpenEXR images can't be empty",
+            ));
+        }
+
+        let mut header = vec![];
+        header.extend(MAGIC);
+        header.extend(VERSION);
+
+        let mut channels = vec![];
+        for (name, _) in CHANNELS {
+            channels.extend(name.as_bytes());
+            channels.push(0);
+            channels.extend(PIXEL_TYPE_FLOAT.to_le_bytes());
+            // Not perceptually linear, three reserved bytes, and no
+            // subsampling.
+            channels.extend([0, 0, 0, 0]);
+            channels.extend(1i32.to_le_bytes());
+            channels.extend(1i32.to_le_bytes());
+        }
+        channels.push(0);
+        write_attribute(&mut header, "channels", "chlist", &channels);
+        write_attribute(&mut header, "compression", "compression", &[0]);
+
+        let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        write_attribute(&mut header, "dataWindow", "box2i", &window);
+        write_attribute(&mut header, "displayWindow", "box2i", &window);
+        // Scanlines from the top down.
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+        output.write_all(&header)?;
+
+        // Every scanline is its own chunk, and a table of where each one
+        // starts in the file comes first.
+        let line_size = width * CHANNELS.len() * 4;
+        let chunk_size = 8 + line_size;
+        let table_size = height * 8;
+        let table: Vec<u8> = (0..height)
+            .flat_map(|y| ((header.len() + table_size + y * chunk_size) as u64).to_le_bytes())
+            .collect();
+        output.write_all(&table)?;
+
+        for y in 0..height {
+            let mut chunk = Vec::with_capacity(chunk_size);
+            chunk.extend((y as i32).to_le_bytes());
+            chunk.extend((line_size as i32).to_le_bytes());
+            for (_, channel) in CHANNELS {
+                for x in 0..width {
+                    chunk.extend((image.pixel(x, y)[channel] as f32).to_le_bytes());
+                }
+            }
+            output.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::TryInto};
+
+    use approx::assert_relative_eq;
+
+    use crate::util::color::Color;
+
+    use super::*;
+
+    fn read_i32(data: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    fn read_f32(data: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    fn read_string(data: &[u8], at: &mut usize) -> String {
+        let end = *at + data[*at..].iter().position(|&byte| byte == 0).unwrap();
+        let text = String::from_utf8(data[*at..end].to_vec()).unwrap();
+        *at = end + 1;
+        text
+    }
+
+    /// Reads back what `ExrWriter` writes, checking the structure of the file
+    /// along the way.
+    fn read_exr(data: &[u8]) -> (HashMap<String, (String, Vec<u8>)>, Image) {
+        assert_eq!(MAGIC, data[..4]);
+        assert_eq!(VERSION, data[4..8]);
+        let mut at = 8;
+        let mut attributes = HashMap::new();
+        while data[at] != 0 {
+            let name = read_string(data, &mut at);
+            let kind = read_string(data, &mut at);
+            let size = read_i32(data, at) as usize;
+            attributes.insert(name, (kind, data[at + 4..at + 4 + size].to_vec()));
+            at += 4 + size;
+        }
+        at += 1;
+
+        let window = &attributes["dataWindow"].1;
+        let width = (read_i32(window, 8) + 1) as usize;
+        let height = (read_i32(window, 12) + 1) as usize;
+        let mut pixels = vec![Color::empty(); width * height];
+        for line in 0..height {
+            let offset =
+                u64::from_le_bytes(data[at + line * 8..at + line * 8 + 8].try_into().unwrap());
+            let chunk = &data[offset as usize..];
+            let y = read_i32(chunk, 0) as usize;
+            assert_eq!((width * 12) as i32, read_i32(chunk, 4));
+            for (index, (_, channel)) in CHANNELS.iter().enumerate() {
+                for x in 0..width {
+                    let value = read_f32(chunk, 8 + (index * width + x) * 4);
+                    pixels[y * width + x][*channel] = f64::from(value);
+                }
+            }
+        }
+        (attributes, Image::new(width, height, pixels))
+    }
+
+    #[test]
+    fn can_round_trip_exr() {
+        let pixels: Vec<Color> = (0..15)
+            .map(|i| Color::new(f64::from(i), 0.5 * f64::from(i), 1000.0 - f64::from(i)))
+            .collect();
+        let image = Image::new(5, 3, pixels);
+        let mut data = vec![];
+        ExrWriter.write_image(&image, &mut data).unwrap();
+        let (attributes, read) = read_exr(&data);
+
+        for name in [
+            "channels",
+            "compression",
+            "dataWindow",
+            "displayWindow",
+            "lineOrder",
+            "pixelAspectRatio",
+            "screenWindowCenter",
+            "screenWindowWidth",
+        ] {
+            assert!(attributes.contains_key(name), "{} is missing", name);
+        }
+        assert_eq!(
+            ("compression".to_owned(), vec![0]),
+            attributes["compression"]
+        );
+        assert_eq!(3 * 18 + 1, attributes["channels"].1.len());
+        for y in 0..3 {
+            for x in 0..5 {
+                assert_relative_eq!(image.pixel(x, y), read.pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn cant_write_empty_exr() {
+        let image = Image::new(0, 0, vec![]);
+        assert!(ExrWriter.write_image(&image, &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::{self, BufRead, Write};
+
+use crate::util::color::Color;
+
+use super::{Image, ImageWriter};
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Converts a shared-exponent RGBE pixel to a linear color.
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::empty();
+    }
+    let scale = 2f64.powi(i32::from(rgbe[3]) - (128 + 8));
+    Color::new(
+        f64::from(rgbe[0]) * scale,
+        f64::from(rgbe[1]) * scale,
+        f64::from(rgbe[2]) * scale,
+    )
+}
+
+/// Converts a linear color to a shared-exponent RGBE pixel. Negative channels
+/// are stored as 0.
+fn color_to_rgbe(color: &Color) -> [u8; 4] {
+    let channels = [color.x(), color.y(), color.z()].map(|channel| channel.max(0.0));
+    let brightest = channels[0].max(channels[1]).max(channels[2]);
+    if brightest < 1e-32 {
+        return [0; 4];
+    }
+    // The exponent that puts the brightest channel in [128, 256).
+    let mut exponent = brightest.log2().floor() as i32 + 1;
+    if brightest / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2f64.powi(exponent);
+    let [r, g, b] = channels.map(|channel| (channel * scale).min(255.0) as u8);
+    [r, g, b, (exponent + 128).clamp(0, 255) as u8]
+}
+
+/// Run-length encodes one channel of a scanline: runs of at least three equal
+/// bytes are stored as a count and a byte, others are copied as they are.
+fn encode_channel(values: &[u8], output: &mut Vec<u8>) {
+    const MIN_RUN: usize = 3;
+    let mut start = 0;
+    while start < values.len() {
+        // Find the next run that is worth encoding.
+        let mut run_start = start;
+        let mut run_length = 0;
+        while run_start < values.len() {
+            run_length = values[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&value| value == values[run_start])
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += 1;
+        }
+
+        // Copy what comes before it, at most 128 bytes at a time.
+        for literals in values[start..run_start].chunks(128) {
+            output.push(literals.len() as u8);
+            output.extend(literals);
+        }
+        if run_start < values.len() {
+            output.push(128 + run_length as u8);
+            output.push(values[run_start]);
+            start = run_start + run_length;
+        } else {
+            start = run_start;
+        }
+    }
+}
+
+/// Writes Radiance RGBE (`.hdr`) images, keeping the full range of the
+/// colors. Scanlines are run-length encoded where the format allows it.
+pub struct HdrWriter;
+
+impl ImageWriter for HdrWriter {
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        write!(
+            output,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            image.height(),
+            image.width()
+        )?;
+        let width = image.width();
+        for y in 0..image.height() {
+            let pixels: Vec<[u8; 4]> = (0..width)
+                .map(|x| color_to_rgbe(&image.pixel(x, y)))
+                .collect();
+            if !(8..32768).contains(&width) {
+                output.write_all(&pixels.concat())?;
+                continue;
+            }
+            let mut scanline = vec![2, 2, (width >> 8) as u8, (width & 0xff) as u8];
+            for channel in 0..4 {
+                let values: Vec<u8> = pixels.iter().map(|pixel| pixel[channel]).collect();
+                encode_channel(&values, &mut scanline);
+            }
+            output.write_all(&scanline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads one scanline of `width` RGBE pixels, which is either stored flat or
+/// run-length encoded one channel at a time.
+fn read_scanline(reader: &mut impl BufRead, width: usize) -> io::Result<Vec<[u8; 4]>> {
+    let mut first = [0; 4];
+    reader.read_exact(&mut first)?;
+
+    let encoded_width = usize::from(first[2]) << 8 | usize::from(first[3]);
+    let run_length_encoded =
+        (8..32768).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !run_length_encoded {
+        let mut pixels = vec![first];
+        let mut rest = vec![0; (width - 1) * 4];
+        reader.read_exact(&mut rest)?;
+        pixels.extend(rest.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]));
+        return Ok(pixels);
+    }
+    if encoded_width != width {
+        return Err(invalid_data(format!(
+            "HDR scanline is {} pixels wide, expected {}",
+            encoded_width, width
+        )));
+    }
+
+    let mut pixels = vec![[0; 4]; width];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut header = [0; 1];
+            reader.read_exact(&mut header)?;
+            let (count, run) = if header[0] > 128 {
+                (usize::from(header[0] - 128), true)
+            } else {
+                (usize::from(header[0]), false)
+            };
+            if count == 0 || x + count > width {
+                return Err(invalid_data(String::from("Bad HDR run length")));
+            }
+            if run {
+                let mut value = [0; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut pixels[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                let mut values = vec![0; count];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in pixels[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(pixels)
+}
+
+/// Reads a Radiance RGBE (`.hdr`) image, keeping the full range of the
+/// colors.
+///
+/// Only the common top-to-bottom, left-to-right (`-Y height +X width`)
+/// orientation is supported.
+pub fn read_hdr(reader: &mut impl BufRead) -> io::Result<Image> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data(String::from("Not a Radiance HDR file")));
+    }
+
+    // Header variables, up to an empty line.
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data(String::from("HDR header ended unexpectedly")));
+        }
+        let variable = line.trim();
+        if variable.is_empty() {
+            break;
+        }
+        if let Some(format) = variable.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("Unsupported HDR format {}", format)));
+            }
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let resolution: Vec<&str> = line.split_whitespace().collect();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => match (height.parse(), width.parse()) {
+            (Ok(height), Ok(width)) => (height, width),
+            _ => return Err(invalid_data(format!("Bad HDR resolution {:?}", line))),
+        },
+        _ => {
+            return Err(invalid_data(format!(
+                "Unsupported HDR orientation {:?}",
+                line.trim()
+            )))
+        }
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for _ in 0..height {
+        if width == 0 {
+            break;
+        }
+        pixels.extend(read_scanline(reader, width)?.into_iter().map(rgbe_to_color));
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n";
+
+    #[test]
+    fn can_read_flat_hdr() {
+        let mut data = HEADER.to_vec();
+        data.extend(b"-Y 1 +X 2\n");
+        data.extend([128, 64, 0, 129, 0, 0, 0, 0]);
+        let image = read_hdr(&mut &data[..]).unwrap();
+        assert_eq!(2, image.width());
+        assert_eq!(1, image.height());
+        assert_relative_eq!(Color::new(1.0, 0.5, 0.0), image.pixel(0, 0));
+        assert_relative_eq!(Color::empty(), image.pixel(1, 0));
+    }
+
+    #[test]
+    fn can_read_run_length_encoded_hdr() {
+        let mut data = HEADER.to_vec();
+        data.extend(b"-Y 1 +X 8\n");
+        data.extend([2, 2, 0, 8]);
+        // Red: a run of 8. Green: 8 literal values. Blue: two runs of 4.
+        data.extend([128 + 8, 64]);
+        data.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        data.extend([128 + 4, 0, 128 + 4, 128]);
+        // Exponent: a run of 8.
+        data.extend([128 + 8, 130]);
+        let image = read_hdr(&mut &data[..]).unwrap();
+        assert_eq!(8, image.width());
+        assert_relative_eq!(Color::new(1.0, 0.0, 0.0), image.pixel(0, 0));
+        assert_relative_eq!(Color::new(1.0, 1.75, 2.0), image.pixel(7, 0));
+    }
+
+    #[test]
+    fn cant_read_truncated_hdr() {
+        let mut data = HEADER.to_vec();
+        data.extend(b"-Y 2 +X 2\n");
+        data.extend([128, 64, 0, 129]);
+        assert!(read_hdr(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn can_round_trip_hdr() {
+        for width in [3, 40] {
+            let pixels: Vec<Color> = (0..width * 2)
+                .map(|i| match i % 5 {
+                    0 => Color::new(1000.0, 0.5, 0.001),
+                    1 => Color::empty(),
+                    2 => Color::new(-1.0, 1.0, 3.0),
+                    _ => Color::new(0.25, 0.25, f64::from(i as u32)),
+                })
+                .collect();
+            let image = Image::new(width, 2, pixels);
+            let mut data = vec![];
+            HdrWriter.write_image(&image, &mut data).unwrap();
+            let read = read_hdr(&mut &data[..]).unwrap();
+            assert_eq!(width, read.width());
+            assert_eq!(2, read.height());
+            for y in 0..2 {
+                for x in 0..width {
+                    let expected = image.pixel(x, y);
+                    let actual = read.pixel(x, y);
+                    // The mantissas are 8 bits, shared by the three channels.
+                    let brightest = expected.x().max(expected.y()).max(expected.z());
+                    for channel in 0..3 {
+                        let expected = expected[channel].max(0.0);
+                        assert!((expected - actual[channel]).abs() <= brightest / 128.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_length_encoding_shrinks_flat_scanlines() {
+        let image = Image::new(100, 1, vec![Color::new(0.5, 0.5, 0.5); 100]);
+        let mut data = vec![];
+        HdrWriter.write_image(&image, &mut data).unwrap();
+        let header_length = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 100\n".len();
+        assert_eq!(header_length + 4 + 4 * 2, data.len());
+    }
+
+    #[test]
+    fn encodes_rgbe_like_radiance() {
+        assert_eq!([128, 64, 0, 129], color_to_rgbe(&Color::new(1.0, 0.5, 0.0)));
+        assert_eq!(
+            [255, 0, 0, 128],
+            color_to_rgbe(&Color::new(0.999, 0.0, 0.0))
+        );
+        assert_eq!([0; 4], color_to_rgbe(&Color::empty()));
+    }
+
+    #[test]
+    fn cant_read_other_orientations() {
+        let mut data = HEADER.to_vec();
+        data.extend(b"+Y 1 +X 1\n");
+        data.extend([128, 64, 0, 129]);
+        assert!(read_hdr(&mut &data[..]).is_err());
+    }
+}
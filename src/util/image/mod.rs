@@ -0,0 +1,61 @@
+mod deflate;
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+mod ppm;
+mod quantize;
+mod writer;
+
+pub use exr::ExrWriter;
+pub use hdr::{read_hdr, HdrWriter};
+pub use pfm::{read_pfm, PfmWriter};
+pub use png::PngWriter;
+pub use ppm::{read_ppm, PpmWriter};
+pub use quantize::{Dither, Quantizer};
+pub use writer::{ImageFormat, ImageWriter};
+
+use super::color::Color;
+
+/// An image held in memory. Channels are between 0 and 1, except for high
+/// dynamic range images, where they can be any positive value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    /// Pixels stored row by row, starting at the top left.
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    /// Returns a new image. Panics if the number of pixels doesn't match the
+    /// dimensions.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Image {
+        if pixels.len() != width * height {
+            panic!(
+                "Image of {}x{} can't hold {} pixels.",
+                width,
+                height,
+                pixels.len()
+            );
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the pixel at column `x` and row `y`, where row 0 is the top.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{self, BufRead, Write};
+
+use crate::util::color::Color;
+
+use super::{Image, ImageWriter};
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a color Portable FloatMap (`.pfm`), in either byte order.
+pub fn read_pfm(reader: &mut impl BufRead) -> io::Result<Image> {
+    // The header is three lines: the type, the size, and the scale, whose
+    // sign gives the byte order.
+    let mut header = vec![];
+    for _ in 0..3 {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        header.push(line.trim().to_owned());
+    }
+    if header[0] != "PF" {
+        return Err(invalid_data(format!(
+            "Unsupported PFM type {:?}",
+            header[0]
+        )));
+    }
+    let size: Vec<usize> = header[1]
+        .split_whitespace()
+        .map(|number| number.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid_data(format!("Bad PFM size {:?}", header[1])))?;
+    let (width, height) = match size.as_slice() {
+        [width, height] => (*width, *height),
+        _ => return Err(invalid_data(format!("Bad PFM size {:?}", header[1]))),
+    };
+    let scale: f64 = header[2]
+        .parse()
+        .map_err(|_| invalid_data(format!("Bad PFM scale {:?}", header[2])))?;
+    let little_endian = scale < 0.0;
+
+    let mut data = vec![0; width * height * 3 * 4];
+    reader.read_exact(&mut data)?;
+    let samples: Vec<f64> = data
+        .chunks_exact(4)
+        .map(|bytes| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            f64::from(if little_endian {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            })
+        })
+        .collect();
+
+    // Rows are stored from the bottom up.
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in samples.chunks_exact(width * 3).rev() {
+        pixels.extend(
+            row.chunks_exact(3)
+                .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2])),
+        );
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+/// Writes color Portable FloatMaps (`.pfm`): uncompressed 32-bit floats, in
+/// little-endian byte order.
+pub struct PfmWriter;
+
+impl ImageWriter for PfmWriter {
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        write!(output, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+        for y in (0..image.height()).rev() {
+            let mut row = Vec::with_capacity(image.width() * 3 * 4);
+            for x in 0..image.width() {
+                let pixel = image.pixel(x, y);
+                for channel in 0..3 {
+                    row.extend((pixel[channel] as f32).to_le_bytes());
+                }
+            }
+            output.write_all(&row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn can_round_trip_pfm() {
+        let image = Image::new(
+            2,
+            2,
+            vec![
+                Color::new(1.0, 2.0, 3.0),
+                Color::new(1000.5, 0.0, -0.25),
+                Color::new(0.125, 0.5, 0.0),
+                Color::new(65536.0, 1e-6, 7.0),
+            ],
+        );
+        let mut data = vec![];
+        PfmWriter.write_image(&image, &mut data).unwrap();
+        assert!(data.starts_with(b"PF\n2 2\n-1.0\n"));
+        let read = read_pfm(&mut &data[..]).unwrap();
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_relative_eq!(image.pixel(x, y), read.pixel(x, y), max_relative = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn can_read_big_endian_pfm() {
+        let mut data = b"PF\n1 2\n1.0\n".to_vec();
+        // The bottom row comes first.
+        for value in [0.0f32, 0.0, 1.0, 2.0, 0.5, 0.25] {
+            data.extend(value.to_be_bytes());
+        }
+        let image = read_pfm(&mut &data[..]).unwrap();
+        assert_relative_eq!(Color::new(2.0, 0.5, 0.25), image.pixel(0, 0));
+        assert_relative_eq!(Color::new(0.0, 0.0, 1.0), image.pixel(0, 1));
+    }
+
+    #[test]
+    fn cant_read_grayscale_or_truncated_pfm() {
+        assert!(read_pfm(&mut &b"Pf\n1 1\n-1.0\n\0\0\0\0"[..]).is_err());
+        assert!(read_pfm(&mut &b"PF\n1 1\n-1.0\n\0\0\0\0"[..]).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::{self, Write};
+
+use super::{deflate, Image, ImageWriter, Quantizer};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Bytes per pixel: 8-bit red, green and blue.
+const BYTES_PER_PIXEL: usize = 3;
+
+/// The CRC-32 that ends every chunk, over its type and data.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn write_chunk(output: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut checked = kind.to_vec();
+    checked.extend(data);
+    output.write_all(&checked)?;
+    output.write_all(&crc32(&checked).to_be_bytes())
+}
+
+/// The Paeth predictor of the PNG format: whichever of the left, above and
+/// upper left neighbours is closest to `left + above - upper_left`.
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = i16::from(left) + i16::from(above) - i16::from(upper_left);
+    let left_distance = (estimate - i16::from(left)).abs();
+    let above_distance = (estimate - i16::from(above)).abs();
+    let upper_left_distance = (estimate - i16::from(upper_left)).abs();
+    if left_distance <= above_distance && left_distance <= upper_left_distance {
+        left
+    } else if above_distance <= upper_left_distance {
+        above
+    } else {
+        upper_left
+    }
+}
+
+/// Filters a row with each of the five PNG filters, and keeps the one that
+/// is likely to compress best: the one with the smallest sum of differences.
+fn filter_row(row: &[u8], previous: &[u8]) -> Vec<u8> {
+    let left = |i: usize| {
+        if i < BYTES_PER_PIXEL {
+            0
+        } else {
+            row[i - BYTES_PER_PIXEL]
+        }
+    };
+    let upper_left = |i: usize| {
+        if i < BYTES_PER_PIXEL {
+            0
+        } else {
+            previous[i - BYTES_PER_PIXEL]
+        }
+    };
+
+    (0u8..5)
+        .map(|filter| {
+            let mut filtered = vec![filter];
+            filtered.extend(row.iter().enumerate().map(|(i, &value)| {
+                let prediction = match filter {
+                    0 => 0,
+                    1 => left(i),
+                    2 => previous[i],
+                    3 => ((u16::from(left(i)) + u16::from(previous[i])) / 2) as u8,
+                    _ => paeth(left(i), previous[i], upper_left(i)),
+                };
+                value.wrapping_sub(prediction)
+            }));
+            filtered
+        })
+        .min_by_key(|filtered| {
+            filtered[1..]
+                .iter()
+                .map(|&value| u32::from((value as i8).unsigned_abs()))
+                .sum::<u32>()
+        })
+        .unwrap()
+}
+
+/// Writes 8-bit RGB PNG images.
+pub struct PngWriter {
+    quantizer: Quantizer,
+}
+
+impl PngWriter {
+    pub fn new(quantizer: Quantizer) -> PngWriter {
+        PngWriter { quantizer }
+    }
+}
+
+impl ImageWriter for PngWriter {
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        output.write_all(&SIGNATURE)?;
+
+        let mut header = vec![];
+        header.extend((image.width() as u32).to_be_bytes());
+        header.extend((image.height() as u32).to_be_bytes());
+        // Bit depth 8, truecolor, deflate, adaptive filtering, no interlacing.
+        header.extend([8, 2, 0, 0, 0]);
+        write_chunk(output, b"IHDR", &header)?;
+
+        let row_length = image.width() * BYTES_PER_PIXEL;
+        let mut previous = vec![0; row_length];
+        let mut filtered = Vec::with_capacity((row_length + 1) * image.height());
+        for y in 0..image.height() {
+            let row: Vec<u8> = (0..image.width())
+                .flat_map(|x| self.quantizer.quantize_color(&image.pixel(x, y), x, y))
+                .collect();
+            filtered.extend(filter_row(&row, &previous));
+            previous = row;
+        }
+        write_chunk(output, b"IDAT", &deflate::zlib_compress(&filtered))?;
+        write_chunk(output, b"IEND", &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::color::Color;
+
+    use super::*;
+
+    /// Splits a PNG file in to its chunks, checking their CRCs.
+    fn chunks(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert_eq!(SIGNATURE, data[..8]);
+        let mut chunks = vec![];
+        let mut rest = &data[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let checked = &rest[4..8 + length];
+            let crc = &rest[8 + length..12 + length];
+            assert_eq!(crc32(checked).to_be_bytes(), crc);
+            chunks.push((
+                String::from_utf8(checked[..4].to_vec()).unwrap(),
+                checked[4..].to_vec(),
+            ));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    /// Undoes the filters of decompressed image data.
+    fn unfilter(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let row_length = width * BYTES_PER_PIXEL;
+        let mut pixels: Vec<u8> = vec![];
+        for y in 0..height {
+            let filter = data[y * (row_length + 1)];
+            let row = &data[y * (row_length + 1) + 1..(y + 1) * (row_length + 1)];
+            for (i, &value) in row.iter().enumerate() {
+                let start = y * row_length;
+                let left = if i < BYTES_PER_PIXEL {
+                    0
+                } else {
+                    pixels[start + i - BYTES_PER_PIXEL]
+                };
+                let above = if y == 0 {
+                    0
+                } else {
+                    pixels[start + i - row_length]
+                };
+                let upper_left = if y == 0 || i < BYTES_PER_PIXEL {
+                    0
+                } else {
+                    pixels[start + i - row_length - BYTES_PER_PIXEL]
+                };
+                let prediction = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => above,
+                    3 => ((u16::from(left) + u16::from(above)) / 2) as u8,
+                    4 => paeth(left, above, upper_left),
+                    _ => panic!("Unknown filter {}", filter),
+                };
+                pixels.push(value.wrapping_add(prediction));
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn crc32_matches_known_value() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn can_round_trip_png() {
+        let (width, height) = (7, 5);
+        let pixels: Vec<Color> = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64, (i / width) as f64);
+                Color::new(
+                    x / 7.0,
+                    y / 5.0,
+                    if (x + y) % 2.0 == 0.0 { 1.0 } else { 0.2 },
+                )
+            })
+            .collect();
+        let image = Image::new(width, height, pixels);
+
+        let mut data = vec![];
+        let quantizer = Quantizer::default();
+        PngWriter::new(quantizer)
+            .write_image(&image, &mut data)
+            .unwrap();
+        let chunks = chunks(&data);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(vec!["IHDR", "IDAT", "IEND"], kinds);
+        assert_eq!(vec![0, 0, 0, 7, 0, 0, 0, 5, 8, 2, 0, 0, 0], chunks[0].1);
+
+        let filtered = deflate::zlib_decompress(&chunks[1].1);
+        let decoded = unfilter(&filtered, width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let start = (y * width + x) * BYTES_PER_PIXEL;
+                assert_eq!(
+                    quantizer.quantize_color(&image.pixel(x, y), x, y),
+                    decoded[start..start + BYTES_PER_PIXEL]
+                );
+            }
+        }
+    }
+}
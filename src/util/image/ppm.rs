@@ -0,0 +1,281 @@
+use std::io::{self, BufRead, Write};
+
+use crate::util::color::Color;
+
+use super::{Image, ImageWriter, Quantizer};
+
+/// Writes 8-bit portable pixmaps, either binary (P6) or ASCII (P3).
+pub struct PpmWriter {
+    binary: bool,
+    quantizer: Quantizer,
+}
+
+impl PpmWriter {
+    /// A writer for binary (P6) files, which are a third of the size.
+    pub fn binary(quantizer: Quantizer) -> PpmWriter {
+        PpmWriter {
+            binary: true,
+            quantizer,
+        }
+    }
+
+    /// A writer for ASCII (P3) files, which can be read by people.
+    pub fn ascii(quantizer: Quantizer) -> PpmWriter {
+        PpmWriter {
+            binary: false,
+            quantizer,
+        }
+    }
+}
+
+impl ImageWriter for PpmWriter {
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()> {
+        let magic = if self.binary { "P6" } else { "P3" };
+        writeln!(
+            output,
+            "{}\n{} {}\n255",
+            magic,
+            image.width(),
+            image.height()
+        )?;
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                let bytes = self.quantizer.quantize_color(&image.pixel(x, y), x, y);
+                if self.binary {
+                    output.write_all(&bytes)?;
+                } else {
+                    writeln!(output, "{} {} {}", bytes[0], bytes[1], bytes[2])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a binary (P6) or ASCII (P3) portable pixmap.
+pub fn read_ppm(reader: &mut impl BufRead) -> io::Result<Image> {
+    let magic = read_token(reader)?;
+    let binary = match magic.as_str() {
+        "P6" => true,
+        "P3" => false,
+        _ => return Err(invalid_data(format!("Unsupported PPM type {:?}", magic))),
+    };
+    let width = read_number(reader)?;
+    let height = read_number(reader)?;
+    let max_value = read_number(reader)?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data(format!(
+            "Invalid PPM maximum value {}",
+            max_value
+        )));
+    }
+
+    let sample_count = width * height * 3;
+    let samples: Vec<usize> = if binary {
+        // Exactly one whitespace character separates the header from the data.
+        let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+        let mut data = vec![0; sample_count * bytes_per_sample];
+        reader.read_exact(&mut data)?;
+        if bytes_per_sample == 1 {
+            data.into_iter().map(usize::from).collect()
+        } else {
+            data.chunks_exact(2)
+                .map(|pair| usize::from(u16::from_be_bytes([pair[0], pair[1]])))
+                .collect()
+        }
+    } else {
+        (0..sample_count)
+            .map(|_| read_number(reader))
+            .collect::<io::Result<_>>()?
+    };
+
+    if let Some(sample) = samples.iter().find(|&&sample| sample > max_value) {
+        return Err(invalid_data(format!(
+            "PPM sample {} is larger than the maximum value {}",
+            sample, max_value
+        )));
+    }
+
+    let scale = 1.0 / max_value as f64;
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|rgb| {
+            Color::new(
+                rgb[0] as f64 * scale,
+                rgb[1] as f64 * scale,
+                rgb[2] as f64 * scale,
+            )
+        })
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_byte(reader: &mut impl BufRead) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match reader.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads one whitespace separated token, skipping `#` comments. Consumes the
+/// single whitespace character following the token.
+fn read_token(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut token = String::new();
+    while let Some(byte) = read_byte(reader)? {
+        if byte == b'#' {
+            let mut comment = vec![];
+            reader.read_until(b'\n', &mut comment)?;
+            if !token.is_empty() {
+                break;
+            }
+        } else if byte.is_ascii_whitespace() {
+            if !token.is_empty() {
+                break;
+            }
+        } else {
+            token.push(byte as char);
+        }
+    }
+    if token.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "PPM ended unexpectedly",
+        ));
+    }
+    Ok(token)
+}
+
+fn read_number(reader: &mut impl BufRead) -> io::Result<usize> {
+    let token = read_token(reader)?;
+    token
+        .parse()
+        .map_err(|_| invalid_data(format!("Expected a number in PPM, found {:?}", token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::util::image::Dither;
+
+    use super::*;
+
+    #[test]
+    fn can_read_ascii_ppm() {
+        let data = b"P3\n# a comment\n2 1\n255\n255 0 0  0 0 51\n";
+        let image = read_ppm(&mut &data[..]).unwrap();
+        assert_eq!(2, image.width());
+        assert_eq!(1, image.height());
+        assert_relative_eq!(Color::new(1.0, 0.0, 0.0), image.pixel(0, 0));
+        assert_relative_eq!(Color::new(0.0, 0.0, 0.2), image.pixel(1, 0));
+    }
+
+    #[test]
+    fn can_read_binary_ppm() {
+        let mut data = b"P6 1 2 255\n".to_vec();
+        data.extend([0, 255, 0, 10, 20, 30]);
+        let image = read_ppm(&mut &data[..]).unwrap();
+        assert_eq!(1, image.width());
+        assert_eq!(2, image.height());
+        assert_relative_eq!(Color::new(0.0, 1.0, 0.0), image.pixel(0, 0));
+        assert_relative_eq!(
+            Color::new(10.0 / 255.0, 20.0 / 255.0, 30.0 / 255.0),
+            image.pixel(0, 1)
+        );
+    }
+
+    #[test]
+    fn cant_read_truncated_ppm() {
+        let data = b"P6 2 2 255\n\x00\x00\x00";
+        assert!(read_ppm(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn can_write_binary_and_ascii_ppm() {
+        let image = Image::new(
+            2,
+            1,
+            vec![Color::new(1.0, 0.0, 0.5), Color::new(0.25, 0.0, 0.0)],
+        );
+        let mut binary = vec![];
+        PpmWriter::binary(Quantizer::default())
+            .write_image(&image, &mut binary)
+            .unwrap();
+        assert_eq!(b"P6\n2 1\n255\n\xff\x00\x80\x40\x00\x00".to_vec(), binary);
+
+        let mut ascii = vec![];
+        PpmWriter::ascii(Quantizer::default())
+            .write_image(&image, &mut ascii)
+            .unwrap();
+        assert_eq!(
+            "P3\n2 1\n255\n255 0 128\n64 0 0\n",
+            String::from_utf8(ascii).unwrap()
+        );
+    }
+
+    /// A smooth gradient, with some colors outside of 0 to 1.
+    fn gradient() -> Image {
+        let (width, height) = (37, 11);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64, (i / width) as f64);
+                Color::new(x / 36.0, y / 10.0, 1.2 - x / 30.0)
+            })
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    #[test]
+    fn can_round_trip_ppm() {
+        let image = gradient();
+        for dither in [Dither::None, Dither::Ordered, Dither::BlueNoise] {
+            let quantizer = Quantizer::new(dither);
+            for writer in [PpmWriter::binary(quantizer), PpmWriter::ascii(quantizer)] {
+                let mut data = vec![];
+                writer.write_image(&image, &mut data).unwrap();
+                let read = read_ppm(&mut &data[..]).unwrap();
+                assert_eq!(image.width(), read.width());
+                assert_eq!(image.height(), read.height());
+                for y in 0..image.height() {
+                    for x in 0..image.width() {
+                        let expected = image.pixel(x, y);
+                        let actual = read.pixel(x, y);
+                        for channel in 0..3 {
+                            let expected = num::clamp(expected[channel], 0.0, 1.0);
+                            // Rounding is off by at most half a step, and
+                            // dithering by at most a whole one.
+                            let tolerance = match dither {
+                                Dither::None => 0.5,
+                                _ => 1.0,
+                            } / 255.0;
+                            assert!((expected - actual[channel]).abs() <= tolerance + 1e-9);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn written_ppm_has_integer_channels() {
+        let mut data = vec![];
+        PpmWriter::ascii(Quantizer::default())
+            .write_image(&gradient(), &mut data)
+            .unwrap();
+        let text = String::from_utf8(data).unwrap();
+        let samples: Vec<&str> = text.split_whitespace().skip(4).collect();
+        assert_eq!(37 * 11 * 3, samples.len());
+        assert!(samples.iter().all(|sample| sample.parse::<u8>().is_ok()));
+    }
+
+    #[test]
+    fn cant_read_other_formats() {
+        let data = b"P5 1 1 255\n\x00";
+        assert!(read_ppm(&mut &data[..]).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::sync::OnceLock;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::util::color::Color;
+
+/// How to hide the banding that comes from rounding smooth gradients to 8
+/// bits.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Round every channel to the nearest value.
+    #[default]
+    None,
+    /// Add a regular 8x8 Bayer pattern before rounding.
+    Ordered,
+    /// Add a blue noise pattern before rounding, which has no visible
+    /// structure.
+    BlueNoise,
+}
+
+/// Turns channels between 0 and 1 in to 8-bit values.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quantizer {
+    dither: Dither,
+}
+
+impl Quantizer {
+    pub fn new(dither: Dither) -> Quantizer {
+        Quantizer { dither }
+    }
+
+    /// Quantizes one channel of the pixel at column `x` and row `y`. Values
+    /// outside of 0 to 1 are clamped.
+    pub fn quantize(&self, value: f64, x: usize, y: usize) -> u8 {
+        let offset = match self.dither {
+            Dither::None => 0.0,
+            Dither::Ordered => threshold(BAYER_SIZE, &BAYER[..], x, y),
+            Dither::BlueNoise => threshold(BLUE_NOISE_SIZE, blue_noise(), x, y),
+        };
+        (num::clamp(value, 0.0, 1.0) * 255.0 + offset)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    }
+
+    /// Quantizes the red, green and blue channels of a pixel.
+    pub fn quantize_color(&self, color: &Color, x: usize, y: usize) -> [u8; 3] {
+        [
+            self.quantize(color.x(), x, y),
+            self.quantize(color.y(), x, y),
+            self.quantize(color.z(), x, y),
+        ]
+    }
+}
+
+/// Turns the rank of a pixel in a dither pattern in to an offset between
+/// -0.5 and 0.5, which averages out to 0.
+fn threshold(size: usize, ranks: &[u16], x: usize, y: usize) -> f64 {
+    let rank = ranks[(y % size) * size + x % size];
+    (f64::from(rank) + 0.5) / (size * size) as f64 - 0.5
+}
+
+const BAYER_SIZE: usize = 8;
+#[rustfmt::skip]
+const BAYER: [u16; BAYER_SIZE * BAYER_SIZE] = [
+     0, 32,  8, 40,  2, 34, 10, 42,
+    48, 16, 56, 24, 50, 18, 58, 26,
+    12, 44,  4, 36, 14, 46,  6, 38,
+    60, 28, 52, 20, 62, 30, 54, 22,
+     3, 35, 11, 43,  1, 33,  9, 41,
+    51, 19, 59, 27, 49, 17, 57, 25,
+    15, 47,  7, 39, 13, 45,  5, 37,
+    63, 31, 55, 23, 61, 29, 53, 21,
+];
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// The blue noise pattern, made the first time it is needed.
+fn blue_noise() -> &'static [u16] {
+    static PATTERN: OnceLock<Vec<u16>> = OnceLock::new();
+    PATTERN.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE))
+}
+
+/// Keeps track of which pixels of a tiling pattern are set, and how crowded
+/// every pixel is by the set pixels around it.
+struct Energy {
+    size: usize,
+    /// A Gaussian of the distance, for every offset, wrapping around.
+    kernel: Vec<f64>,
+    set: Vec<bool>,
+    energy: Vec<f64>,
+}
+
+impl Energy {
+    fn new(size: usize) -> Energy {
+        let sigma: f64 = 1.5;
+        let kernel = (0..size * size)
+            .map(|index| {
+                let wrapped = |d: usize| d.min(size - d) as f64;
+                let (dx, dy) = (wrapped(index % size), wrapped(index / size));
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        Energy {
+            size,
+            kernel,
+            set: vec![false; size * size],
+            energy: vec![0.0; size * size],
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        self.set[index] = !self.set[index];
+        let sign = if self.set[index] { 1.0 } else { -1.0 };
+        let (x, y) = (index % self.size, index / self.size);
+        for (other, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (other % self.size + self.size - x) % self.size;
+            let dy = (other / self.size + self.size - y) % self.size;
+            *energy += sign * self.kernel[dy * self.size + dx];
+        }
+    }
+
+    /// The set pixel with the most set pixels around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The unset pixel with the fewest set pixels around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, set: bool, better: fn(f64, f64) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for index in (0..self.set.len()).filter(|&index| self.set[index] == set) {
+            if best.is_none_or(|best| better(self.energy[index], self.energy[best])) {
+                best = Some(index);
+            }
+        }
+        best.expect("no pixel to choose from")
+    }
+}
+
+/// Makes a tiling blue noise pattern with the void-and-cluster method, giving
+/// every pixel a distinct rank.
+fn void_and_cluster(size: usize) -> Vec<u16> {
+    let count = size * size;
+    let mut pattern = Energy::new(size);
+
+    // Start with a tenth of the pixels set at random, and even them out until
+    // the tightest cluster is also the largest void.
+    let mut generator = StdRng::seed_from_u64(0x0b1e_0015e);
+    let initial = count / 10;
+    while pattern.set.iter().filter(|&&set| set).count() < initial {
+        let index = generator.gen_range(0..count);
+        if !pattern.set[index] {
+            pattern.toggle(index);
+        }
+    }
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+    let prototype = pattern.set.clone();
+
+    let mut ranks = vec![0; count];
+    // Ranks below the prototype: take away the tightest clusters.
+    for rank in (0..initial).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank as u16;
+    }
+    // Ranks above it: fill the largest voids.
+    for (index, &set) in prototype.iter().enumerate() {
+        if set != pattern.set[index] {
+            pattern.toggle(index);
+        }
+    }
+    for rank in initial..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank as u16;
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_to_nearest_value() {
+        let quantizer = Quantizer::default();
+        assert_eq!(0, quantizer.quantize(-1.0, 0, 0));
+        assert_eq!(0, quantizer.quantize(0.0, 0, 0));
+        assert_eq!(1, quantizer.quantize(0.7 / 255.0, 0, 0));
+        assert_eq!(128, quantizer.quantize(0.5, 0, 0));
+        assert_eq!(255, quantizer.quantize(0.999, 0, 0));
+        assert_eq!(255, quantizer.quantize(7.0, 0, 0));
+    }
+
+    #[test]
+    fn patterns_rank_every_pixel_once() {
+        for (size, ranks) in [(BAYER_SIZE, &BAYER[..]), (BLUE_NOISE_SIZE, blue_noise())] {
+            let mut sorted = ranks.to_vec();
+            sorted.sort_unstable();
+            let expected: Vec<u16> = (0..(size * size) as u16).collect();
+            assert_eq!(expected, sorted);
+        }
+    }
+
+    #[test]
+    fn dithering_keeps_the_average_of_flat_areas() {
+        let value = 0.3;
+        for dither in [Dither::Ordered, Dither::BlueNoise] {
+            let quantizer = Quantizer::new(dither);
+            let total: u32 = (0..BLUE_NOISE_SIZE)
+                .flat_map(|y| (0..BLUE_NOISE_SIZE).map(move |x| (x, y)))
+                .map(|(x, y)| u32::from(quantizer.quantize(value, x, y)))
+                .sum();
+            let average = f64::from(total) / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f64;
+            assert!((average - value * 255.0).abs() < 0.05, "{:?}", dither);
+        }
+        // Without dithering the whole area rounds the same way.
+        assert_eq!(77, Quantizer::default().quantize(value, 0, 0));
+    }
+
+    #[test]
+    fn dithering_stays_within_one_step() {
+        for dither in [Dither::Ordered, Dither::BlueNoise] {
+            let quantizer = Quantizer::new(dither);
+            for (x, y) in [(0, 0), (3, 5), (63, 63), (100, 7)] {
+                let quantized = quantizer.quantize(0.5, x, y);
+                assert!((127..=128).contains(&quantized));
+                assert_eq!(0, quantizer.quantize(0.0, x, y));
+                assert_eq!(255, quantizer.quantize(1.0, x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn blue_noise_has_no_clumps() {
+        // The darkest tenth of the pattern should be spread out, with no two
+        // of its pixels next to each other.
+        let ranks = blue_noise();
+        let limit = (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE / 10) as u16;
+        let at = |x: usize, y: usize| {
+            ranks[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE]
+        };
+        for y in 0..BLUE_NOISE_SIZE {
+            for x in 0..BLUE_NOISE_SIZE {
+                if at(x, y) < limit {
+                    assert!(at(x + 1, y) >= limit && at(x, y + 1) >= limit);
+                }
+            }
+        }
+    }
+}
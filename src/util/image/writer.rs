@@ -0,0 +1,94 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use super::{ExrWriter, HdrWriter, Image, PfmWriter, PngWriter, PpmWriter, Quantizer};
+
+/// Something that can write an image in some file format.
+pub trait ImageWriter {
+    /// Writes the image. For 8-bit formats, channels are expected to be
+    /// display values between 0 and 1, already gamma corrected. High dynamic
+    /// range formats store linear values as they are.
+    fn write_image(&self, image: &Image, output: &mut dyn Write) -> io::Result<()>;
+}
+
+/// The file formats images can be written in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Compressed 8-bit PNG.
+    Png,
+    /// Binary (P6) portable pixmap.
+    BinaryPpm,
+    /// ASCII (P3) portable pixmap.
+    AsciiPpm,
+    /// Radiance RGBE, high dynamic range.
+    Hdr,
+    /// Portable FloatMap, 32-bit floats.
+    Pfm,
+    /// OpenEXR, 32-bit floats.
+    Exr,
+}
+
+impl ImageFormat {
+    /// Picks the format from the extension of a file: `.png`, `.ppm` (for
+    /// binary PPM), `.hdr`, `.pfm` or `.exr`. Returns `None` for other
+    /// extensions.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::BinaryPpm),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// Whether the format stores linear colors of any brightness, rather than
+    /// display values between 0 and 1.
+    pub fn is_high_dynamic_range(&self) -> bool {
+        matches!(self, ImageFormat::Hdr | ImageFormat::Pfm | ImageFormat::Exr)
+    }
+
+    /// Returns a writer for the format. 8-bit formats turn colors in to 8-bit
+    /// values with `quantizer`.
+    pub fn writer(&self, quantizer: Quantizer) -> Box<dyn ImageWriter> {
+        match self {
+            ImageFormat::Png => Box::new(PngWriter::new(quantizer)),
+            ImageFormat::BinaryPpm => Box::new(PpmWriter::binary(quantizer)),
+            ImageFormat::AsciiPpm => Box::new(PpmWriter::ascii(quantizer)),
+            ImageFormat::Hdr => Box::new(HdrWriter),
+            ImageFormat::Pfm => Box::new(PfmWriter),
+            ImageFormat::Exr => Box::new(ExrWriter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(
+            Some(ImageFormat::Png),
+            ImageFormat::from_path(Path::new("out/image.PNG"))
+        );
+        assert_eq!(
+            Some(ImageFormat::BinaryPpm),
+            ImageFormat::from_path(Path::new("image.ppm"))
+        );
+        assert_eq!(
+            Some(ImageFormat::Exr),
+            ImageFormat::from_path(Path::new("image.exr"))
+        );
+        assert!(ImageFormat::from_path(Path::new("image.hdr"))
+            .unwrap()
+            .is_high_dynamic_range());
+        assert!(!ImageFormat::Png.is_high_dynamic_range());
+        assert_eq!(None, ImageFormat::from_path(Path::new("image.jpg")));
+        assert_eq!(None, ImageFormat::from_path(Path::new("image")));
+    }
+}
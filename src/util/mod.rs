@@ -1,3 +1,14 @@
+pub mod adaptive;
 pub mod camera;
+pub mod checkpoint;
 pub mod color;
+pub mod film;
+pub mod filter;
+pub mod framebuffer;
+pub mod image;
 pub mod point;
+pub mod progress;
+pub mod random;
+pub mod renderer;
+pub mod sampler;
+pub mod tone_map;
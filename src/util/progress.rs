@@ -0,0 +1,169 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How long to wait between reports, so printing doesn't slow rendering.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Keeps track of how far a render is, and reports it on standard error.
+/// Tiles can finish on any thread.
+pub struct Progress {
+    total_pixels: u64,
+    pixels: AtomicU64,
+    samples: AtomicU64,
+    start: Instant,
+    /// When the last report was printed. Holding the lock while printing
+    /// keeps reports from different threads from mixing.
+    last_report: Mutex<Option<Instant>>,
+    visible: bool,
+}
+
+impl Progress {
+    /// Starts keeping track of a render.
+    ///
+    /// # Arguments
+    ///
+    /// * `total_pixels` - How many pixels are rendered in all.
+    /// * `visible` - Whether to print reports.
+    pub fn new(total_pixels: u64, visible: bool) -> Progress {
+        Progress {
+            total_pixels,
+            pixels: AtomicU64::new(0),
+            samples: AtomicU64::new(0),
+            start: Instant::now(),
+            last_report: Mutex::new(None),
+            visible,
+        }
+    }
+
+    /// Records that a tile is done, and prints a report if the last one was
+    /// a while ago.
+    pub fn add_tile(&self, pixels: u64, samples: u64) {
+        self.pixels.fetch_add(pixels, Ordering::Relaxed);
+        self.samples.fetch_add(samples, Ordering::Relaxed);
+        if !self.visible {
+            return;
+        }
+        let mut last_report = self.last_report.lock().unwrap();
+        let now = Instant::now();
+        if last_report.is_some_and(|last| now - last < REPORT_INTERVAL) {
+            return;
+        }
+        *last_report = Some(now);
+        eprint!("\r{}", self.report());
+    }
+
+    /// Prints the last report, and ends its line.
+    pub fn finish(&self) {
+        if self.visible {
+            let _last_report = self.last_report.lock().unwrap();
+            eprintln!("\r{}", self.report());
+        }
+    }
+
+    /// The samples traced so far.
+    pub fn samples(&self) -> u64 {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    fn report(&self) -> String {
+        format_report(
+            self.pixels.load(Ordering::Relaxed),
+            self.total_pixels,
+            self.samples(),
+            self.start.elapsed(),
+        )
+    }
+}
+
+/// A line like ` 42.0% | 1.25M samples/s | ETA 1:05`, padded to keep the
+/// line the same length.
+fn format_report(pixels: u64, total_pixels: u64, samples: u64, elapsed: Duration) -> String {
+    let fraction = if total_pixels == 0 {
+        1.0
+    } else {
+        pixels as f64 / total_pixels as f64
+    };
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        samples as f64 / seconds
+    } else {
+        0.0
+    };
+    let eta = if pixels == 0 {
+        "?".to_string()
+    } else {
+        format_duration(seconds * (1.0 - fraction) / fraction)
+    };
+    format!(
+        "{:5.1}% | {} samples/s | ETA {:<8}",
+        fraction * 100.0,
+        format_count(rate),
+        eta
+    )
+}
+
+/// Writes a duration as minutes and seconds, with hours if there are any.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Writes a count with a suffix for thousands or millions.
+fn format_count(count: f64) -> String {
+    if count >= 1e6 {
+        format!("{:.2}M", count / 1e6)
+    } else if count >= 1e3 {
+        format!("{:.1}k", count / 1e3)
+    } else {
+        format!("{:.0}", count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations_and_counts() {
+        assert_eq!("0:07", format_duration(7.2));
+        assert_eq!("2:05", format_duration(125.0));
+        assert_eq!("1:00:01", format_duration(3601.0));
+        assert_eq!("950", format_count(950.0));
+        assert_eq!("12.3k", format_count(12_345.0));
+        assert_eq!("1.25M", format_count(1_250_000.0));
+    }
+
+    #[test]
+    fn estimates_time_left_from_pixels_done() {
+        let report = format_report(25, 100, 5000, Duration::from_secs(10));
+        assert_eq!(" 25.0% | 500 samples/s | ETA 0:30    ", report);
+        let report = format_report(0, 100, 0, Duration::from_secs(0));
+        assert!(report.starts_with("  0.0% | 0 samples/s | ETA ?"));
+    }
+
+    #[test]
+    fn counts_tiles_from_many_threads() {
+        let progress = Progress::new(1000, false);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        progress.add_tile(25, 100);
+                    }
+                });
+            }
+        });
+        assert_eq!(4000, progress.samples());
+        assert_eq!(1000, progress.pixels.load(Ordering::Relaxed));
+    }
+}
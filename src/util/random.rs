@@ -0,0 +1,72 @@
+//! Random numbers that only depend on a seed, so a render can be repeated
+//! exactly, whichever thread renders which pixel.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+/// One step of SplitMix64: scrambles the bits of `value`, so that nearby
+/// values give unrelated results.
+pub fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Mixes all of the values together in to one hash. Changing any of them,
+/// or their order, gives an unrelated hash.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |state, &value| mix(state ^ value))
+}
+
+/// Turns a hash in to a number from 0 up to 1.
+pub fn to_unit(hash: u64) -> f64 {
+    // The top 53 bits fill the mantissa of a double.
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The generator for one sample of one pixel.
+///
+/// # Arguments
+///
+/// * `seed` - The seed of the whole render.
+/// * `x` - The column of the pixel.
+/// * `y` - The row of the pixel, counted from the top.
+/// * `sample` - Which sample of the pixel this is.
+pub fn sample_generator(seed: u64, x: usize, y: usize, sample: u64) -> StdRng {
+    StdRng::seed_from_u64(hash(&[seed, x as u64, y as u64, sample]))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn generators_repeat_for_same_sample() {
+        let draw = |seed, x, y, sample| sample_generator(seed, x, y, sample).gen::<u64>();
+        assert_eq!(draw(1, 2, 3, 4), draw(1, 2, 3, 4));
+        let others = [
+            draw(0, 2, 3, 4),
+            draw(1, 3, 2, 4),
+            draw(1, 2, 3, 5),
+            draw(1, 2, 4, 4),
+        ];
+        for other in others {
+            assert_ne!(draw(1, 2, 3, 4), other);
+        }
+    }
+
+    #[test]
+    fn hashes_are_spread_between_zero_and_one() {
+        let count = 10_000u32;
+        let values: Vec<f64> = (0..count)
+            .map(|i| to_unit(hash(&[u64::from(i), 5])))
+            .collect();
+        assert!(values.iter().all(|value| (0.0..1.0).contains(value)));
+        let mean = values.iter().sum::<f64>() / f64::from(count);
+        assert!((mean - 0.5).abs() < 0.01, "mean {}", mean);
+        assert_eq!(hash(&[1, 2]), hash(&[1, 2]));
+        assert_ne!(hash(&[1, 2]), hash(&[2, 1]));
+    }
+}
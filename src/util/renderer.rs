@@ -0,0 +1,660 @@
+//! Turns a scene in to an image, a square tile at a time.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use rand::RngCore;
+use rayon::prelude::*;
+
+use crate::{
+    geometry::{
+        ray::Ray,
+        vector_3d::{self, Vector3D},
+    },
+    scene::{
+        hit_record::HitRecord,
+        hittable::Hittable,
+        scene::Scene,
+        settings::{Region, RenderSettings},
+    },
+};
+
+use super::{
+    adaptive::{AdaptiveSampling, PixelStatistics},
+    checkpoint::CheckpointHeader,
+    color::Color,
+    film::{Film, FilmTile},
+    progress::Progress,
+    random,
+    sampler::Sampler,
+};
+
+/// The width and height of a tile in pixels. The pixels of a tile trace rays
+/// through the same part of the scene, which keeps it in the cache.
+const TILE_SIZE: usize = 16;
+
+/// Renders a scene in to a film, keeping track of the samples of every
+/// pixel.
+///
+/// Tiles are rendered on many threads, a batch at a time, and merged in to
+/// the film in order. As every sample has its own generator, the image only
+/// depends on the settings, not on which thread renders what.
+pub struct Renderer<'a> {
+    scene: &'a Scene,
+    width: usize,
+    height: usize,
+    max_depth: i32,
+    seed: u64,
+    sampler: Box<dyn Sampler>,
+    adaptive: AdaptiveSampling,
+    region: Region,
+    film: Film,
+    /// The samples of every pixel so far, row by row from the top left.
+    statistics: Vec<PixelStatistics>,
+    header: CheckpointHeader,
+    checkpoints: Option<Checkpoints>,
+}
+
+/// Where and how often a render saves checkpoints.
+struct Checkpoints {
+    path: PathBuf,
+    interval: Duration,
+    last_saved: Instant,
+}
+
+impl<'a> Renderer<'a> {
+    /// Returns a renderer that hasn't taken any samples yet.
+    ///
+    /// # Panics
+    ///
+    /// If the region to render doesn't fit in the image.
+    pub fn new(scene: &'a Scene, settings: &RenderSettings) -> Renderer<'a> {
+        let width = settings.width.max(0) as usize;
+        let height = settings.height().max(0) as usize;
+        let region = settings.render_region();
+        if !region.fits(width, height) {
+            panic!(
+                "The region {:?} doesn't fit in an image of {}x{}.",
+                region, width, height
+            );
+        }
+        Renderer {
+            scene,
+            width,
+            height,
+            max_depth: settings.max_depth,
+            seed: settings.seed,
+            sampler: settings
+                .sampler
+                .sampler(settings.seed, settings.samples_per_pixel.max(0) as u64),
+            adaptive: settings.adaptive_sampling(),
+            region,
+            film: Film::new(width, height, settings.filter).with_region(region),
+            statistics: vec![PixelStatistics::new(); width * height],
+            header: CheckpointHeader::new(settings),
+            checkpoints: None,
+        }
+    }
+
+    /// Saves a checkpoint to `path` every `interval` while rendering, and
+    /// once more when done.
+    pub fn save_checkpoints(&mut self, path: PathBuf, interval: Duration) {
+        self.checkpoints = Some(Checkpoints {
+            path,
+            interval,
+            last_saved: Instant::now(),
+        });
+    }
+
+    /// Saves the samples so far. The checkpoint is written next to `path`
+    /// first and then moved over it, so a crash while saving leaves the
+    /// last checkpoint whole.
+    pub fn save_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut output = BufWriter::new(File::create(&temporary)?);
+        self.header.write_to(&mut output)?;
+        self.film.write_to(&mut output)?;
+        for statistics in &self.statistics {
+            statistics.write_to(&mut output)?;
+        }
+        output.flush()?;
+        drop(output);
+        fs::rename(&temporary, path)
+    }
+
+    /// Carries on from a checkpoint, replacing the samples so far with the
+    /// ones saved in it. Fails if the checkpoint was made with settings
+    /// that would take different samples.
+    pub fn resume(&mut self, path: &Path) -> io::Result<()> {
+        let mut input = BufReader::new(File::open(path)?);
+        CheckpointHeader::read_from(&mut input)?.check_matches(&self.header)?;
+        self.film.read_from(&mut input)?;
+        for statistics in self.statistics.iter_mut() {
+            *statistics = PixelStatistics::read_from(&mut input)?;
+        }
+        Ok(())
+    }
+
+    /// Samples every pixel of the region until it has had enough. Fails if
+    /// a checkpoint can't be saved.
+    pub fn render(&mut self, progress: &Progress) -> io::Result<()> {
+        self.render_pass(u64::MAX, None, progress).map(|_| ())
+    }
+
+    /// Gives every pixel of the region up to `samples` more samples, fewer
+    /// if it has had enough before. Fails if a checkpoint can't be saved.
+    ///
+    /// Returns whether the pass was finished, rather than stopped at the
+    /// deadline. The deadline is checked between batches of tiles, so the
+    /// pass can run on a little past it. A checkpoint is saved at the end
+    /// either way, so a stopped render can be resumed.
+    pub fn render_pass(
+        &mut self,
+        samples: u64,
+        deadline: Option<Instant>,
+        progress: &Progress,
+    ) -> io::Result<bool> {
+        let tiles = self.tiles();
+        let batch_size = rayon::current_num_threads() * 2;
+        let mut finished = true;
+        for batch in tiles.chunks(batch_size) {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                finished = false;
+                break;
+            }
+            let rendered = batch
+                .par_iter()
+                .map(|tile| {
+                    let (film_tile, statistics) = self.render_tile(tile, samples);
+                    let samples = statistics
+                        .iter()
+                        .zip(self.tile_pixels(tile))
+                        .map(|(after, index)| after.count() - self.statistics[index].count())
+                        .sum();
+                    progress.add_tile((tile.width * tile.height) as u64, samples);
+                    (film_tile, statistics)
+                })
+                .collect::<Vec<(FilmTile, Vec<PixelStatistics>)>>();
+            for (tile, (film_tile, statistics)) in batch.iter().zip(rendered) {
+                self.film.merge(&film_tile);
+                for (index, pixel) in self.tile_pixels(tile).zip(statistics) {
+                    self.statistics[index] = pixel;
+                }
+            }
+            if let Some(checkpoints) = &self.checkpoints {
+                if checkpoints.last_saved.elapsed() >= checkpoints.interval {
+                    self.save_checkpoint(&checkpoints.path)?;
+                    self.checkpoints.as_mut().unwrap().last_saved = Instant::now();
+                }
+            }
+        }
+        if let Some(checkpoints) = &self.checkpoints {
+            self.save_checkpoint(&checkpoints.path)?;
+        }
+        Ok(finished)
+    }
+
+    /// Whether every pixel of the region has had enough samples.
+    pub fn is_done(&self) -> bool {
+        self.tile_pixels(&self.region)
+            .all(|index| self.adaptive.is_done(&self.statistics[index]))
+    }
+
+    /// The samples gathered so far.
+    pub fn film(&self) -> &Film {
+        &self.film
+    }
+
+    /// How many samples every pixel has had, row by row from the top left.
+    pub fn sample_counts(&self) -> Vec<u64> {
+        self.statistics.iter().map(PixelStatistics::count).collect()
+    }
+
+    /// The part of the image being rendered.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Splits the region in to tiles, row by row from the top left.
+    fn tiles(&self) -> Vec<Region> {
+        let region = &self.region;
+        let mut tiles = Vec::new();
+        for y in (region.y..region.y + region.height).step_by(TILE_SIZE) {
+            for x in (region.x..region.x + region.width).step_by(TILE_SIZE) {
+                tiles.push(Region {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(region.x + region.width - x),
+                    height: TILE_SIZE.min(region.y + region.height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// The indices of the pixels of a tile, row by row.
+    fn tile_pixels(&self, tile: &Region) -> impl Iterator<Item = usize> {
+        let (width, tile) = (self.width, *tile);
+        (tile.y..tile.y + tile.height)
+            .flat_map(move |y| (tile.x..tile.x + tile.width).map(move |x| y * width + x))
+    }
+
+    /// Gives the pixels of a tile up to `samples` more samples, and returns
+    /// the samples and the new statistics of the pixels.
+    fn render_tile(&self, tile: &Region, samples: u64) -> (FilmTile, Vec<PixelStatistics>) {
+        let mut film_tile = self.film.tile(tile.x, tile.y, tile.width, tile.height);
+        let statistics = self
+            .tile_pixels(tile)
+            .map(|index| {
+                let (x, y) = (index % self.width, index / self.width);
+                let mut statistics = self.statistics[index];
+                let limit = statistics.count().saturating_add(samples);
+                while statistics.count() < limit && !self.adaptive.is_done(&statistics) {
+                    let sample = statistics.count();
+                    let (color, film_x, film_y) = self.sample(x, y, sample);
+                    film_tile.add_sample(film_x, film_y, &color);
+                    statistics.add(&color);
+                }
+                statistics
+            })
+            .collect();
+        (film_tile, statistics)
+    }
+
+    /// Traces one sample of a pixel, and returns its color and where it is
+    /// in the image, in pixels from the top left.
+    fn sample(&self, x: usize, y: usize, sample: u64) -> (Color, f64, f64) {
+        let generator = &mut random::sample_generator(self.seed, x, y, sample);
+        let camera_sample = self.sampler.camera_sample(x, y, sample);
+        // The camera counts rows from the bottom.
+        let row = self.height - 1 - y;
+        let u = (x as f64 + camera_sample.film[0]) / (self.width - 1) as f64;
+        let v = (row as f64 + camera_sample.film[1]) / (self.height - 1) as f64;
+        let ray = self
+            .scene
+            .camera
+            .get_ray(u, v, camera_sample.lens, generator);
+        let color = ray_color(&ray, self.scene, self.max_depth, 1.0, generator);
+        (
+            color,
+            x as f64 + camera_sample.film[0],
+            y as f64 + 1.0 - camera_sample.film[1],
+        )
+    }
+}
+
+/// The light coming back along a ray.
+///
+/// Where a ray scatters off a surface that isn't a mirror, light is found two
+/// ways: by a shadow ray aimed at the lights, and by the scattered ray
+/// hitting one. Each is weighed by the power heuristic, so the way more
+/// likely to find a given light counts the most, and the light isn't counted
+/// twice. `emission_weight` is the weight of light given off by what the ray
+/// hits, which is less than 1 if the surface it left already aimed at it.
+fn ray_color(
+    ray: &Ray,
+    scene: &Scene,
+    depth: i32,
+    emission_weight: f64,
+    generator: &mut dyn RngCore,
+) -> Color {
+    if depth <= 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let record = match scene.objects.hit(ray, 0.001, f64::INFINITY, generator) {
+        Some(record) => record,
+        None => return scene.background.color(ray),
+    };
+    let emitted = emission_weight * record.material.emitted(record.u, record.v, &record.p);
+    let wo = -vector_3d::unit_vector(&ray.direction);
+    let sample = match record.material.sample(&wo, &record, generator) {
+        Some(sample) => sample,
+        None => return emitted,
+    };
+    let scattered = Ray::new(record.p, sample.direction, Some(ray.time));
+
+    if sample.is_specular {
+        // A shadow ray can't find light off a mirror.
+        return emitted + sample.weight * ray_color(&scattered, scene, depth - 1, 1.0, generator);
+    }
+    let direct = if depth > 1 {
+        direct_light(&wo, &record, ray.time, scene, generator)
+    } else {
+        Color::empty()
+    };
+    let light_pdf = scene.lights.pdf_value(&record.p, &sample.direction);
+    let weight = power_heuristic(sample.pdf, light_pdf);
+    emitted + direct + sample.weight * ray_color(&scattered, scene, depth - 1, weight, generator)
+}
+
+/// Light reaching a point straight from the lights of the scene, found with
+/// a shadow ray, and sent towards `wo`.
+///
+/// # Arguments
+///
+/// * `wo` - The direction back along the ray that hit the point.
+/// * `record` - Where the ray hit.
+/// * `time` - The time of the ray.
+fn direct_light(
+    wo: &Vector3D,
+    record: &HitRecord,
+    time: f64,
+    scene: &Scene,
+    generator: &mut dyn RngCore,
+) -> Color {
+    let wi = vector_3d::unit_vector(&scene.lights.random_direction(&record.p, generator));
+    let light_pdf = scene.lights.pdf_value(&record.p, &wi);
+    let scattering_pdf = record.material.pdf(&wi, wo, record);
+    if light_pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return Color::empty();
+    }
+    // Whatever the shadow ray hits first is what lights the point, which
+    // is black unless it is a light.
+    let shadow_ray = Ray::new(record.p, wi, Some(time));
+    match scene
+        .objects
+        .hit(&shadow_ray, 0.001, f64::INFINITY, generator)
+    {
+        Some(light) => {
+            let emitted = light.material.emitted(light.u, light.v, &light.p);
+            power_heuristic(light_pdf, scattering_pdf) / light_pdf
+                * record.material.evaluate(&wi, wo, record)
+                * emitted
+        }
+        None => Color::empty(),
+    }
+}
+
+/// Veach's power heuristic with an exponent of 2: the weight of a sample
+/// taken with a density of `pdf`, when another way of sampling would have
+/// taken it with a density of `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+    if pdf + other_pdf == 0.0 {
+        return 0.0;
+    }
+    pdf / (pdf + other_pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rayon::ThreadPoolBuilder;
+
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        geometry::{flat_bvh::FlatBvh, vector_3d::Vector3D},
+        scene::{
+            aa_rect::XzRect,
+            background::Background,
+            materials::{DiffuseLight, Lambertian},
+        },
+        util::{
+            camera::Camera,
+            filter::{Filter, FilterKind},
+            point::Point3D,
+        },
+    };
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 40,
+            aspect_ratio: 4.0 / 3.0,
+            samples_per_pixel: 4,
+            max_depth: 5,
+            filter: Filter::new(FilterKind::Gaussian),
+            ..RenderSettings::default()
+        }
+    }
+
+    #[test]
+    fn tiles_cover_region_once() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let mut settings = settings();
+        settings.region = Some("3,5,35,20".parse().unwrap());
+        let renderer = Renderer::new(&scene, &settings);
+        let mut covered = vec![0; 40 * 30];
+        for tile in renderer.tiles() {
+            assert!(tile.width <= TILE_SIZE && tile.height <= TILE_SIZE);
+            for index in renderer.tile_pixels(&tile) {
+                covered[index] += 1;
+            }
+        }
+        for (index, count) in covered.into_iter().enumerate() {
+            let (x, y) = (index % 40, index / 40);
+            let inside = (3..38).contains(&x) && (5..25).contains(&y);
+            assert_eq!(usize::from(inside), count, "pixel {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn renders_only_region() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let mut settings = settings();
+        settings.region = Some("10,10,5,5".parse().unwrap());
+        let mut renderer = Renderer::new(&scene, &settings);
+        let progress = Progress::new(25, false);
+        renderer.render(&progress).unwrap();
+        assert_eq!(25 * 4, progress.samples());
+        let counts = renderer.sample_counts();
+        let framebuffer = renderer.film().to_framebuffer();
+        for y in 0..30 {
+            for x in 0..40 {
+                let inside = (10..15).contains(&x) && (10..15).contains(&y);
+                assert_eq!(if inside { 4 } else { 0 }, counts[y * 40 + x]);
+                // The Gaussian filter doesn't reach past the region.
+                assert_eq!(inside, framebuffer.pixel(x, y).length() > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn same_image_on_any_number_of_threads() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let render = |threads| {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut renderer = Renderer::new(&scene, &settings());
+                renderer.render(&Progress::new(40 * 30, false)).unwrap();
+                renderer.film().to_framebuffer()
+            })
+        };
+        assert_eq!(render(1), render(3));
+    }
+
+    #[test]
+    fn resuming_gives_same_image_as_not_stopping() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let path = std::env::temp_dir().join(format!("checkpoint-{}", std::process::id()));
+        let mut settings = settings();
+        settings.samples_per_pixel = 2;
+        let mut first = Renderer::new(&scene, &settings);
+        first.save_checkpoints(path.clone(), Duration::from_secs(3600));
+        first.render(&Progress::new(0, false)).unwrap();
+
+        settings.samples_per_pixel = 5;
+        let mut resumed = Renderer::new(&scene, &settings);
+        resumed.resume(&path).unwrap();
+        let progress = Progress::new(40 * 30, false);
+        resumed.render(&progress).unwrap();
+        assert_eq!(40 * 30 * 3, progress.samples());
+
+        let mut whole = Renderer::new(&scene, &settings);
+        whole.render(&Progress::new(0, false)).unwrap();
+        assert_eq!(whole.sample_counts(), resumed.sample_counts());
+        // Only the order the samples were added in differs.
+        let (whole, resumed) = (
+            whole.film().to_framebuffer(),
+            resumed.film().to_framebuffer(),
+        );
+        for y in 0..30 {
+            for x in 0..40 {
+                assert_relative_eq!(whole.pixel(x, y), resumed.pixel(x, y), epsilon = 1e-12);
+            }
+        }
+
+        settings.seed = 1;
+        let error = Renderer::new(&scene, &settings).resume(&path).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn passes_add_up_to_whole_render() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let mut settings = settings();
+        settings.samples_per_pixel = 5;
+        let mut passes = Renderer::new(&scene, &settings);
+        let mut finished_passes = 0;
+        while !passes.is_done() {
+            assert!(passes
+                .render_pass(2, None, &Progress::new(0, false))
+                .unwrap());
+            finished_passes += 1;
+            assert!(passes
+                .sample_counts()
+                .iter()
+                .all(|&count| count == (2 * finished_passes).min(5)));
+        }
+        assert_eq!(3, finished_passes);
+
+        let mut whole = Renderer::new(&scene, &settings);
+        whole.render(&Progress::new(0, false)).unwrap();
+        let (whole, passes) = (
+            whole.film().to_framebuffer(),
+            passes.film().to_framebuffer(),
+        );
+        for y in 0..30 {
+            for x in 0..40 {
+                assert_relative_eq!(whole.pixel(x, y), passes.pixel(x, y), epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn stops_pass_at_deadline() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let path = std::env::temp_dir().join(format!("deadline-{}", std::process::id()));
+        let mut renderer = Renderer::new(&scene, &settings());
+        renderer.save_checkpoints(path.clone(), Duration::from_secs(3600));
+        let progress = Progress::new(0, false);
+        assert!(!renderer
+            .render_pass(2, Some(Instant::now()), &progress)
+            .unwrap());
+        assert_eq!(0, progress.samples());
+        assert!(!renderer.is_done());
+        // The stopped render can still be resumed.
+        assert!(path.exists());
+        Renderer::new(&scene, &settings()).resume(&path).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    /// A white floor under a square lamp, seen from above, with the lamp in
+    /// the light list or not.
+    fn lamp_scene(aim_at_lamp: bool) -> Scene {
+        let lamp: Arc<dyn Hittable> = Arc::new(
+            XzRect::new(
+                (-0.5, 0.5),
+                (-0.5, 0.5),
+                2.0,
+                Box::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0))),
+            )
+            .flipped(),
+        );
+        let floor = XzRect::new(
+            (-10.0, 10.0),
+            (-10.0, 10.0),
+            0.0,
+            Box::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+        );
+        let objects: Vec<Box<dyn Hittable>> = vec![Box::new(Arc::clone(&lamp)), Box::new(floor)];
+        let lights: Vec<Box<dyn Hittable>> = if aim_at_lamp {
+            vec![Box::new(lamp)]
+        } else {
+            vec![]
+        };
+        Scene {
+            camera: Camera::new(
+                Point3D::new(0.0, 1.5, 0.0),
+                Point3D::empty(),
+                Vector3D::new(0.0, 0.0, 1.0),
+                90.0,
+                1.0,
+                0.0,
+                1.5,
+                0.0,
+                1.0,
+            ),
+            objects: FlatBvh::new(objects, 0.0, 1.0),
+            lights,
+            background: Background::Solid(Color::empty()),
+        }
+    }
+
+    #[test]
+    fn aiming_at_lights_gives_same_image_with_less_noise() {
+        let settings = RenderSettings {
+            width: 8,
+            aspect_ratio: 1.0,
+            samples_per_pixel: 256,
+            max_depth: 3,
+            ..RenderSettings::default()
+        };
+        // The mean brightness, and how much renders with two seeds differ.
+        let brightness = |aim_at_lamp| {
+            let scene = lamp_scene(aim_at_lamp);
+            let pixels = |seed| {
+                let settings = RenderSettings {
+                    seed,
+                    ..settings.clone()
+                };
+                let mut renderer = Renderer::new(&scene, &settings);
+                renderer.render(&Progress::new(0, false)).unwrap();
+                let framebuffer = renderer.film().to_framebuffer();
+                (0..64)
+                    .map(|index| framebuffer.pixel(index % 8, index / 8).y())
+                    .collect::<Vec<f64>>()
+            };
+            let (first, second) = (pixels(0), pixels(1));
+            let mean = first.iter().sum::<f64>() / 64.0;
+            let noise = first
+                .iter()
+                .zip(&second)
+                .map(|(first, second)| (first - second).powi(2))
+                .sum::<f64>();
+            (mean, noise)
+        };
+        let (aimed_mean, aimed_noise) = brightness(true);
+        let (mean, noise) = brightness(false);
+        assert_relative_eq!(mean, aimed_mean, max_relative = 0.1);
+        assert!(aimed_noise < noise / 4.0);
+    }
+
+    #[test]
+    fn power_heuristic_favors_likelier_sampling() {
+        assert_relative_eq!(0.5, power_heuristic(2.0, 2.0));
+        assert_relative_eq!(0.9, power_heuristic(3.0, 1.0));
+        assert_relative_eq!(1.0, power_heuristic(1.0, 0.0));
+        assert_relative_eq!(0.0, power_heuristic(0.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn region_must_fit_in_image() {
+        let scene = Scene::two_balls(4.0 / 3.0);
+        let mut settings = settings();
+        settings.region = Some("30,0,20,10".parse().unwrap());
+        Renderer::new(&scene, &settings);
+    }
+}
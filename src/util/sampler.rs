@@ -0,0 +1,492 @@
+//! Where in its pixel, and where on the lens, each camera ray goes.
+//!
+//! Independent random numbers clump together and leave gaps, which shows up
+//! as noise. The other samplers spread the samples of a pixel evenly over the
+//! four dimensions of a camera ray: two across the pixel and two across the
+//! lens. Every pixel randomizes its samples differently, so the patterns of
+//! neighbouring pixels don't line up in to visible structure.
+
+use super::random;
+
+/// The numbers that place one camera ray, all from 0 up to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraSample {
+    /// Where the ray goes through the pixel, across and up.
+    pub film: [f64; 2],
+    /// Where on the lens the ray starts.
+    pub lens: [f64; 2],
+}
+
+/// Places the samples of every pixel of an image.
+pub trait Sampler: Sync {
+    /// Where a sample of a pixel goes. The same arguments always give the
+    /// same sample.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column of the pixel.
+    /// * `y` - The row of the pixel, counted from the top.
+    /// * `sample` - Which sample of the pixel this is. Samples past the number
+    ///   the sampler was made for are fine, but not as evenly spread.
+    fn camera_sample(&self, x: usize, y: usize, sample: u64) -> CameraSample;
+}
+
+/// The ways to place samples.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    /// Independent uniform random numbers.
+    #[default]
+    Independent,
+    /// One sample in every cell of a grid over the pixel and over the lens,
+    /// at a random place in the cell.
+    Stratified,
+    /// The Halton sequence, shifted randomly for every pixel.
+    Halton,
+    /// The Sobol sequence with Owen scrambling.
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Returns a sampler of this kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed of the whole render.
+    /// * `samples_per_pixel` - How many samples every pixel gets. The
+    ///   stratified sampler makes a grid with about as many cells.
+    pub fn sampler(&self, seed: u64, samples_per_pixel: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler { seed }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler { seed }),
+            SamplerKind::Sobol => Box::new(SobolSampler { seed }),
+        }
+    }
+}
+
+/// A hash that is different for every pixel of every render.
+fn pixel_hash(seed: u64, x: usize, y: usize) -> u64 {
+    random::hash(&[seed, x as u64, y as u64])
+}
+
+struct IndependentSampler {
+    seed: u64,
+}
+
+impl Sampler for IndependentSampler {
+    fn camera_sample(&self, x: usize, y: usize, sample: u64) -> CameraSample {
+        let pixel = pixel_hash(self.seed, x, y);
+        let value = |dimension| random::to_unit(random::hash(&[pixel, sample, dimension]));
+        CameraSample {
+            film: [value(0), value(1)],
+            lens: [value(2), value(3)],
+        }
+    }
+}
+
+/// Divides the pixel and the lens in to grids with a cell for every sample.
+/// Which cell of the pixel goes with which cell of the lens is shuffled, so
+/// that the two don't move together.
+struct StratifiedSampler {
+    seed: u64,
+    columns: u32,
+    rows: u32,
+}
+
+impl StratifiedSampler {
+    fn new(seed: u64, samples_per_pixel: u64) -> StratifiedSampler {
+        let samples = samples_per_pixel.clamp(1, u64::from(u16::MAX)) as f64;
+        let columns = samples.sqrt().ceil();
+        let rows = (samples / columns).ceil();
+        StratifiedSampler {
+            seed,
+            columns: columns as u32,
+            rows: rows as u32,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn camera_sample(&self, x: usize, y: usize, sample: u64) -> CameraSample {
+        let cells = u64::from(self.columns * self.rows);
+        // Samples past the last cell start over in a differently shuffled
+        // grid.
+        let round = sample / cells;
+        let index = (sample % cells) as u32;
+        let pixel = random::hash(&[pixel_hash(self.seed, x, y), round]);
+        let point = |dimension: u64| {
+            let cell = permute(
+                index,
+                cells as u32,
+                random::hash(&[pixel, dimension]) as u32,
+            );
+            let jitter =
+                |axis| random::to_unit(random::hash(&[pixel, dimension, u64::from(index), axis]));
+            [
+                (f64::from(cell % self.columns) + jitter(0)) / f64::from(self.columns),
+                (f64::from(cell / self.columns) + jitter(1)) / f64::from(self.rows),
+            ]
+        };
+        CameraSample {
+            film: point(0),
+            lens: point(1),
+        }
+    }
+}
+
+/// Shuffles the numbers from 0 up to `length`: every seed gives a different
+/// order, and every number comes out once. This is Kensler's hashed
+/// permutation from "Correlated Multi-Jittered Sampling", which hashes within
+/// the next power of two and tries again until the result is in range.
+fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            return (index + seed % length) % length;
+        }
+    }
+}
+
+/// The Halton sequence in bases 2 and 3 for the pixel and 5 and 7 for the
+/// lens. Every pixel shifts all of its points by the same random amount,
+/// wrapping around at 1, which keeps them as evenly spread.
+struct HaltonSampler {
+    seed: u64,
+}
+
+impl Sampler for HaltonSampler {
+    fn camera_sample(&self, x: usize, y: usize, sample: u64) -> CameraSample {
+        let pixel = pixel_hash(self.seed, x, y);
+        let value = |dimension: u64, base| {
+            let shift = random::to_unit(random::hash(&[pixel, dimension]));
+            (radical_inverse(base, sample) + shift).fract()
+        };
+        CameraSample {
+            film: [value(0, 2), value(1, 3)],
+            lens: [value(2, 5), value(3, 7)],
+        }
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the decimal point, so
+/// 6, which is 110 in base 2, becomes 0.011 in base 2.
+fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut scale = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inverse_base;
+    }
+    result
+}
+
+/// The first four dimensions of the Sobol sequence, with Burley's hash based
+/// Owen scrambling from "Practical Hash-based Owen Scrambling". Scrambling
+/// keeps every power of two of consecutive points as evenly spread as the
+/// sequence itself. The order of the points is shuffled the same way, so
+/// that every pixel uses a different part of the sequence.
+struct SobolSampler {
+    seed: u64,
+}
+
+impl Sampler for SobolSampler {
+    fn camera_sample(&self, x: usize, y: usize, sample: u64) -> CameraSample {
+        let pixel = pixel_hash(self.seed, x, y);
+        let index = nested_uniform_scramble(sample as u32, pixel as u32);
+        let value = |dimension: usize| {
+            let seed = random::hash(&[pixel, dimension as u64]) as u32;
+            let scrambled = nested_uniform_scramble(sobol(index, dimension), seed);
+            f64::from(scrambled) / (1u64 << 32) as f64
+        };
+        CameraSample {
+            film: [value(0), value(1)],
+            lens: [value(2), value(3)],
+        }
+    }
+}
+
+/// The direction numbers of the first four dimensions of the Sobol sequence,
+/// from Joe and Kuo's table.
+const SOBOL_DIRECTIONS: [[u32; 32]; 4] = [
+    direction_numbers(0, &[]),
+    direction_numbers(0, &[1]),
+    direction_numbers(1, &[1, 3]),
+    direction_numbers(1, &[1, 3, 1]),
+];
+
+/// Works out the direction numbers of a dimension of the Sobol sequence.
+/// With no initial numbers this gives the first dimension, which is the van
+/// der Corput sequence.
+///
+/// # Arguments
+///
+/// * `coefficients` - The inner coefficients of the primitive polynomial,
+///   highest power first.
+/// * `initial` - The first direction numbers, as odd numbers smaller than
+///   two to the power of their position plus one.
+const fn direction_numbers(coefficients: u32, initial: &[u32]) -> [u32; 32] {
+    let degree = initial.len();
+    let mut directions = [0; 32];
+    let mut bit = 0;
+    while bit < 32 {
+        directions[bit] = if degree == 0 {
+            1 << (31 - bit)
+        } else if bit < degree {
+            initial[bit] << (31 - bit)
+        } else {
+            let mut direction = directions[bit - degree] ^ (directions[bit - degree] >> degree);
+            let mut k = 1;
+            while k < degree {
+                if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                    direction ^= directions[bit - k];
+                }
+                k += 1;
+            }
+            direction
+        };
+        bit += 1;
+    }
+    directions
+}
+
+/// A point of one dimension of the Sobol sequence, as a fraction of two to
+/// the power of 32.
+fn sobol(index: u32, dimension: usize) -> u32 {
+    let mut result = 0;
+    for (bit, direction) in SOBOL_DIRECTIONS[dimension].iter().enumerate() {
+        if (index >> bit) & 1 == 1 {
+            result ^= direction;
+        }
+    }
+    result
+}
+
+/// Owen scrambles a fraction of two to the power of 32: whether a bit is
+/// flipped only depends on the bits above it.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+
+/// A hash in which every bit only depends on the bits below it.
+fn laine_karras_permutation(mut value: u32, seed: u32) -> u32 {
+    value = value.wrapping_add(seed);
+    value ^= value.wrapping_mul(0x6c50_b47c);
+    value ^= value.wrapping_mul(0xb82f_1e52);
+    value ^= value.wrapping_mul(0xc7af_e638);
+    value ^= value.wrapping_mul(0x8d22_f6e6);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    fn samples(sampler: &dyn Sampler, x: usize, y: usize, count: u64) -> Vec<CameraSample> {
+        (0..count)
+            .map(|sample| sampler.camera_sample(x, y, sample))
+            .collect()
+    }
+
+    /// Whether exactly one point falls in every cell of a grid.
+    fn one_in_every_cell(points: &[[f64; 2]], columns: usize, rows: usize) -> bool {
+        let mut counts = vec![0; columns * rows];
+        for point in points {
+            let column = (point[0] * columns as f64) as usize;
+            let row = (point[1] * rows as f64) as usize;
+            counts[row * columns + column] += 1;
+        }
+        counts.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn samples_are_repeatable_and_in_range() {
+        for kind in KINDS {
+            let sampler = kind.sampler(7, 16);
+            let pixel = samples(sampler.as_ref(), 3, 4, 40);
+            assert_eq!(pixel, samples(sampler.as_ref(), 3, 4, 40), "{:?}", kind);
+            assert_ne!(pixel, samples(sampler.as_ref(), 4, 3, 40), "{:?}", kind);
+            assert_ne!(
+                pixel,
+                samples(kind.sampler(8, 16).as_ref(), 3, 4, 40),
+                "{:?}",
+                kind
+            );
+            for sample in pixel {
+                for value in sample.film.iter().chain(&sample.lens) {
+                    assert!((0.0..1.0).contains(value), "{:?} gave {}", kind, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_in_every_cell() {
+        let sampler = SamplerKind::Stratified.sampler(1, 16);
+        for (x, y) in [(0, 0), (5, 9)] {
+            let pixel = samples(sampler.as_ref(), x, y, 16);
+            let film: Vec<[f64; 2]> = pixel.iter().map(|sample| sample.film).collect();
+            let lens: Vec<[f64; 2]> = pixel.iter().map(|sample| sample.lens).collect();
+            assert!(one_in_every_cell(&film, 4, 4));
+            assert!(one_in_every_cell(&lens, 4, 4));
+        }
+        // Counts that aren't square leave some cells empty, but never put
+        // two samples in one.
+        let pixel = samples(SamplerKind::Stratified.sampler(1, 6).as_ref(), 0, 0, 6);
+        let film: Vec<[f64; 2]> = pixel.iter().map(|sample| sample.film).collect();
+        let mut cells: Vec<(usize, usize)> = film
+            .iter()
+            .map(|point| ((point[0] * 3.0) as usize, (point[1] * 2.0) as usize))
+            .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        assert_eq!(6, cells.len());
+    }
+
+    #[test]
+    fn sobol_points_form_nets() {
+        let sampler = SamplerKind::Sobol.sampler(3, 64);
+        for (x, y) in [(0, 0), (12, 1)] {
+            let pixel = samples(sampler.as_ref(), x, y, 64);
+            let film: Vec<[f64; 2]> = pixel.iter().map(|sample| sample.film).collect();
+            for (columns, rows) in [(8, 8), (64, 1), (1, 64), (4, 16), (32, 2)] {
+                assert!(one_in_every_cell(&film, columns, rows));
+            }
+            for dimension in 0..4 {
+                let values: Vec<[f64; 2]> = pixel
+                    .iter()
+                    .map(|sample| {
+                        let values = [sample.film, sample.lens].concat();
+                        [values[dimension], 0.0]
+                    })
+                    .collect();
+                assert!(one_in_every_cell(&values, 64, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn halton_points_are_spread_in_every_dimension() {
+        let sampler = SamplerKind::Halton.sampler(5, 0);
+        let pixel = samples(sampler.as_ref(), 2, 2, 2 * 3 * 5 * 7);
+        for (dimension, base) in [(0, 2), (1, 3), (2, 5), (3, 7)] {
+            // Shifting wraps the points around, so count the cells from half
+            // a cell before the first point, away from rounding errors.
+            let values: Vec<f64> = pixel
+                .iter()
+                .map(|sample| [sample.film, sample.lens].concat()[dimension])
+                .collect();
+            let points: Vec<[f64; 2]> = values
+                .iter()
+                .take(base * base)
+                .map(|value| {
+                    let cell = 1.0 / (base * base) as f64;
+                    [(value - values[0] + 1.0 + cell / 2.0).fract(), 0.0]
+                })
+                .collect();
+            assert!(one_in_every_cell(&points, base * base, 1), "base {}", base);
+        }
+    }
+
+    #[test]
+    fn permutes_every_number_once() {
+        for length in [1, 2, 7, 16, 100] {
+            for seed in [0, 1, 0xdead_beef] {
+                let mut permuted: Vec<u32> = (0..length)
+                    .map(|index| permute(index, length, seed))
+                    .collect();
+                permuted.sort_unstable();
+                assert_eq!((0..length).collect::<Vec<u32>>(), permuted);
+            }
+        }
+        assert_ne!(permute(0, 100, 1), permute(0, 100, 2));
+    }
+
+    #[test]
+    fn sobol_matches_known_directions() {
+        assert_eq!(
+            [0x8000_0000, 0xc000_0000, 0xa000_0000, 0xf000_0000],
+            SOBOL_DIRECTIONS[1][..4]
+        );
+        assert_eq!(0xffff_ffff, SOBOL_DIRECTIONS[1][31]);
+        assert_eq!(
+            [0x8000_0000, 0xc000_0000, 0x6000_0000, 0x9000_0000],
+            SOBOL_DIRECTIONS[2][..4]
+        );
+        assert_eq!(0x4000_0000, sobol(1 << 1, 0));
+    }
+
+    #[test]
+    fn even_samplers_estimate_with_less_error() {
+        // The area of a quarter of a circle, over the pixel and over the lens.
+        let estimate_error = |kind: SamplerKind| {
+            let sampler = kind.sampler(11, 16);
+            let inside = |point: [f64; 2]| {
+                if point[0] * point[0] + point[1] * point[1] < 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+            let mut squared_error = 0.0;
+            for x in 0..256 {
+                let pixel = samples(sampler.as_ref(), x, 0, 16);
+                for area in [
+                    |sample: &CameraSample| sample.film,
+                    |sample: &CameraSample| sample.lens,
+                ] {
+                    let estimate =
+                        pixel.iter().map(|sample| inside(area(sample))).sum::<f64>() / 16.0;
+                    squared_error += (estimate - std::f64::consts::FRAC_PI_4).powi(2);
+                }
+            }
+            squared_error
+        };
+        let independent = estimate_error(SamplerKind::Independent);
+        for kind in [
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let error = estimate_error(kind);
+            assert!(
+                error < independent / 2.0,
+                "{:?}: {} against {}",
+                kind,
+                error,
+                independent
+            );
+        }
+    }
+}
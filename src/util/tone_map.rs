@@ -0,0 +1,190 @@
+use super::color::Color;
+
+/// How radiance, which has no upper limit, is squeezed in to the 0 to 1 range
+/// of a display.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// Cut off everything brighter than 1.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)`, which never quite reaches white.
+    Reinhard,
+    /// Reinhard, scaled so the white point maps to white.
+    ExtendedReinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+}
+
+/// Turns linear colors in to sRGB encoded colors between 0 and 1, ready to
+/// be quantized.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Brightness adjustment in stops: every stop doubles the brightness.
+    pub exposure: f64,
+    /// The smallest value that becomes white with extended Reinhard.
+    pub white_point: f64,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::default(),
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Tone maps and encodes a linear color.
+    pub fn apply(&self, color: &Color) -> Color {
+        let scale = self.exposure.exp2();
+        let map = |value: f64| srgb_encode(self.map_channel(value * scale));
+        Color::new(map(color.x()), map(color.y()), map(color.z()))
+    }
+
+    /// Tone maps one exposed channel, giving a linear value between 0 and 1.
+    fn map_channel(&self, value: f64) -> f64 {
+        let value = value.max(0.0);
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => value,
+            ToneMapOperator::Reinhard => value / (1.0 + value),
+            ToneMapOperator::ExtendedReinhard => {
+                let white = self.white_point * self.white_point;
+                value * (1.0 + value / white) / (1.0 + value)
+            }
+            ToneMapOperator::Aces => {
+                // The fit expects values a little darker than the original
+                // curve does.
+                let value = value * 0.6;
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+            ToneMapOperator::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                hable_curve(value * EXPOSURE_BIAS) / hable_curve(WHITE)
+            }
+        };
+        mapped.min(1.0)
+    }
+}
+
+fn hable_curve(x: f64) -> f64 {
+    const SHOULDER_STRENGTH: f64 = 0.15;
+    const LINEAR_STRENGTH: f64 = 0.50;
+    const LINEAR_ANGLE: f64 = 0.10;
+    const TOE_STRENGTH: f64 = 0.20;
+    const TOE_NUMERATOR: f64 = 0.02;
+    const TOE_DENOMINATOR: f64 = 0.30;
+    (x * (SHOULDER_STRENGTH * x + LINEAR_ANGLE * LINEAR_STRENGTH) + TOE_STRENGTH * TOE_NUMERATOR)
+        / (x * (SHOULDER_STRENGTH * x + LINEAR_STRENGTH) + TOE_STRENGTH * TOE_DENOMINATOR)
+        - TOE_NUMERATOR / TOE_DENOMINATOR
+}
+
+/// The sRGB transfer function, from a linear value between 0 and 1 to the
+/// value to store. Values outside of 0 to 1 are clamped.
+pub fn srgb_encode(linear: f64) -> f64 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 5] = [
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ExtendedReinhard,
+        ToneMapOperator::Aces,
+        ToneMapOperator::Hable,
+    ];
+
+    fn mapping(operator: ToneMapOperator) -> ToneMapping {
+        ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        }
+    }
+
+    #[test]
+    fn srgb_matches_known_values() {
+        assert_relative_eq!(0.0, srgb_encode(0.0));
+        assert_relative_eq!(0.0129, srgb_encode(0.001), epsilon = 1e-4);
+        assert_relative_eq!(0.7354, srgb_encode(0.5), epsilon = 1e-4);
+        assert_relative_eq!(1.0, srgb_encode(1.0), epsilon = 1e-12);
+        assert_relative_eq!(1.0, srgb_encode(3.0));
+        assert_relative_eq!(0.0, srgb_encode(-1.0));
+        // The two pieces meet.
+        assert_relative_eq!(
+            12.92 * 0.003_130_8,
+            1.055 * 0.003_130_8f64.powf(1.0 / 2.4) - 0.055,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn operators_keep_black_and_stay_in_range() {
+        for operator in OPERATORS {
+            let mapping = mapping(operator);
+            assert_relative_eq!(
+                Color::empty(),
+                mapping.apply(&Color::empty()),
+                epsilon = 1e-3
+            );
+            let mut previous = 0.0;
+            for step in 1..200 {
+                let value = mapping
+                    .apply(&Color::new(f64::from(step) * 0.1, 0.0, 0.0))
+                    .x();
+                assert!((0.0..=1.0).contains(&value), "{:?}", operator);
+                assert!(value >= previous, "{:?} isn't monotonic", operator);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn operators_match_known_values() {
+        let map = |operator, value| mapping(operator).map_channel(value);
+        assert_relative_eq!(1.0, map(ToneMapOperator::Clamp, 2.0));
+        assert_relative_eq!(0.5, map(ToneMapOperator::Reinhard, 1.0));
+        assert_relative_eq!(1.0, map(ToneMapOperator::ExtendedReinhard, 4.0));
+        assert_relative_eq!(
+            0.5 * 17.0 / 16.0,
+            map(ToneMapOperator::ExtendedReinhard, 1.0)
+        );
+        assert_relative_eq!(1.0, map(ToneMapOperator::Hable, 5.6), epsilon = 1e-12);
+        assert_relative_eq!(0.8, map(ToneMapOperator::Aces, 1.0 / 0.6), epsilon = 0.01);
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let brighter = ToneMapping {
+            exposure: 1.0,
+            ..ToneMapping::default()
+        };
+        let darker = ToneMapping {
+            exposure: -2.0,
+            ..ToneMapping::default()
+        };
+        let color = Color::new(0.1, 0.2, 0.4);
+        assert_relative_eq!(
+            brighter.apply(&color),
+            ToneMapping::default().apply(&(color * 2.0))
+        );
+        assert_relative_eq!(
+            darker.apply(&color),
+            ToneMapping::default().apply(&(color * 0.25))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Renders through the public API only, as a crate depending on the tracer
+//! would.
+
+use std::path::Path;
+
+use raytracer::{
+    render,
+    scene::{
+        scene::Scene,
+        scene_file::SceneDescription,
+        settings::{Region, RenderSettings},
+    },
+    util::{
+        color::Color,
+        image::{read_pfm, read_ppm, Dither, ImageFormat, Quantizer},
+    },
+};
+
+fn small_settings() -> RenderSettings {
+    RenderSettings {
+        width: 24,
+        aspect_ratio: 3.0 / 2.0,
+        samples_per_pixel: 4,
+        max_depth: 8,
+        ..RenderSettings::default()
+    }
+}
+
+#[test]
+fn renders_built_in_scene() {
+    let settings = small_settings();
+    let scene = Scene::two_balls(settings.aspect_ratio);
+    let framebuffer = render(&scene, &settings);
+    assert_eq!(24, framebuffer.width());
+    assert_eq!(16, framebuffer.height());
+    // The sky lights every pixel.
+    for y in 0..framebuffer.height() {
+        for x in 0..framebuffer.width() {
+            let pixel = framebuffer.pixel(x, y);
+            assert!(pixel.x() > 0.0 && pixel.y() > 0.0 && pixel.z() > 0.0);
+        }
+    }
+}
+
+#[test]
+fn same_seed_gives_same_image() {
+    let settings = small_settings();
+    let scene = Scene::two_balls(settings.aspect_ratio);
+    assert_eq!(render(&scene, &settings), render(&scene, &settings));
+    let other = RenderSettings {
+        seed: 1,
+        ..small_settings()
+    };
+    assert_ne!(render(&scene, &settings), render(&scene, &other));
+}
+
+#[test]
+fn renders_only_the_region() {
+    let settings = RenderSettings {
+        region: Some(Region {
+            x: 4,
+            y: 2,
+            width: 8,
+            height: 6,
+        }),
+        ..small_settings()
+    };
+    let scene = Scene::two_balls(settings.aspect_ratio);
+    let framebuffer = render(&scene, &settings);
+    for y in 0..framebuffer.height() {
+        for x in 0..framebuffer.width() {
+            let inside = (4..12).contains(&x) && (2..8).contains(&y);
+            let black = framebuffer.pixel(x, y) == Color::new(0.0, 0.0, 0.0);
+            assert_eq!(inside, !black, "pixel {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn renders_scene_file() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/cornell_box.scene");
+    let description = SceneDescription::load(path).unwrap();
+    let mut settings = RenderSettings::default();
+    description.apply_settings(&mut settings);
+    settings.width = 20;
+    settings.samples_per_pixel = 2;
+    let scene = description.into_scene(settings.aspect_ratio);
+    let framebuffer = render(&scene, &settings);
+    assert_eq!(20, framebuffer.width());
+    assert_eq!(settings.height() as usize, framebuffer.height());
+}
+
+#[test]
+fn written_images_read_back() {
+    let settings = small_settings();
+    let framebuffer = render(&Scene::two_balls(settings.aspect_ratio), &settings);
+
+    let mut bytes = Vec::new();
+    ImageFormat::Pfm
+        .writer(Quantizer::new(Dither::None))
+        .write_image(&framebuffer.to_image(), &mut bytes)
+        .unwrap();
+    let image = read_pfm(&mut bytes.as_slice()).unwrap();
+    assert_eq!((24, 16), (image.width(), image.height()));
+    let (rendered, read) = (framebuffer.pixel(5, 5), image.pixel(5, 5));
+    assert!((rendered.y() - read.y()).abs() < 1e-6);
+
+    let mut bytes = Vec::new();
+    ImageFormat::BinaryPpm
+        .writer(Quantizer::new(Dither::None))
+        .write_image(
+            &framebuffer.to_display_image(&settings.tone_mapping),
+            &mut bytes,
+        )
+        .unwrap();
+    let image = read_ppm(&mut bytes.as_slice()).unwrap();
+    assert_eq!((24, 16), (image.width(), image.height()));
+}